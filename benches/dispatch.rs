@@ -0,0 +1,172 @@
+/*
+ * The MIT License (MIT)
+ *
+ * Copyright (c) 2015 Mattis Marjak (mattis.marjak@gmail.com)
+ *
+ * Permission is hereby granted, free of charge, to any person obtaining a copy
+ * of this software and associated documentation files (the "Software"), to deal
+ * in the Software without restriction, including without limitation the rights
+ * to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+ * copies of the Software, and to permit persons to whom the Software is
+ * furnished to do so, subject to the following conditions:
+ *
+ * The above copyright notice and this permission notice shall be included in all
+ * copies or substantial portions of the Software.
+ *
+ * THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+ * IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+ * FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+ * AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+ * LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+ * OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+ * SOFTWARE.
+ */
+
+//! Compares the generated `match` dispatch with dispatch through `lookup`
+//! trait objects on an eight level hierarchy, where every event is probed
+//! on each level before the root handles it. A twenty four level hierarchy
+//! is run once with every level probed and once with the levels declaring
+//! with `handles` that they leave the event to the root, so dispatch jumps
+//! straight there.
+//!
+//! Run with `cargo bench --bench dispatch`.
+
+#[macro_use]
+extern crate hsm;
+
+use std::hint::black_box;
+use std::time::{Duration, Instant};
+use hsm::{State, StateLookup, StateMachine};
+
+
+const EVENTS: u32 = 1_000_000;
+
+#[derive(Debug, Clone)]
+pub enum Events {
+    Ping,
+    Pong,
+}
+
+hsm_define_objects!(StateStruct, States, Events, u64, (
+    L0 {}, L1 {}, L2 {}, L3 {}, L4 {}, L5 {}, L6 {}, L7 {}
+));
+
+hsm_state_parents!(States ;
+    L0 -> None, L1 -> L0, L2 -> L1, L3 -> L2, L4 -> L3, L5 -> L4, L6 -> L5, L7 -> L6
+);
+
+hsm_impl_state!(L0, Events, States, u64, count, evt, probe,
+    hsm::Event::User(Events::Ping) => { *count += 1; hsm::Action::Ignore },
+    _ => hsm::Action::Ignore
+);
+
+macro_rules! passing_state {
+    ($($s:ident),*) => {
+        $(hsm_impl_state!($s, Events, States, u64,
+            hsm::Event::User(_) => hsm::Action::Parent,
+            _ => hsm::Action::Ignore
+        );)*
+    }
+}
+passing_state!(L1, L2, L3, L4, L5, L6, L7);
+
+hsm_define_objects!(ProbedStruct, ProbedStates, Events, u64, (
+    P0 {}, P1 {}, P2 {}, P3 {}, P4 {}, P5 {}, P6 {}, P7 {},
+    P8 {}, P9 {}, P10 {}, P11 {}, P12 {}, P13 {}, P14 {}, P15 {},
+    P16 {}, P17 {}, P18 {}, P19 {}, P20 {}, P21 {}, P22 {}, P23 {}
+));
+
+hsm_state_parents!(ProbedStates ;
+    P0 -> None, P1 -> P0, P2 -> P1, P3 -> P2, P4 -> P3, P5 -> P4,
+    P6 -> P5, P7 -> P6, P8 -> P7, P9 -> P8, P10 -> P9, P11 -> P10,
+    P12 -> P11, P13 -> P12, P14 -> P13, P15 -> P14, P16 -> P15, P17 -> P16,
+    P18 -> P17, P19 -> P18, P20 -> P19, P21 -> P20, P22 -> P21, P23 -> P22
+);
+
+hsm_define_objects!(DeclaredStruct, DeclaredStates, Events, u64, (
+    D0 {}, D1 {}, D2 {}, D3 {}, D4 {}, D5 {}, D6 {}, D7 {},
+    D8 {}, D9 {}, D10 {}, D11 {}, D12 {}, D13 {}, D14 {}, D15 {},
+    D16 {}, D17 {}, D18 {}, D19 {}, D20 {}, D21 {}, D22 {}, D23 {}
+));
+
+hsm_state_parents!(DeclaredStates ;
+    D0 -> None, D1 -> D0, D2 -> D1, D3 -> D2, D4 -> D3, D5 -> D4,
+    D6 -> D5, D7 -> D6, D8 -> D7, D9 -> D8, D10 -> D9, D11 -> D10,
+    D12 -> D11, D13 -> D12, D14 -> D13, D15 -> D14, D16 -> D15, D17 -> D16,
+    D18 -> D17, D19 -> D18, D20 -> D19, D21 -> D20, D22 -> D21, D23 -> D22
+);
+
+macro_rules! root_state {
+    ($($s:ident : $states:ty),*) => {
+        $(hsm_impl_state!($s, Events, $states, u64, count, evt, probe,
+            hsm::Event::User(Events::Ping) => { *count += 1; hsm::Action::Ignore },
+            _ => hsm::Action::Ignore
+        );)*
+    }
+}
+root_state!(P0 : ProbedStates, D0 : DeclaredStates);
+
+macro_rules! probed_state {
+    ($($s:ident),*) => {
+        $(hsm_impl_state!($s, Events, ProbedStates, u64,
+            hsm::Event::User(_) => hsm::Action::Parent,
+            _ => hsm::Action::Ignore
+        );)*
+    }
+}
+probed_state!(P1, P2, P3, P4, P5, P6, P7, P8, P9, P10, P11, P12, P13, P14, P15, P16, P17, P18, P19, P20, P21, P22, P23);
+
+macro_rules! declared_state {
+    ($($s:ident),*) => {
+        $(hsm_impl_state!($s, Events, DeclaredStates, u64, handles Events::Pong,
+            hsm::Event::User(_) => hsm::Action::Parent,
+            _ => hsm::Action::Ignore
+        );)*
+    }
+}
+declared_state!(D1, D2, D3, D4, D5, D6, D7, D8, D9, D10, D11, D12, D13, D14, D15, D16, D17, D18, D19, D20, D21, D22, D23);
+
+// Only implements the required methods, so every call goes through `lookup`
+#[derive(Debug)]
+struct Dynamic(StateStruct);
+impl StateLookup<States, Events, u64> for Dynamic {
+    fn lookup(&mut self, typ: &States) -> &mut dyn State<Events, States, u64> {
+        self.0.lookup(typ)
+    }
+    fn parent(&self, typ: &States) -> Option<States> {
+        self.0.parent(typ)
+    }
+    fn states(&self) -> Vec<States> {
+        self.0.states()
+    }
+}
+
+fn run<S, E>(mut sm: StateMachine<S, E, Events, u64>) -> Duration
+    where S: std::fmt::Debug + StateLookup<E, Events, u64>,
+          E: std::fmt::Debug + Eq + Clone
+{
+    sm.start();
+    let start = Instant::now();
+    for _ in 0..EVENTS {
+        sm.input(black_box(Events::Ping)).unwrap();
+    }
+    let elapsed = start.elapsed();
+    assert_eq!(*sm.shared_data(), EVENTS as u64);
+    elapsed
+}
+
+fn report(name: &str, elapsed: Duration) {
+    println!("{:<10} {:>8.1} ns/event", name, elapsed.as_nanos() as f64 / EVENTS as f64);
+}
+
+fn main() {
+    let generated = StateMachine::with_states(States::L7, <StateStruct as hsm::Initializer>::new(), 0);
+    let dynamic = StateMachine::with_states(States::L7, Dynamic(<StateStruct as hsm::Initializer>::new()), 0);
+    report("match", run(generated));
+    report("lookup", run(dynamic));
+
+    let probed = StateMachine::with_states(ProbedStates::P23, <ProbedStruct as hsm::Initializer>::new(), 0);
+    let declared = StateMachine::with_states(DeclaredStates::D23, <DeclaredStruct as hsm::Initializer>::new(), 0);
+    report("probed", run(probed));
+    report("declared", run(declared));
+}
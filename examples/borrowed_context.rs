@@ -0,0 +1,77 @@
+/*
+ * The MIT License (MIT)
+ *
+ * Copyright (c) 2015 Mattis Marjak (mattis.marjak@gmail.com)
+ *
+ * Permission is hereby granted, free of charge, to any person obtaining a copy
+ * of this software and associated documentation files (the "Software"), to deal
+ * in the Software without restriction, including without limitation the rights
+ * to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+ * copies of the Software, and to permit persons to whom the Software is
+ * furnished to do so, subject to the following conditions:
+ *
+ * The above copyright notice and this permission notice shall be included in all
+ * copies or substantial portions of the Software.
+ *
+ * THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+ * IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+ * FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+ * AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+ * LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+ * OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+ * SOFTWARE.
+ */
+
+#[macro_use]
+extern crate hsm;
+
+
+#[derive(Debug, Clone)]
+pub enum Events {
+    Open,
+    Write(&'static str),
+    Close,
+}
+
+// Owned by the application, only lent to the state machine while it handles an event
+#[derive(Debug)]
+pub struct Connection {
+    log: Vec<String>
+}
+
+hsm_define_objects_noparents!(StateStruct, States, Events, Connection, (
+    Closed,
+    Opened
+));
+
+hsm_impl_state!(Closed, Events, States, Connection, conn, evt, probe,
+    hsm::Event::User(Events::Open) => {
+        conn.log.push("open".to_string());
+        hsm::Action::Transition(States::Opened)
+    },
+    _ => hsm::Action::Ignore
+);
+
+hsm_impl_state!(Opened, Events, States, Connection, conn, evt, probe,
+    hsm::Event::User(Events::Write(data)) => {
+        conn.log.push(data.to_string());
+        hsm::Action::Ignore
+    },
+    hsm::Event::User(Events::Close) => hsm::Action::Transition(States::Closed),
+    hsm::Event::Exit(_) => {
+        conn.log.push("close".to_string());
+        hsm::Action::Ignore
+    },
+    _ => hsm::Action::Ignore
+);
+
+fn main() {
+    let mut conn = Connection { log: Vec::new() };
+    let mut sm = hsm::DetachedStateMachine::<StateStruct, States, Events, Connection>::new(States::Closed);
+    sm.start_with(&mut conn);
+    sm.input_with(&mut conn, Events::Open).unwrap();
+    sm.input_with(&mut conn, Events::Write("hello")).unwrap();
+    sm.input_with(&mut conn, Events::Close).unwrap();
+    println!("{:?}", conn.log);
+    assert_eq!(conn.log, ["open", "hello", "close"]);
+}
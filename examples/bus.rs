@@ -0,0 +1,177 @@
+/*
+ * The MIT License (MIT)
+ *
+ * Copyright (c) 2015 Mattis Marjak (mattis.marjak@gmail.com)
+ *
+ * Permission is hereby granted, free of charge, to any person obtaining a copy
+ * of this software and associated documentation files (the "Software"), to deal
+ * in the Software without restriction, including without limitation the rights
+ * to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+ * copies of the Software, and to permit persons to whom the Software is
+ * furnished to do so, subject to the following conditions:
+ *
+ * The above copyright notice and this permission notice shall be included in all
+ * copies or substantial portions of the Software.
+ *
+ * THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+ * IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+ * FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+ * AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+ * LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+ * OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+ * SOFTWARE.
+ */
+
+
+#[macro_use]
+extern crate hsm;
+
+use hsm::bus::{Address, EventBus, Publisher};
+
+
+// Messages on the bus, each machine decodes the ones it cares about
+#[derive(Debug, Clone)]
+pub enum Msg {
+    Ping(u32),
+    Pong(u32),
+    Log(String),
+}
+
+mod pinger {
+    use hsm;
+    use hsm::bus::Publisher;
+    use super::Msg;
+
+    #[derive(Debug, Clone)]
+    pub enum Events {
+        Pong(u32),
+    }
+
+    #[derive(Debug)]
+    pub struct Shared {
+        pub bus: Publisher<Msg>,
+    }
+
+    hsm_define_objects_noparents!(StateStruct, States, Events, Shared, (
+        Playing,
+        Done
+    ));
+
+    hsm_impl_state!(Playing, Events, States, Shared, shr, evt, probe,
+        hsm::Event::User(Events::Pong(n)) => {
+            if n >= 3 {
+                shr.bus.publish("log", Msg::Log(format!("pinger done after {}", n)));
+                hsm::Action::Transition(States::Done)
+            } else {
+                shr.bus.to_machine("ponger", Msg::Ping(n + 1));
+                hsm::Action::Ignore
+            }
+        },
+        _ => hsm::Action::Ignore
+    );
+
+    hsm_impl_state!(Done, Events, States, Shared, shr, evt, probe,
+        _ => hsm::Action::Ignore
+    );
+
+    pub fn decode(msg: &Msg) -> Option<Events> {
+        match *msg {
+            Msg::Pong(n) => Some(Events::Pong(n)),
+            _            => None
+        }
+    }
+}
+
+mod ponger {
+    use hsm;
+    use hsm::bus::Publisher;
+    use super::Msg;
+
+    #[derive(Debug, Clone)]
+    pub enum Events {
+        Ping(u32),
+    }
+
+    #[derive(Debug)]
+    pub struct Shared {
+        pub bus: Publisher<Msg>,
+    }
+
+    hsm_define_objects_noparents!(StateStruct, States, Events, Shared, (
+        Waiting
+    ));
+
+    hsm_impl_state!(Waiting, Events, States, Shared, shr, evt, probe,
+        hsm::Event::User(Events::Ping(n)) => {
+            shr.bus.publish("log", Msg::Log(format!("ping {}", n)));
+            shr.bus.to_machine("pinger", Msg::Pong(n));
+            hsm::Action::Ignore
+        },
+        _ => hsm::Action::Ignore
+    );
+
+    pub fn decode(msg: &Msg) -> Option<Events> {
+        match *msg {
+            Msg::Ping(n) => Some(Events::Ping(n)),
+            _            => None
+        }
+    }
+}
+
+mod logger {
+    use hsm;
+    use super::Msg;
+
+    #[derive(Debug, Clone)]
+    pub enum Events {
+        Line(String),
+    }
+
+    #[derive(Debug)]
+    pub struct Shared {
+        pub lines: Vec<String>,
+    }
+
+    hsm_define_objects_noparents!(StateStruct, States, Events, Shared, (
+        Logging
+    ));
+
+    hsm_impl_state!(Logging, Events, States, Shared, shr, evt, probe,
+        hsm::Event::User(Events::Line(ref line)) => {
+            shr.lines.push(line.clone());
+            hsm::Action::Ignore
+        },
+        _ => hsm::Action::Ignore
+    );
+
+    pub fn decode(msg: &Msg) -> Option<Events> {
+        match *msg {
+            Msg::Log(ref line) => Some(Events::Line(line.clone())),
+            _                  => None
+        }
+    }
+}
+
+fn main() {
+    let mut bus = EventBus::new();
+    let publisher: Publisher<Msg> = bus.publisher();
+
+    bus.subscribe("pinger", &[],
+        hsm::StateMachine::<pinger::StateStruct, _, _, _>::new(pinger::States::Playing, pinger::Shared { bus: publisher.clone() }),
+        pinger::decode).unwrap();
+    bus.subscribe("ponger", &[],
+        hsm::StateMachine::<ponger::StateStruct, _, _, _>::new(ponger::States::Waiting, ponger::Shared { bus: publisher.clone() }),
+        ponger::decode).unwrap();
+    bus.subscribe("logger", &["log"],
+        hsm::StateMachine::<logger::StateStruct, _, _, _>::new(logger::States::Logging, logger::Shared { lines: Vec::new() }),
+        logger::decode).unwrap();
+
+    bus.send(Address::Machine("ponger".to_string()), Msg::Ping(1));
+    bus.run().unwrap();
+
+    assert_eq!(bus.current("pinger"), Some("Done".to_string()));
+    let logger = bus.machine_mut::<logger::StateStruct, logger::States, logger::Events, logger::Shared>("logger").unwrap();
+    let lines = &logger.shared_data().lines;
+    println!("{:?}", lines);
+    assert_eq!(*lines, ["ping 1", "ping 2", "ping 3", "pinger done after 3"]);
+}
@@ -0,0 +1,96 @@
+/*
+ * The MIT License (MIT)
+ *
+ * Copyright (c) 2015 Mattis Marjak (mattis.marjak@gmail.com)
+ *
+ * Permission is hereby granted, free of charge, to any person obtaining a copy
+ * of this software and associated documentation files (the "Software"), to deal
+ * in the Software without restriction, including without limitation the rights
+ * to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+ * copies of the Software, and to permit persons to whom the Software is
+ * furnished to do so, subject to the following conditions:
+ *
+ * The above copyright notice and this permission notice shall be included in all
+ * copies or substantial portions of the Software.
+ *
+ * THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+ * IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+ * FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+ * AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+ * LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+ * OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+ * SOFTWARE.
+ */
+
+
+#[macro_use]
+extern crate hsm;
+
+use hsm::ConfigInitializer;
+
+
+#[derive(Debug)]
+pub struct Settings {
+    retries : u32,
+    banner  : String,
+}
+
+#[derive(Debug, Clone)]
+pub enum Events {
+    Fail,
+    Reset,
+}
+
+#[derive(Debug)]
+pub struct Shared {
+    banner: String,
+}
+impl ConfigInitializer<Settings> for Shared {
+    fn with_config(config: &Settings) -> Self {
+        Shared { banner: config.banner.clone() }
+    }
+}
+
+// Field defaults may refer to `cfg`, the value given to StateMachine::from_config
+hsm_define_objects_noparents!(StateStruct, States, Events, Shared, (
+    Trying {
+        left: u32 = cfg.retries
+    },
+    GaveUp {}
+) with cfg: Settings);
+
+hsm_impl_state!(Trying, Events, States, Shared, self, shr, evt, probe,
+    hsm::Event::User(Events::Fail) => {
+        if self.left == 0 {
+            hsm::Action::Transition(States::GaveUp)
+        } else {
+            self.left -= 1;
+            hsm::Action::Ignore
+        }
+    },
+    _ => hsm::Action::Ignore
+);
+
+hsm_impl_state!(GaveUp, Events, States, Shared, shr, evt, probe,
+    hsm::Event::Enter(_) => {
+        println!("{}: giving up", shr.banner);
+        hsm::Action::Ignore
+    },
+    hsm::Event::User(Events::Reset) => hsm::Action::Transition(States::Trying),
+    _ => hsm::Action::Ignore
+);
+
+fn main() {
+    let settings = Settings { retries: 2, banner: "worker".to_string() };
+    let mut sm = hsm::StateMachine::<StateStruct, States, Events, Shared>::from_config(States::Trying, &settings);
+    sm.start();
+    for _ in 0..3 {
+        sm.input(Events::Fail).unwrap();
+    }
+    assert_eq!(*sm.current(), States::GaveUp);
+
+    sm.reset_with_config(&Settings { retries: 0, banner: "worker".to_string() });
+    sm.start();
+    sm.input(Events::Fail).unwrap();
+    assert_eq!(*sm.current(), States::GaveUp);
+}
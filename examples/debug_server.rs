@@ -0,0 +1,89 @@
+/*
+ * The MIT License (MIT)
+ *
+ * Copyright (c) 2015 Mattis Marjak (mattis.marjak@gmail.com)
+ *
+ * Permission is hereby granted, free of charge, to any person obtaining a copy
+ * of this software and associated documentation files (the "Software"), to deal
+ * in the Software without restriction, including without limitation the rights
+ * to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+ * copies of the Software, and to permit persons to whom the Software is
+ * furnished to do so, subject to the following conditions:
+ *
+ * The above copyright notice and this permission notice shall be included in all
+ * copies or substantial portions of the Software.
+ *
+ * THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+ * IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+ * FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+ * AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+ * LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+ * OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+ * SOFTWARE.
+ */
+
+#[macro_use]
+extern crate hsm;
+
+use std::io::{BufRead, BufReader, Write};
+use std::net::TcpStream;
+use std::thread;
+use std::time::Duration;
+use hsm::debug_server::DebugServer;
+
+
+#[derive(Debug, Clone)]
+pub enum Events {
+    Open,
+    Close,
+}
+
+hsm_define_objects_noparents!(StateStruct, States, Events, (), (
+    Closed,
+    Opened
+));
+
+hsm_impl_state!(Closed, Events, States, (),
+    hsm::Event::User(Events::Open) => hsm::Action::Transition(States::Opened),
+    _ => hsm::Action::Ignore
+);
+
+hsm_impl_state!(Opened, Events, States, (),
+    hsm::Event::User(Events::Close) => hsm::Action::Transition(States::Closed),
+    _ => hsm::Action::Ignore
+);
+
+fn parse(name: &str) -> Option<Events> {
+    match name {
+        "Open"  => Some(Events::Open),
+        "Close" => Some(Events::Close),
+        _       => None
+    }
+}
+
+fn main() {
+    let server = DebugServer::bind_tcp("127.0.0.1:0").unwrap();
+    let addr = server.tcp_addr().unwrap();
+    let mut sm = hsm::StateMachine::<StateStruct, States, Events, ()>::new(States::Closed, ());
+    sm.add_listener(Box::new(server.listener()));
+    sm.start();
+
+    // Stands in for e.g. `nc 127.0.0.1 <port>`
+    let client = thread::spawn(move || {
+        let mut stream = TcpStream::connect(addr).unwrap();
+        stream.write_all(b"{\"cmd\": \"inject\", \"event\": \"Open\"}\n").unwrap();
+        stream.write_all(b"{\"cmd\": \"query\"}\n").unwrap();
+        let lines: Vec<String> = BufReader::new(stream).lines().take(7).map(|l| l.unwrap()).collect();
+        lines
+    });
+
+    let mut handled = 0;
+    while handled < 2 {
+        handled += server.poll(&mut sm, parse);
+        thread::sleep(Duration::from_millis(10));
+    }
+    for line in client.join().unwrap() {
+        println!("{}", line);
+    }
+    assert_eq!(*sm.current(), States::Opened);
+}
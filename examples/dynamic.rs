@@ -0,0 +1,96 @@
+/*
+ * The MIT License (MIT)
+ *
+ * Copyright (c) 2015 Mattis Marjak (mattis.marjak@gmail.com)
+ *
+ * Permission is hereby granted, free of charge, to any person obtaining a copy
+ * of this software and associated documentation files (the "Software"), to deal
+ * in the Software without restriction, including without limitation the rights
+ * to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+ * copies of the Software, and to permit persons to whom the Software is
+ * furnished to do so, subject to the following conditions:
+ *
+ * The above copyright notice and this permission notice shall be included in all
+ * copies or substantial portions of the Software.
+ *
+ * THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+ * IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+ * FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+ * AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+ * LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+ * OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+ * SOFTWARE.
+ */
+
+extern crate hsm;
+
+use hsm::{Action, Event, InputError, Name, State};
+use hsm::dynamic::{DynamicBuilder, StateId};
+
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Events {
+    Toggle,
+    Reset,
+    Home,
+}
+
+// A state whose transition target is only known at runtime
+#[derive(Debug)]
+struct Toggle {
+    next: &'static str
+}
+impl Name for Toggle {
+    fn name(&self) -> &'static str {
+        "Toggle"
+    }
+}
+impl State<Events, StateId, u32> for Toggle {
+    fn handle_event(&mut self, count: &mut u32, evt: &Event<Events, StateId>, _: bool) -> Action<StateId> {
+        match *evt {
+            Event::Enter(_)              => { *count += 1; Action::Ignore },
+            Event::User(Events::Toggle)  => Action::Transition(self.next.into()),
+            _                            => Action::Parent
+        }
+    }
+}
+
+#[derive(Debug)]
+struct Root;
+impl Name for Root {
+    fn name(&self) -> &'static str {
+        "Root"
+    }
+}
+impl State<Events, StateId, u32> for Root {
+    fn handle_event(&mut self, _: &mut u32, evt: &Event<Events, StateId>, _: bool) -> Action<StateId> {
+        match *evt {
+            Event::User(Events::Reset) => Action::Transition("Off".into()),
+            Event::User(Events::Home)  => Action::Transition("Root".into()),
+            _                          => Action::Ignore
+        }
+    }
+}
+
+fn main() {
+    let mut sm = DynamicBuilder::new()
+        .state("Root", None, Root)
+        .state("Off", Some("Root"), Toggle { next: "On" })
+        .state("On", Some("Root"), Toggle { next: "Off" })
+        .initial("Off")
+        .build(0)
+        .unwrap();
+    sm.start();
+    sm.input(Events::Toggle).unwrap();
+    sm.input(Events::Toggle).unwrap();
+    sm.input(Events::Toggle).unwrap();
+    sm.input(Events::Reset).unwrap();
+    let count = *sm.shared_data();
+    println!("{} entered {} times", sm.current(), count);
+    assert_eq!(count, 5);
+
+    // Root has children but no initial child, a leaf only machine refuses to stop in it
+    sm.set_leaf_only(true);
+    assert_eq!(sm.input(Events::Home), Err(InputError::CompositeTarget("Root".into())));
+    assert_eq!(sm.current().name(), "Off");
+}
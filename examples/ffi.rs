@@ -0,0 +1,83 @@
+/*
+ * The MIT License (MIT)
+ *
+ * Copyright (c) 2015 Mattis Marjak (mattis.marjak@gmail.com)
+ *
+ * Permission is hereby granted, free of charge, to any person obtaining a copy
+ * of this software and associated documentation files (the "Software"), to deal
+ * in the Software without restriction, including without limitation the rights
+ * to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+ * copies of the Software, and to permit persons to whom the Software is
+ * furnished to do so, subject to the following conditions:
+ *
+ * The above copyright notice and this permission notice shall be included in all
+ * copies or substantial portions of the Software.
+ *
+ * THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+ * IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+ * FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+ * AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+ * LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+ * OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+ * SOFTWARE.
+ */
+
+#[macro_use]
+extern crate hsm;
+
+
+#[derive(Debug, Clone)]
+pub enum Events {
+    Coin(u8),
+    Push,
+}
+
+#[derive(Debug)]
+pub struct Till {
+    coins: u32
+}
+
+hsm_define_objects_noparents!(StateStruct, States, Events, Till, (
+    Locked,
+    Unlocked
+));
+
+hsm_impl_state!(Locked, Events, States, Till, till, evt, probe,
+    hsm::Event::User(Events::Coin(n)) => {
+        till.coins += n as u32;
+        hsm::Action::Transition(States::Unlocked)
+    },
+    _ => hsm::Action::Ignore
+);
+
+hsm_impl_state!(Unlocked, Events, States, Till, till, evt, probe,
+    hsm::Event::User(Events::Push) => hsm::Action::Transition(States::Locked),
+    _ => hsm::Action::Ignore
+);
+
+// Exports turnstile_new, turnstile_input, ... to C
+hsm_ffi!(turnstile ; StateStruct, States, Events, Till ;
+    new => hsm::StateMachine::new(States::Locked, Till { coins: 0 }) ;
+    events {
+        COIN = 0 => |data: &[u8]| data.first().map(|n| Events::Coin(*n)),
+        PUSH = 1 => |_| Some(Events::Push)
+    }
+);
+
+fn main() {
+    print!("{}", turnstile::c_header());
+
+    // What a C caller would do
+    unsafe {
+        let m = turnstile::new();
+        assert_eq!(turnstile::start(m), hsm::ffi::OK);
+        assert_eq!(turnstile::current(m), States::Locked as u32);
+        assert_eq!(turnstile::input(m, 0, [2u8].as_ptr(), 1), hsm::ffi::OK);
+        assert_eq!(turnstile::current(m), States::Unlocked as u32);
+        assert_eq!(turnstile::input(m, 0, std::ptr::null(), 0), hsm::ffi::INVALID);
+        assert_eq!(turnstile::input(m, 1, std::ptr::null(), 0), hsm::ffi::OK);
+        assert_eq!(turnstile::current(m), States::Locked as u32);
+        turnstile::free(m);
+        assert_eq!(turnstile::current(std::ptr::null()), hsm::ffi::NO_STATE);
+    }
+}
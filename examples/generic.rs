@@ -0,0 +1,91 @@
+/*
+ * The MIT License (MIT)
+ *
+ * Copyright (c) 2015 Mattis Marjak (mattis.marjak@gmail.com)
+ *
+ * Permission is hereby granted, free of charge, to any person obtaining a copy
+ * of this software and associated documentation files (the "Software"), to deal
+ * in the Software without restriction, including without limitation the rights
+ * to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+ * copies of the Software, and to permit persons to whom the Software is
+ * furnished to do so, subject to the following conditions:
+ *
+ * The above copyright notice and this permission notice shall be included in all
+ * copies or substantial portions of the Software.
+ *
+ * THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+ * IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+ * FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+ * AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+ * LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+ * OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+ * SOFTWARE.
+ */
+
+#[macro_use]
+extern crate hsm;
+
+use std::fmt;
+
+
+#[derive(Debug, Clone)]
+pub enum Events<T> {
+    Push(T),
+    Flush,
+}
+
+#[derive(Debug)]
+pub struct Sink<T> {
+    flushed: Vec<T>
+}
+
+// One definition for buffers of any payload type
+hsm_define_objects!(StateStruct<T>, States, Events<T>, Sink<T>, {
+    Buffer {
+        Empty,
+        Filling {items: Vec<T> = Vec::new()}
+    }
+} where T: fmt::Debug + Clone);
+
+hsm_impl_state!(<T> Buffer<T>, Events<T>, States, Sink<T>,
+    _ => hsm::Action::Ignore
+);
+
+hsm_impl_state!(<T> where [T: Clone] Empty<T>, Events<T>, States, Sink<T>, shr, evt, probe,
+    hsm::Event::User(Events::Push(_)) => hsm::Action::Transition(States::Filling),
+    _ => hsm::Action::Parent
+);
+
+hsm_impl_state!(<T> where [T: Clone] Filling<T>, Events<T>, States, Sink<T>, self, sink, evt, probe,
+    hsm::Event::Enter(ref t) => {
+        if let Some(Events::Push(ref item)) = t.trigger {
+            self.items.push(item.clone());
+        }
+        hsm::Action::Ignore
+    },
+    hsm::Event::User(Events::Push(ref item)) => {
+        self.items.push(item.clone());
+        hsm::Action::Ignore
+    },
+    hsm::Event::User(Events::Flush) => {
+        sink.flushed.append(&mut self.items);
+        hsm::Action::Transition(States::Empty)
+    },
+    _ => hsm::Action::Parent
+);
+
+fn run<T: fmt::Debug + Clone>(items: Vec<T>) -> Vec<T> {
+    let mut sm = hsm::StateMachine::<StateStruct<T>, States, Events<T>, Sink<T>>::new(States::Buffer, Sink { flushed: Vec::new() });
+    sm.start();
+    for item in items {
+        sm.input(Events::Push(item)).unwrap();
+    }
+    sm.input(Events::Flush).unwrap();
+    assert_eq!(*sm.current(), States::Empty);
+    sm.shared_data().flushed.clone()
+}
+
+fn main() {
+    println!("{:?}", run(vec![1, 2, 3]));
+    println!("{:?}", run(vec!["a", "b"]));
+}
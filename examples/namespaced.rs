@@ -0,0 +1,82 @@
+/*
+ * The MIT License (MIT)
+ *
+ * Copyright (c) 2015 Mattis Marjak (mattis.marjak@gmail.com)
+ *
+ * Permission is hereby granted, free of charge, to any person obtaining a copy
+ * of this software and associated documentation files (the "Software"), to deal
+ * in the Software without restriction, including without limitation the rights
+ * to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+ * copies of the Software, and to permit persons to whom the Software is
+ * furnished to do so, subject to the following conditions:
+ *
+ * The above copyright notice and this permission notice shall be included in all
+ * copies or substantial portions of the Software.
+ *
+ * THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+ * IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+ * FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+ * AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+ * LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+ * OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+ * SOFTWARE.
+ */
+
+#[macro_use]
+extern crate hsm;
+
+
+#[derive(Debug, Clone)]
+pub enum Events {
+    Go,
+    Stop,
+}
+
+// Both machines have an Idle state, each lives in its own module
+hsm_define_objects!(mod motor, StateStruct, States, Events, (), (
+    Powered {},
+    Idle {},
+    Running {}
+));
+hsm_state_parents!(mod motor, States ; Powered -> None, Idle -> Powered, Running -> Powered);
+
+hsm_define_objects_noparents!(mod lamp, StateStruct, States, Events, (), (
+    Idle,
+    On
+));
+
+hsm_impl_state!(motor::Powered, Events, motor::States, (),
+    _ => hsm::Action::Ignore
+);
+
+hsm_impl_state!(motor::Idle, Events, motor::States, (),
+    hsm::Event::User(Events::Go) => hsm::Action::Transition(motor::States::Running),
+    _ => hsm::Action::Parent
+);
+
+hsm_impl_state!(motor::Running, Events, motor::States, (),
+    hsm::Event::User(Events::Stop) => hsm::Action::Transition(motor::States::Idle),
+    _ => hsm::Action::Parent
+);
+
+hsm_impl_state!(lamp::Idle, Events, lamp::States, (),
+    hsm::Event::User(Events::Go) => hsm::Action::Transition(lamp::States::On),
+    _ => hsm::Action::Ignore
+);
+
+hsm_impl_state!(lamp::On, Events, lamp::States, (),
+    hsm::Event::User(Events::Stop) => hsm::Action::Transition(lamp::States::Idle),
+    _ => hsm::Action::Ignore
+);
+
+fn main() {
+    let mut motor = hsm::StateMachine::<motor::StateStruct, motor::States, Events, ()>::new(motor::States::Idle, ());
+    let mut lamp = hsm::StateMachine::<lamp::StateStruct, lamp::States, Events, ()>::new(lamp::States::Idle, ());
+    motor.start();
+    lamp.start();
+    motor.input(Events::Go).unwrap();
+    lamp.input(Events::Go).unwrap();
+    println!("motor {:?}, lamp {}", motor.active_states(), lamp.current());
+    assert_eq!(*motor.current(), motor::States::Running);
+    assert_eq!(*lamp.current(), lamp::States::On);
+}
@@ -0,0 +1,87 @@
+/*
+ * The MIT License (MIT)
+ *
+ * Copyright (c) 2015 Mattis Marjak (mattis.marjak@gmail.com)
+ *
+ * Permission is hereby granted, free of charge, to any person obtaining a copy
+ * of this software and associated documentation files (the "Software"), to deal
+ * in the Software without restriction, including without limitation the rights
+ * to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+ * copies of the Software, and to permit persons to whom the Software is
+ * furnished to do so, subject to the following conditions:
+ *
+ * The above copyright notice and this permission notice shall be included in all
+ * copies or substantial portions of the Software.
+ *
+ * THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+ * IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+ * FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+ * AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+ * LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+ * OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+ * SOFTWARE.
+ */
+
+#[macro_use]
+extern crate hsm;
+
+
+#[derive(Debug, Clone)]
+pub enum Events {
+    Connect,
+    Connected,
+    Drop,
+}
+
+// The first child of a state is the one entered when a transition targets it
+hsm_define_objects!(StateStruct, States, Events, (), {
+    Root {
+        Idle,
+        Active {attempts: u8 = 0} {
+            Connecting,
+            Online
+        }
+    }
+});
+
+hsm_impl_state!(Root, Events, States, (),
+    _ => hsm::Action::Ignore
+);
+
+hsm_impl_state!(Idle, Events, States, (),
+    hsm::Event::User(Events::Connect) => hsm::Action::Transition(States::Active),
+    _ => hsm::Action::Parent
+);
+
+hsm_impl_state!(Active, Events, States, (), self, shr, evt, probe,
+    hsm::Event::Enter(_) => {
+        self.attempts += 1;
+        hsm::Action::Ignore
+    },
+    hsm::Event::User(Events::Drop) => hsm::Action::Transition(States::Idle),
+    _ => hsm::Action::Parent
+);
+
+hsm_impl_state!(Connecting, Events, States, (),
+    hsm::Event::User(Events::Connected) => hsm::Action::Transition(States::Online),
+    _ => hsm::Action::Parent
+);
+
+hsm_impl_state!(Online, Events, States, (),
+    _ => hsm::Action::Parent
+);
+
+fn main() {
+    let mut sm = hsm::StateMachine::<StateStruct, States, Events, ()>::new(States::Root, ());
+    sm.start();
+    assert_eq!(*sm.current(), States::Idle);
+    sm.input(Events::Connect).unwrap();
+    assert_eq!(*sm.current(), States::Connecting);
+    sm.input(Events::Connected).unwrap();
+    println!("{:?}", sm.active_states());
+    for state in sm.all_states() {
+        println!("{:>width$}{}", "", state, width = 2 * state.depth());
+    }
+    sm.input(Events::Drop).unwrap();
+    assert_eq!(*sm.current(), States::Idle);
+}
@@ -30,7 +30,7 @@ extern crate fern;
 extern crate time;
 
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub enum Events {
     Plus,
     Minus,
@@ -45,6 +45,7 @@ pub enum Tokens {
     Int(i32)
 }
 
+#[allow(dead_code)]
 #[derive(Debug)]
 pub struct SharedData {
     accepted_tokens: Vec<Tokens>
@@ -61,26 +62,26 @@ hsm_define_objects_noparents!(StateStruct, States, Events, SharedData, (
     WaitOp {}
 ));
 
-impl hsm::State<Events, States, SharedData> for WaitMinusOrInt {
-    #[allow(unused_variables)]
-    fn handle_event(&mut self, shr_data: &mut SharedData, evt: &hsm::Event<Events>, probe: bool) -> hsm::Action<States> {
+hsm_impl_state!(WaitMinusOrInt, Events, States, SharedData, self, shr_data, evt, probe,
+    pre {
         self.counter += 1;
         info!("{} time in WaitMinusOrInt, shared: {:?}", self.counter, shr_data);
-        match *evt {
-            hsm::Event::User(Events::Minus) => {
-                info!("minus");
-                hsm::Action::Transition(States::WaitInt)
-            },
-            hsm::Event::User(Events::Int(x)) => {
-                info!("int({:?})", x);
-                hsm::Action::Transition(States::WaitOp)
-            },
-            _ => hsm::Action::Ignore
-        }
-    }
-}
+    },
+    hsm::Event::Enter(ref t) => {
+        info!("entered from {:?} on {:?}", t.source, t.trigger);
+        hsm::Action::Ignore
+    },
+    hsm::Event::User(Events::Minus) => {
+        info!("minus");
+        hsm::Action::Transition(States::WaitInt)
+    },
+    hsm::Event::User(Events::Int(x)) => {
+        info!("int({:?})", x);
+        hsm::Action::Transition(States::WaitOp)
+    },
+    _ => hsm::Action::Ignore
+);
 
-// Can't use self
 hsm_impl_state!(WaitInt, Events, States, SharedData, shr, evt, probe,
     hsm::Event::User(Events::Int(x)) => hsm_delayed_transition!(probe, {
         println!("{:?} {:?} {:?}", shr, evt, probe);
@@ -90,8 +91,9 @@ hsm_impl_state!(WaitInt, Events, States, SharedData, shr, evt, probe,
     _ => hsm::Action::Ignore
 );
 
-// Can't use self, shrared_data, event or probe
-hsm_impl_state!(WaitOp, Events, States, SharedData,
+// Without names for them, the arms can't use self, shared data, event or
+// probe. Other user events go straight to the parent without probing
+hsm_impl_state!(WaitOp, Events, States, SharedData, handles Events::Minus | Events::Plus,
     hsm::Event::User(Events::Minus) => {
         info!("minus");
         hsm::Action::Transition(States::WaitMinusOrInt)
@@ -107,24 +109,28 @@ fn main() {
     conf_logger();
     let mut sm = hsm::StateMachine::<StateStruct, States, Events, SharedData>::new(States::WaitMinusOrInt, SharedData::new());
     sm.start();
-    sm.input(Events::Int(4));
-    sm.input(Events::Plus);
-    sm.input(Events::Minus);
-    sm.input(Events::Int(5));
+    sm.input(Events::Int(4)).unwrap();
+    sm.input(Events::Plus).unwrap();
+    sm.input(Events::Minus).unwrap();
+    sm.input(Events::Int(5)).unwrap();
+    // WaitOp passes ints on but has no parent, log them instead of failing
+    sm.set_unhandled_policy(hsm::UnhandledPolicy::Log);
+    sm.input(Events::Int(6)).unwrap();
 }
 
 fn conf_logger() {
-    let logger_config = fern::DispatchConfig {
-        format: Box::new(|msg: &str, level: &log::LogLevel, _location: &log::LogLocation| {
-            let t = time::now();
-            let ms = t.tm_nsec/1000_000;
-            format!("{}.{:3} [{}] {}", t.strftime("%Y-%m-%dT%H:%M:%S").unwrap(), ms, level, msg)
-        }),
-        output: vec![fern::OutputConfig::stderr()],
-        level: log::LogLevelFilter::Trace,
-    };
+    let res = fern::Dispatch::new()
+        .format(|out, msg, record| {
+            let t = time::OffsetDateTime::now_utc();
+            out.finish(format_args!("{:04}-{:02}-{:02}T{:02}:{:02}:{:02}.{:03} [{}] {}",
+                t.year(), t.month() as u8, t.day(), t.hour(), t.minute(), t.second(),
+                t.millisecond(), record.level(), msg))
+        })
+        .level(log::LevelFilter::Trace)
+        .chain(::std::io::stderr())
+        .apply();
 
-    if let Err(e) = fern::init_global_logger(logger_config, log::LogLevelFilter::Trace) {
+    if let Err(e) = res {
         panic!("Failed to initialize global logger: {}", e);
     }
 }
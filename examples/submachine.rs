@@ -0,0 +1,129 @@
+/*
+ * The MIT License (MIT)
+ *
+ * Copyright (c) 2015 Mattis Marjak (mattis.marjak@gmail.com)
+ *
+ * Permission is hereby granted, free of charge, to any person obtaining a copy
+ * of this software and associated documentation files (the "Software"), to deal
+ * in the Software without restriction, including without limitation the rights
+ * to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+ * copies of the Software, and to permit persons to whom the Software is
+ * furnished to do so, subject to the following conditions:
+ *
+ * The above copyright notice and this permission notice shall be included in all
+ * copies or substantial portions of the Software.
+ *
+ * THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+ * IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+ * FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+ * AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+ * LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+ * OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+ * SOFTWARE.
+ */
+
+#[macro_use]
+extern crate hsm;
+
+
+#[derive(Debug, Clone)]
+pub enum Events {
+    Connect,
+    Challenge,
+    Response,
+    LoggedIn,
+    Disconnect,
+}
+
+mod login {
+    use Events as OuterEvents;
+
+    #[derive(Debug, Clone)]
+    pub enum Events {
+        Challenge,
+        Response,
+    }
+
+    hsm_define_objects_noparents!(StateStruct, States, Events, (), (
+        WaitChallenge,
+        WaitResponse,
+        LoggedIn
+    ));
+
+    hsm_impl_state!(WaitChallenge, Events, States, (),
+        ::hsm::Event::User(Events::Challenge) => ::hsm::Action::Transition(States::WaitResponse),
+        _ => ::hsm::Action::Ignore
+    );
+
+    hsm_impl_state!(WaitResponse, Events, States, (),
+        ::hsm::Event::User(Events::Response) => ::hsm::Action::Transition(States::LoggedIn),
+        _ => ::hsm::Action::Ignore
+    );
+
+    hsm_impl_state!(LoggedIn, Events, States, (),
+        _ => ::hsm::Action::Ignore
+    );
+
+    pub type Machine = ::hsm::Submachine<StateStruct, States, Events, (), OuterEvents>;
+
+    pub fn new() -> Machine {
+        ::hsm::Submachine::new(::hsm::StateMachine::new(States::WaitChallenge, ()), forward, complete)
+    }
+
+    fn forward(evt: &OuterEvents) -> Option<Events> {
+        match *evt {
+            OuterEvents::Challenge => Some(Events::Challenge),
+            OuterEvents::Response  => Some(Events::Response),
+            _                      => None
+        }
+    }
+
+    fn complete(state: &States, _: &()) -> Option<OuterEvents> {
+        match *state {
+            States::LoggedIn => Some(OuterEvents::LoggedIn),
+            _                => None
+        }
+    }
+}
+
+hsm_define_objects!(StateStruct, States, Events, (), (
+    Session {},
+    Disconnected {},
+    Login { login: login::Machine = login::new() },
+    Connected {}
+));
+hsm_state_parents!(States; Session -> None, Disconnected -> Session, Login -> Session, Connected -> Session);
+
+hsm_impl_state!(Session, Events, States, (),
+    hsm::Event::User(Events::Disconnect) => hsm::Action::Transition(States::Disconnected),
+    _ => hsm::Action::Parent
+);
+
+hsm_impl_state!(Disconnected, Events, States, (),
+    hsm::Event::User(Events::Connect) => hsm::Action::Transition(States::Login),
+    _ => hsm::Action::Parent
+);
+
+// LoggedIn is taken from the login submachine once it completes
+hsm_impl_submachine!(Login, login, Events, States, (),
+    hsm::Event::User(Events::LoggedIn) => hsm::Action::Transition(States::Connected),
+    _ => hsm::Action::Parent
+);
+
+hsm_impl_state!(Connected, Events, States, (),
+    _ => hsm::Action::Parent
+);
+
+fn main() {
+    let mut sm = hsm::StateMachine::<StateStruct, States, Events, ()>::new(States::Disconnected, ());
+    sm.start();
+    sm.input(Events::Connect).unwrap();
+    sm.input(Events::Challenge).unwrap();
+    println!("{:?}", sm.current());
+    sm.input(Events::Disconnect).unwrap();
+    sm.input(Events::Connect).unwrap();
+    sm.input(Events::Challenge).unwrap();
+    sm.input(Events::Response).unwrap();
+    println!("{:?}", sm.current());
+    assert_eq!(*sm.current(), States::Connected);
+}
@@ -0,0 +1,183 @@
+/*
+ * The MIT License (MIT)
+ *
+ * Copyright (c) 2015 Mattis Marjak (mattis.marjak@gmail.com)
+ *
+ * Permission is hereby granted, free of charge, to any person obtaining a copy
+ * of this software and associated documentation files (the "Software"), to deal
+ * in the Software without restriction, including without limitation the rights
+ * to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+ * copies of the Software, and to permit persons to whom the Software is
+ * furnished to do so, subject to the following conditions:
+ *
+ * The above copyright notice and this permission notice shall be included in all
+ * copies or substantial portions of the Software.
+ *
+ * THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+ * IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+ * FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+ * AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+ * LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+ * OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+ * SOFTWARE.
+ */
+
+//! Pretty-prints `hsm::trace` files and computes per-state statistics.
+//!
+//! ```text
+//! hsm-trace [--stats] [--state NAME] [--event NAME] [FILE]
+//! ```
+//!
+//! Reads standard input when FILE is missing or `-`.
+
+extern crate hsm;
+
+use std::collections::BTreeMap;
+use std::env;
+use std::fs::File;
+use std::io::{self, BufReader};
+use std::process;
+use hsm::trace::{self, Record};
+
+
+const USAGE: &str = "usage: hsm-trace [--stats] [--state NAME] [--event NAME] [FILE]";
+
+#[derive(Debug, Default)]
+struct Options {
+    stats : bool,
+    state : Option<String>,
+    event : Option<String>,
+    file  : Option<String>,
+}
+
+fn parse_args() -> Result<Options, String> {
+    let mut opts = Options::default();
+    let mut args = env::args().skip(1);
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--stats"       => opts.stats = true,
+            "--state"       => opts.state = Some(args.next().ok_or("--state needs a state name")?),
+            "--event"       => opts.event = Some(args.next().ok_or("--event needs an event name")?),
+            "-h" | "--help" => return Err(USAGE.to_string()),
+            _ if arg.starts_with("--") => return Err(format!("unknown option {}\n{}", arg, USAGE)),
+            _ if opts.file.is_none()   => opts.file = Some(arg),
+            _                          => return Err(USAGE.to_string())
+        }
+    }
+    Ok(opts)
+}
+
+// "Digit(3)" matches "Digit"
+fn event_matches(event: &str, name: &str) -> bool {
+    event == name || hsm::variant_name(event) == name
+}
+
+fn touches_state(record: &Record, name: &str) -> bool {
+    record.state.as_deref() == Some(name) || record.current == name
+        || record.exits.iter().any(|s| s == name) || record.enters.iter().any(|s| s == name)
+}
+
+fn matches(opts: &Options, record: &Record) -> bool {
+    opts.state.as_ref().map_or(true, |s| touches_state(record, s))
+        && opts.event.as_ref().map_or(true, |e| record.event.as_ref().is_some_and(|r| event_matches(r, e)))
+}
+
+fn print_record(start: f64, record: &Record) {
+    let mut line = format!("{:>12.6}  ", record.ts - start);
+    match record.event {
+        Some(ref event) if record.unhandled => line.push_str(&format!("{} UNHANDLED at {}", event, record.state.as_deref().unwrap_or("?"))),
+        Some(ref event)                     => line.push_str(&format!("{} by {}", event, record.state.as_deref().unwrap_or("?"))),
+        None if record.is_stop()            => line.push_str("stop"),
+        None                                => line.push_str("start"),
+    }
+    if !record.exits.is_empty() {
+        line.push_str(&format!("  exit [{}]", record.exits.join(", ")));
+    }
+    if !record.enters.is_empty() {
+        line.push_str(&format!("  enter [{}]", record.enters.join(", ")));
+    }
+    line.push_str(&format!("  -> {}", record.current));
+    println!("{}", line);
+}
+
+#[derive(Debug, Default)]
+struct StateStats {
+    handled   : u64,
+    unhandled : u64,
+    entries   : u64,
+    dwell     : f64,
+}
+
+fn print_stats(opts: &Options, records: &[Record]) {
+    let mut states: BTreeMap<&str, StateStats> = BTreeMap::new();
+    let mut events: BTreeMap<&str, u64> = BTreeMap::new();
+    // The current state and since when, while the machine runs
+    let mut active: Option<(&str, f64)> = None;
+    for record in records {
+        if let Some(ref state) = record.state {
+            let stats = states.entry(state).or_default();
+            if record.unhandled { stats.unhandled += 1 } else { stats.handled += 1 }
+        }
+        if let Some(ref event) = record.event {
+            *events.entry(hsm::variant_name(event)).or_insert(0) += 1;
+        }
+        for state in record.enters.iter() {
+            states.entry(state).or_default().entries += 1;
+        }
+        if let Some((state, since)) = active {
+            if record.is_stop() || state != record.current {
+                states.entry(state).or_default().dwell += record.ts - since;
+                active = None;
+            }
+        }
+        if active.is_none() && !record.is_stop() {
+            active = Some((&record.current, record.ts));
+        }
+    }
+    if let (Some((state, since)), Some(last)) = (active, records.last()) {
+        states.entry(state).or_default().dwell += last.ts - since;
+    }
+
+    println!("{:<24} {:>10} {:>10} {:>10} {:>14}", "state", "handled", "unhandled", "entries", "dwell (s)");
+    for (state, stats) in states.iter() {
+        if opts.state.as_ref().map_or(true, |s| s == state) {
+            println!("{:<24} {:>10} {:>10} {:>10} {:>14.6}", state, stats.handled, stats.unhandled, stats.entries, stats.dwell);
+        }
+    }
+    println!();
+    println!("{:<24} {:>10}", "event", "count");
+    for (event, count) in events.iter() {
+        if opts.event.as_ref().map_or(true, |e| e == event) {
+            println!("{:<24} {:>10}", event, count);
+        }
+    }
+}
+
+fn main() {
+    let opts = match parse_args() {
+        Ok(opts) => opts,
+        Err(msg) => {
+            eprintln!("{}", msg);
+            process::exit(2);
+        }
+    };
+    let records = match opts.file.as_deref() {
+        None | Some("-") => trace::read(io::stdin().lock()),
+        Some(path)       => File::open(path).and_then(|f| trace::read(BufReader::new(f))),
+    };
+    let records = match records {
+        Ok(records) => records,
+        Err(e)      => {
+            eprintln!("hsm-trace: {}", e);
+            process::exit(1);
+        }
+    };
+    if opts.stats {
+        print_stats(&opts, &records);
+    } else {
+        let start = records.first().map(|r| r.ts).unwrap_or(0.0);
+        for record in records.iter().filter(|r| matches(&opts, r)) {
+            print_record(start, record);
+        }
+    }
+}
@@ -0,0 +1,293 @@
+/*
+ * The MIT License (MIT)
+ *
+ * Copyright (c) 2015 Mattis Marjak (mattis.marjak@gmail.com)
+ *
+ * Permission is hereby granted, free of charge, to any person obtaining a copy
+ * of this software and associated documentation files (the "Software"), to deal
+ * in the Software without restriction, including without limitation the rights
+ * to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+ * copies of the Software, and to permit persons to whom the Software is
+ * furnished to do so, subject to the following conditions:
+ *
+ * The above copyright notice and this permission notice shall be included in all
+ * copies or substantial portions of the Software.
+ *
+ * THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+ * IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+ * FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+ * AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+ * LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+ * OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+ * SOFTWARE.
+ */
+
+use std::any::Any;
+use std::cell::RefCell;
+use std::collections::VecDeque;
+use std::error::Error;
+use std::fmt;
+use std::rc::Rc;
+use super::{StateLookup, StateMachine};
+
+
+/// Where a message on an `EventBus` goes.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Address {
+    /// The machine subscribed under this name.
+    Machine(String),
+    /// Every machine subscribed to this topic.
+    Topic(String),
+    /// Every machine on the bus.
+    Broadcast,
+}
+
+/// Queues messages on an `EventBus`, clone it into the shared data of the
+/// machines whose handlers publish.
+pub struct Publisher<M> {
+    queue: Rc<RefCell<VecDeque<(Address, M)>>>,
+}
+impl<M> Clone for Publisher<M> {
+    fn clone(&self) -> Self {
+        Publisher { queue: self.queue.clone() }
+    }
+}
+impl<M> fmt::Debug for Publisher<M> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> Result<(), fmt::Error> {
+        write!(f, "Publisher({} queued)", self.queue.borrow().len())
+    }
+}
+impl<M> Publisher<M> {
+    pub fn send(&self, to: Address, msg: M) {
+        self.queue.borrow_mut().push_back((to, msg));
+    }
+
+    pub fn to_machine(&self, name: &str, msg: M) {
+        self.send(Address::Machine(name.to_string()), msg);
+    }
+
+    pub fn publish(&self, topic: &str, msg: M) {
+        self.send(Address::Topic(topic.to_string()), msg);
+    }
+
+    pub fn broadcast(&self, msg: M) {
+        self.send(Address::Broadcast, msg);
+    }
+}
+
+/// A message `EventBus::run` couldn't deliver.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum DeliveryError {
+    /// The named machine's `input` failed.
+    Failed { machine: String, error: String },
+    /// Nothing on the bus has the address, an unknown machine name or a
+    /// topic without subscribers.
+    NoRecipient(Address),
+}
+impl fmt::Display for DeliveryError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> Result<(), fmt::Error> {
+        match *self {
+            DeliveryError::Failed { ref machine, ref error } => write!(f, "machine {}: {}", machine, error),
+            DeliveryError::NoRecipient(ref to)               => write!(f, "no recipient for {:?}", to),
+        }
+    }
+}
+impl Error for DeliveryError {}
+
+/// Why `EventBus::subscribe` refused a machine.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum BusError {
+    /// A machine is already subscribed under the name.
+    Duplicate(String),
+}
+impl fmt::Display for BusError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> Result<(), fmt::Error> {
+        match *self {
+            BusError::Duplicate(ref name) => write!(f, "machine {} is already subscribed to the event bus", name),
+        }
+    }
+}
+impl Error for BusError {}
+
+// A subscribed machine with its event type erased.
+trait Endpoint<M> {
+    fn start(&mut self);
+    fn deliver(&mut self, msg: &M) -> Result<bool, String>;
+    fn current(&self) -> String;
+    fn as_any(&mut self) -> &mut dyn Any;
+}
+
+struct MachineEndpoint<UsrStStr, UsrStEnum, UsrEvtEnum, UsrShrData, M>
+    where UsrStStr:   fmt::Debug,
+          UsrStEnum:  fmt::Debug,
+          UsrEvtEnum: fmt::Debug,
+          UsrShrData: fmt::Debug,
+{
+    machine : StateMachine<UsrStStr, UsrStEnum, UsrEvtEnum, UsrShrData>,
+    decode  : fn(&M) -> Option<UsrEvtEnum>,
+}
+impl<UsrStStr, UsrStEnum, UsrEvtEnum, UsrShrData, M> Endpoint<M> for MachineEndpoint<UsrStStr, UsrStEnum, UsrEvtEnum, UsrShrData, M>
+    where UsrStStr   : fmt::Debug + StateLookup<UsrStEnum, UsrEvtEnum, UsrShrData> + 'static,
+          UsrStEnum  : fmt::Debug + Eq + Clone + 'static,
+          UsrEvtEnum : fmt::Debug + Clone + 'static,
+          UsrShrData : fmt::Debug + 'static,
+{
+    fn start(&mut self) {
+        if !self.machine.is_started() {
+            self.machine.start();
+        }
+    }
+
+    fn deliver(&mut self, msg: &M) -> Result<bool, String> {
+        match (self.decode)(msg) {
+            Some(evt) => self.machine.input(evt).map(|_| true).map_err(|e| e.to_string()),
+            None      => Ok(false)
+        }
+    }
+
+    fn current(&self) -> String {
+        format!("{:?}", self.machine.current())
+    }
+
+    fn as_any(&mut self) -> &mut dyn Any {
+        &mut self.machine
+    }
+}
+
+struct Member<M> {
+    name     : String,
+    topics   : Vec<String>,
+    endpoint : Box<dyn Endpoint<M>>,
+}
+
+/// Routes messages of type `M` between state machines of any type. Each
+/// machine subscribes under a name with a list of topics and a `decode`
+/// function turning bus messages into its own events, messages it doesn't
+/// decode are skipped. Messages are delivered in the order they were sent,
+/// to the recipients in the order they subscribed, and every `input` runs to
+/// completion before the next delivery. Messages published while handling
+/// one are queued behind those already waiting.
+pub struct EventBus<M> {
+    queue   : Rc<RefCell<VecDeque<(Address, M)>>>,
+    members : Vec<Member<M>>,
+}
+impl<M: fmt::Debug> fmt::Debug for EventBus<M> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> Result<(), fmt::Error> {
+        f.debug_struct("EventBus")
+            .field("machines", &self.members.iter().map(|m| (&m.name, m.endpoint.current())).collect::<Vec<_>>())
+            .field("queue", &self.queue.borrow())
+            .finish()
+    }
+}
+impl<M> Default for EventBus<M> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+impl<M> EventBus<M> {
+    pub fn new() -> Self {
+        EventBus {
+            queue   : Rc::new(RefCell::new(VecDeque::new())),
+            members : Vec::new(),
+        }
+    }
+
+    pub fn publisher(&self) -> Publisher<M> {
+        Publisher { queue: self.queue.clone() }
+    }
+
+    /// Adds a machine, it's started by `start` or `run` unless already running.
+    /// Fails with `BusError::Duplicate` if the name is taken.
+    pub fn subscribe<UsrStStr, UsrStEnum, UsrEvtEnum, UsrShrData>(
+        &mut self,
+        name    : &str,
+        topics  : &[&str],
+        machine : StateMachine<UsrStStr, UsrStEnum, UsrEvtEnum, UsrShrData>,
+        decode  : fn(&M) -> Option<UsrEvtEnum>) -> Result<(), BusError>
+        where UsrStStr   : fmt::Debug + StateLookup<UsrStEnum, UsrEvtEnum, UsrShrData> + 'static,
+              UsrStEnum  : fmt::Debug + Eq + Clone + 'static,
+              UsrEvtEnum : fmt::Debug + Clone + 'static,
+              UsrShrData : fmt::Debug + 'static,
+              M          : 'static,
+    {
+        if self.members.iter().any(|m| m.name == name) {
+            return Err(BusError::Duplicate(name.to_string()));
+        }
+        self.members.push(Member {
+            name     : name.to_string(),
+            topics   : topics.iter().map(|t| t.to_string()).collect(),
+            endpoint : Box::new(MachineEndpoint { machine, decode }),
+        });
+        Ok(())
+    }
+
+    pub fn send(&self, to: Address, msg: M) {
+        self.queue.borrow_mut().push_back((to, msg));
+    }
+
+    pub fn start(&mut self) {
+        for member in self.members.iter_mut() {
+            member.endpoint.start();
+        }
+    }
+
+    /// Delivers queued messages until the queue is empty, returns how many
+    /// deliveries were made. A failing `input` or a message without
+    /// recipients doesn't stop the run, the message is dropped and the
+    /// errors are returned in order once the queue is empty.
+    pub fn run(&mut self) -> Result<usize, Vec<DeliveryError>> {
+        self.start();
+        let mut delivered = 0;
+        let mut errors = Vec::new();
+        loop {
+            let next = self.queue.borrow_mut().pop_front();
+            let (to, msg) = match next {
+                Some(next) => next,
+                None       => break
+            };
+            let mut addressed = false;
+            for member in self.members.iter_mut() {
+                let matches = match to {
+                    Address::Machine(ref name) => member.name == *name,
+                    Address::Topic(ref topic)  => member.topics.contains(topic),
+                    Address::Broadcast         => true,
+                };
+                if !matches {
+                    continue;
+                }
+                addressed = true;
+                match member.endpoint.deliver(&msg) {
+                    Ok(true)   => delivered += 1,
+                    Ok(false)  => {},
+                    Err(error) => errors.push(DeliveryError::Failed { machine: member.name.clone(), error }),
+                }
+            }
+            if !addressed {
+                errors.push(DeliveryError::NoRecipient(to));
+            }
+        }
+        if errors.is_empty() {
+            Ok(delivered)
+        } else {
+            Err(errors)
+        }
+    }
+
+    /// Debug formatted current state of the named machine.
+    pub fn current(&self, name: &str) -> Option<String> {
+        self.members.iter().find(|m| m.name == name).map(|m| m.endpoint.current())
+    }
+
+    /// The named machine, if it has the given type.
+    pub fn machine_mut<UsrStStr, UsrStEnum, UsrEvtEnum, UsrShrData>(&mut self, name: &str)
+        -> Option<&mut StateMachine<UsrStStr, UsrStEnum, UsrEvtEnum, UsrShrData>>
+        where UsrStStr   : fmt::Debug + 'static,
+              UsrStEnum  : fmt::Debug + 'static,
+              UsrEvtEnum : fmt::Debug + Clone + 'static,
+              UsrShrData : fmt::Debug + 'static,
+    {
+        self.members.iter_mut()
+            .find(|m| m.name == name)
+            .and_then(|m| m.endpoint.as_any().downcast_mut())
+    }
+}
@@ -0,0 +1,338 @@
+/*
+ * The MIT License (MIT)
+ *
+ * Copyright (c) 2015 Mattis Marjak (mattis.marjak@gmail.com)
+ *
+ * Permission is hereby granted, free of charge, to any person obtaining a copy
+ * of this software and associated documentation files (the "Software"), to deal
+ * in the Software without restriction, including without limitation the rights
+ * to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+ * copies of the Software, and to permit persons to whom the Software is
+ * furnished to do so, subject to the following conditions:
+ *
+ * The above copyright notice and this permission notice shall be included in all
+ * copies or substantial portions of the Software.
+ *
+ * THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+ * IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+ * FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+ * AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+ * LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+ * OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+ * SOFTWARE.
+ */
+
+//! Loads a `DynamicStateMachine` from a JSON or TOML definition. Every state
+//! is a transition table, guards and actions are looked up by name in a
+//! `Registry` and user events are matched by their `Name`. Events no row
+//! matches go to the parent state, and from the root to the machine's
+//! `UnhandledPolicy`.
+//!
+//! ```toml
+//! initial = "Idle"
+//!
+//! [[states]]
+//! name = "Idle"
+//! enter = "log"
+//! transitions = [ { event = "Start", guard = "ready", action = "count", target = "Active" } ]
+//!
+//! [[states]]
+//! name = "Active"
+//! ```
+
+use std::collections::HashMap;
+use std::error::Error;
+use std::fmt;
+use std::rc::Rc;
+use super::{Action, Event, Name, State, ValidationError};
+use dynamic::{BuildError, DynamicBuilder, DynamicStateMachine, StateId};
+
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct MachineDef {
+    initial : String,
+    states  : Vec<StateDef>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct StateDef {
+    name        : String,
+    #[serde(default)]
+    parent      : Option<String>,
+    #[serde(default)]
+    initial     : Option<String>,
+    #[serde(default)]
+    enter       : Option<String>,
+    #[serde(default)]
+    exit        : Option<String>,
+    #[serde(default)]
+    transitions : Vec<TransitionDef>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct TransitionDef {
+    event  : String,
+    #[serde(default)]
+    guard  : Option<String>,
+    #[serde(default)]
+    action : Option<String>,
+    #[serde(default)]
+    target : Option<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum LoadError {
+    Parse(String),
+    Invalid { path: String, message: String },
+}
+impl fmt::Display for LoadError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> Result<(), fmt::Error> {
+        match *self {
+            LoadError::Parse(ref e)                     => write!(f, "parse error: {}", e),
+            LoadError::Invalid { ref path, ref message } => write!(f, "{}: {}", path, message),
+        }
+    }
+}
+impl Error for LoadError {}
+
+fn invalid(path: String, message: String) -> LoadError {
+    LoadError::Invalid { path, message }
+}
+
+type Guard<UsrEvtEnum, UsrShrData> = Rc<dyn Fn(&UsrShrData, &UsrEvtEnum) -> bool>;
+type Callback<UsrEvtEnum, UsrShrData> = Rc<dyn Fn(&mut UsrShrData, &Event<UsrEvtEnum, StateId>)>;
+
+/// Named guards and actions the definition file can refer to. Actions are
+/// used for transitions as well as for `enter` and `exit`.
+pub struct Registry<UsrEvtEnum, UsrShrData>
+    where UsrEvtEnum : fmt::Debug
+{
+    guards  : HashMap<String, Guard<UsrEvtEnum, UsrShrData>>,
+    actions : HashMap<String, Callback<UsrEvtEnum, UsrShrData>>,
+}
+impl<UsrEvtEnum, UsrShrData> fmt::Debug for Registry<UsrEvtEnum, UsrShrData>
+    where UsrEvtEnum : fmt::Debug
+{
+    fn fmt(&self, f: &mut fmt::Formatter) -> Result<(), fmt::Error> {
+        f.debug_struct("Registry")
+            .field("guards", &self.guards.keys().collect::<Vec<_>>())
+            .field("actions", &self.actions.keys().collect::<Vec<_>>())
+            .finish()
+    }
+}
+impl<UsrEvtEnum, UsrShrData> Default for Registry<UsrEvtEnum, UsrShrData>
+    where UsrEvtEnum : fmt::Debug
+{
+    fn default() -> Self {
+        Self::new()
+    }
+}
+impl<UsrEvtEnum, UsrShrData> Registry<UsrEvtEnum, UsrShrData>
+    where UsrEvtEnum : fmt::Debug
+{
+    pub fn new() -> Self {
+        Registry {
+            guards  : HashMap::new(),
+            actions : HashMap::new(),
+        }
+    }
+
+    pub fn guard<F>(mut self, name: &str, guard: F) -> Self
+        where F: Fn(&UsrShrData, &UsrEvtEnum) -> bool + 'static
+    {
+        self.guards.insert(name.to_string(), Rc::new(guard));
+        self
+    }
+
+    pub fn action<F>(mut self, name: &str, action: F) -> Self
+        where F: Fn(&mut UsrShrData, &Event<UsrEvtEnum, StateId>) + 'static
+    {
+        self.actions.insert(name.to_string(), Rc::new(action));
+        self
+    }
+}
+
+struct Row<UsrEvtEnum, UsrShrData>
+    where UsrEvtEnum : fmt::Debug
+{
+    event  : String,
+    guard  : Option<Guard<UsrEvtEnum, UsrShrData>>,
+    action : Option<Callback<UsrEvtEnum, UsrShrData>>,
+    target : Option<StateId>,
+}
+
+struct TableState<UsrEvtEnum, UsrShrData>
+    where UsrEvtEnum : fmt::Debug
+{
+    name    : String,
+    enter   : Option<Callback<UsrEvtEnum, UsrShrData>>,
+    exit    : Option<Callback<UsrEvtEnum, UsrShrData>>,
+    rows    : Vec<Row<UsrEvtEnum, UsrShrData>>,
+    delayed : Option<usize>,
+}
+impl<UsrEvtEnum, UsrShrData> Name for TableState<UsrEvtEnum, UsrShrData>
+    where UsrEvtEnum : fmt::Debug
+{
+    fn name(&self) -> &str {
+        &self.name
+    }
+}
+impl<UsrEvtEnum, UsrShrData> State<UsrEvtEnum, StateId, UsrShrData> for TableState<UsrEvtEnum, UsrShrData>
+    where UsrEvtEnum : fmt::Debug + Name,
+          UsrShrData : fmt::Debug,
+{
+    fn handle_event(&mut self, shr_data: &mut UsrShrData, evt: &Event<UsrEvtEnum, StateId>, probe: bool) -> Action<StateId> {
+        match *evt {
+            Event::Enter(_) => {
+                if let Some(ref enter) = self.enter {
+                    enter(shr_data, evt);
+                }
+                Action::Ignore
+            },
+            Event::Exit(_) => {
+                if let Some(ref exit) = self.exit {
+                    exit(shr_data, evt);
+                }
+                Action::Ignore
+            },
+            Event::User(ref usr) => {
+                // The row picked while probing is reused, guards may see
+                // different data after the exit actions have run.
+                let idx = match self.delayed.take() {
+                    Some(idx) if !probe => Some(idx),
+                    _ => self.rows.iter().position(|r| {
+                        r.event == usr.name() && r.guard.as_ref().map_or(true, |g| g(shr_data, usr))
+                    })
+                };
+                let row = match idx {
+                    Some(idx) => &self.rows[idx],
+                    None      => return Action::Parent
+                };
+                if let Some(ref target) = row.target {
+                    if row.action.is_some() && probe {
+                        self.delayed = idx;
+                        return Action::DelayedTransitionTo(target.clone());
+                    }
+                }
+                if let Some(ref action) = row.action {
+                    action(shr_data, evt);
+                }
+                match row.target {
+                    Some(ref target) => Action::Transition(target.clone()),
+                    None             => Action::Ignore
+                }
+            }
+        }
+    }
+
+    fn handles(&self, evt: &UsrEvtEnum) -> Option<bool> {
+        Some(self.rows.iter().any(|r| r.event == evt.name()))
+    }
+}
+
+#[cfg(feature = "json")]
+pub fn from_json<UsrEvtEnum, UsrShrData>(doc: &str, registry: &Registry<UsrEvtEnum, UsrShrData>, shared_data: UsrShrData)
+        -> Result<DynamicStateMachine<UsrEvtEnum, UsrShrData>, LoadError>
+    where UsrEvtEnum : fmt::Debug + Clone + Name + 'static,
+          UsrShrData : fmt::Debug + 'static,
+{
+    let def = ::serde_json::from_str(doc).map_err(|e| LoadError::Parse(e.to_string()))?;
+    load(def, registry, shared_data)
+}
+
+#[cfg(feature = "toml")]
+pub fn from_toml<UsrEvtEnum, UsrShrData>(doc: &str, registry: &Registry<UsrEvtEnum, UsrShrData>, shared_data: UsrShrData)
+        -> Result<DynamicStateMachine<UsrEvtEnum, UsrShrData>, LoadError>
+    where UsrEvtEnum : fmt::Debug + Clone + Name + 'static,
+          UsrShrData : fmt::Debug + 'static,
+{
+    let def = ::toml::from_str(doc).map_err(|e| LoadError::Parse(e.to_string()))?;
+    load(def, registry, shared_data)
+}
+
+fn load<UsrEvtEnum, UsrShrData>(def: MachineDef, registry: &Registry<UsrEvtEnum, UsrShrData>, shared_data: UsrShrData)
+        -> Result<DynamicStateMachine<UsrEvtEnum, UsrShrData>, LoadError>
+    where UsrEvtEnum : fmt::Debug + Clone + Name + 'static,
+          UsrShrData : fmt::Debug + 'static,
+{
+    let mut index = HashMap::new();
+    for (i, state) in def.states.iter().enumerate() {
+        if index.insert(state.name.as_str(), i).is_some() {
+            return Err(invalid(format!("states[{}].name", i), format!("duplicate state `{}`", state.name)));
+        }
+    }
+    let known = |path: String, name: &str| -> Result<StateId, LoadError> {
+        match index.contains_key(name) {
+            true  => Ok(StateId::new(name)),
+            false => Err(invalid(path, format!("unknown state `{}`", name)))
+        }
+    };
+    let action = |path: String, name: &Option<String>| -> Result<Option<Callback<UsrEvtEnum, UsrShrData>>, LoadError> {
+        match *name {
+            Some(ref name) => match registry.actions.get(name) {
+                Some(action) => Ok(Some(action.clone())),
+                None         => Err(invalid(path, format!("unknown action `{}`", name)))
+            },
+            None => Ok(None)
+        }
+    };
+
+    known("initial".to_string(), &def.initial)?;
+    let mut builder = DynamicBuilder::new();
+    for (i, state) in def.states.iter().enumerate() {
+        if let Some(ref parent) = state.parent {
+            known(format!("states[{}].parent", i), parent)?;
+        }
+        let mut rows = Vec::new();
+        for (j, t) in state.transitions.iter().enumerate() {
+            let path = format!("states[{}].transitions[{}]", i, j);
+            let guard = match t.guard {
+                Some(ref name) => match registry.guards.get(name) {
+                    Some(guard) => Some(guard.clone()),
+                    None        => return Err(invalid(format!("{}.guard", path), format!("unknown guard `{}`", name)))
+                },
+                None => None
+            };
+            let target = match t.target {
+                Some(ref target) => Some(known(format!("{}.target", path), target)?),
+                None             => None
+            };
+            rows.push(Row {
+                event  : t.event.clone(),
+                guard,
+                action : action(format!("{}.action", path), &t.action)?,
+                target,
+            });
+        }
+        builder = builder.state(&state.name, state.parent.as_deref(), TableState {
+            name    : state.name.clone(),
+            enter   : action(format!("states[{}].enter", i), &state.enter)?,
+            exit    : action(format!("states[{}].exit", i), &state.exit)?,
+            rows,
+            delayed : None,
+        });
+        if let Some(ref child) = state.initial {
+            let path = format!("states[{}].initial", i);
+            known(path.clone(), child)?;
+            if def.states[index[child.as_str()]].parent.as_ref() != Some(&state.name) {
+                return Err(invalid(path, format!("`{}` is not a child of `{}`", child, state.name)));
+            }
+            builder = builder.initial_child(&state.name, child);
+        }
+    }
+
+    let state_path = |state: &StateId, field: &str| format!("states[{}].{}", index[state.name()], field);
+    builder.initial(&def.initial).build(shared_data).map_err(|e| match e {
+        BuildError::Invalid(ValidationError::ParentCycle { ref states }) =>
+            invalid(state_path(&states[0], "parent"), e.to_string()),
+        BuildError::Invalid(ValidationError::TooDeep { ref state }) =>
+            invalid(state_path(state, "parent"), e.to_string()),
+        BuildError::Invalid(ValidationError::NotAChild { ref state, .. }) =>
+            invalid(state_path(state, "initial"), e.to_string()),
+        _ =>
+            invalid("initial".to_string(), e.to_string()),
+    })
+}
@@ -0,0 +1,134 @@
+/*
+ * The MIT License (MIT)
+ *
+ * Copyright (c) 2015 Mattis Marjak (mattis.marjak@gmail.com)
+ *
+ * Permission is hereby granted, free of charge, to any person obtaining a copy
+ * of this software and associated documentation files (the "Software"), to deal
+ * in the Software without restriction, including without limitation the rights
+ * to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+ * copies of the Software, and to permit persons to whom the Software is
+ * furnished to do so, subject to the following conditions:
+ *
+ * The above copyright notice and this permission notice shall be included in all
+ * copies or substantial portions of the Software.
+ *
+ * THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+ * IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+ * FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+ * AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+ * LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+ * OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+ * SOFTWARE.
+ */
+
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt;
+#[cfg(feature = "json")]
+use serde_json;
+use super::{Action, Listener, variant_name};
+
+
+/// Listener recording which states were entered, which (state, event) pairs
+/// were handled and which transitions were taken.
+///
+/// Attach it through an `Arc<Mutex<Coverage>>` and call `report` with the
+/// machine's `all_states()` once the tests ran.
+#[derive(Debug, Clone, Default)]
+pub struct Coverage {
+    entered      : BTreeSet<String>,
+    transitioned : BTreeSet<String>,
+    pairs        : BTreeMap<(String, String), u64>,
+    edges        : BTreeMap<(String, String), u64>,
+}
+
+impl Coverage {
+    pub fn new() -> Self {
+        Coverage::default()
+    }
+
+    pub fn report<UsrStEnum: fmt::Display>(&self, states: &[UsrStEnum]) -> Report {
+        let states: Vec<String> = states.iter().map(|s| s.to_string()).collect();
+        Report {
+            never_entered      : states.iter().filter(|s| !self.entered.contains(*s)).cloned().collect(),
+            never_transitioned : states.iter().filter(|s| !self.transitioned.contains(*s)).cloned().collect(),
+            pairs              : self.pairs.iter().map(|((s, e), n)| (s.clone(), e.clone(), *n)).collect(),
+            edges              : self.edges.iter().map(|((s, t), n)| (s.clone(), t.clone(), *n)).collect(),
+            states,
+        }
+    }
+}
+
+impl<UsrStEnum, UsrEvtEnum> Listener<UsrStEnum, UsrEvtEnum> for Coverage
+    where UsrStEnum  : fmt::Debug + fmt::Display,
+          UsrEvtEnum : fmt::Debug,
+{
+    fn handled(&mut self, state: &UsrStEnum, evt: &UsrEvtEnum, action: &Action<UsrStEnum>) {
+        let event = variant_name(&format!("{:?}", evt)).to_string();
+        *self.pairs.entry((state.to_string(), event)).or_insert(0) += 1;
+        if let Action::Transition(ref target) = *action {
+            self.transitioned.insert(state.to_string());
+            *self.edges.entry((state.to_string(), target.to_string())).or_insert(0) += 1;
+        }
+    }
+    fn enter(&mut self, state: &UsrStEnum) {
+        self.entered.insert(state.to_string());
+    }
+}
+
+/// Coverage of the declared states. `Display` renders it as text and, with
+/// the json feature, `to_json` as JSON.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Report {
+    /// All declared states.
+    pub states             : Vec<String>,
+    pub never_entered      : Vec<String>,
+    /// States whose handlers never returned `Action::Transition`.
+    pub never_transitioned : Vec<String>,
+    /// Handled (state, event) pairs and how often, events by variant name.
+    pub pairs              : Vec<(String, String, u64)>,
+    /// Taken (handling state, target) transitions and how often.
+    pub edges              : Vec<(String, String, u64)>,
+}
+
+#[cfg(feature = "json")]
+impl Report {
+    /// The report as one JSON object, pairs and edges as objects with a `count`.
+    pub fn to_json(&self) -> String {
+        let pairs: Vec<serde_json::Value> = self.pairs.iter()
+            .map(|&(ref state, ref event, n)| serde_json::json!({"state": state, "event": event, "count": n}))
+            .collect();
+        let edges: Vec<serde_json::Value> = self.edges.iter()
+            .map(|&(ref source, ref target, n)| serde_json::json!({"source": source, "target": target, "count": n}))
+            .collect();
+        serde_json::json!({
+            "states"             : self.states,
+            "never_entered"      : self.never_entered,
+            "never_transitioned" : self.never_transitioned,
+            "pairs"              : pairs,
+            "edges"              : edges,
+        }).to_string()
+    }
+}
+
+impl fmt::Display for Report {
+    fn fmt(&self, f: &mut fmt::Formatter) -> Result<(), fmt::Error> {
+        let entered = self.states.len() - self.never_entered.len();
+        writeln!(f, "states entered: {}/{}", entered, self.states.len())?;
+        writeln!(f, "never entered: {}", text_list(&self.never_entered))?;
+        writeln!(f, "never transitioned: {}", text_list(&self.never_transitioned))?;
+        writeln!(f, "handled:")?;
+        for &(ref state, ref event, n) in self.pairs.iter() {
+            writeln!(f, "  {} / {} x{}", state, event, n)?;
+        }
+        writeln!(f, "transitions:")?;
+        for &(ref source, ref target, n) in self.edges.iter() {
+            writeln!(f, "  {} -> {} x{}", source, target, n)?;
+        }
+        Ok(())
+    }
+}
+
+fn text_list(values: &[String]) -> String {
+    if values.is_empty() { "none".to_string() } else { values.join(", ") }
+}
@@ -0,0 +1,347 @@
+/*
+ * The MIT License (MIT)
+ *
+ * Copyright (c) 2015 Mattis Marjak (mattis.marjak@gmail.com)
+ *
+ * Permission is hereby granted, free of charge, to any person obtaining a copy
+ * of this software and associated documentation files (the "Software"), to deal
+ * in the Software without restriction, including without limitation the rights
+ * to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+ * copies of the Software, and to permit persons to whom the Software is
+ * furnished to do so, subject to the following conditions:
+ *
+ * The above copyright notice and this permission notice shall be included in all
+ * copies or substantial portions of the Software.
+ *
+ * THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+ * IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+ * FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+ * AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+ * LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+ * OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+ * SOFTWARE.
+ */
+
+//! Streams what a machine does to local socket clients as JSON lines and lets
+//! them inspect and drive it.
+//!
+//! Every connected client receives one JSON object per line for each event,
+//! handled and unhandled event, transition, enter and exit. Clients can send
+//! commands, one JSON object per line:
+//!
+//! * `{"cmd": "query"}` - replies with the active configuration
+//! * `{"cmd": "inject", "event": "..."}` - parses the event and inputs it,
+//!   replies with the new configuration or with an error if `input` failed
+//!   or panicked
+//!
+//! Commands are only executed from `DebugServer::poll`, on the thread that
+//! owns the machine.
+
+use std::fmt;
+use std::cmp;
+use std::io::{self, BufRead, BufReader, Read, Write};
+use std::net::{Shutdown, SocketAddr, TcpListener, TcpStream, ToSocketAddrs};
+#[cfg(unix)]
+use std::os::unix::net::{UnixListener, UnixStream};
+use std::panic::{self, AssertUnwindSafe};
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{self, Receiver, Sender, TryRecvError};
+use std::thread::{self, JoinHandle};
+use std::time::Duration;
+use serde_json::{self, Value};
+use explore::panic_message;
+use super::{Action, Listener, StateLookup, StateMachine};
+
+
+// Clients that can't keep up for this long are dropped.
+const WRITE_TIMEOUT: Duration = Duration::from_millis(200);
+// Failing accepts, e.g. when out of file descriptors, are retried after a
+// delay that doubles up to the maximum.
+const MIN_BACKOFF: Duration = Duration::from_millis(10);
+const MAX_BACKOFF: Duration = Duration::from_secs(1);
+
+type Close = Box<dyn Fn() + Send>;
+type Streams = (Box<dyn Read + Send>, Box<dyn Write + Send>, Close);
+
+struct Client {
+    id    : usize,
+    out   : Box<dyn Write + Send>,
+    close : Close,
+}
+
+type Clients = Arc<Mutex<Vec<Client>>>;
+
+/// Socket server clients connect to, see the module docs.
+///
+/// Connections are accepted on a background thread, which is stopped and
+/// joined when the server is dropped. Connected clients are disconnected.
+pub struct DebugServer {
+    clients  : Clients,
+    commands : Receiver<(usize, String)>,
+    tcp_addr : Option<SocketAddr>,
+    path     : Option<PathBuf>,
+    shutdown : Arc<AtomicBool>,
+    // Connects to the server so a blocked accept returns.
+    wake     : Box<dyn Fn() -> io::Result<()> + Send>,
+    accepter : Option<JoinHandle<()>>,
+}
+
+impl fmt::Debug for DebugServer {
+    fn fmt(&self, f: &mut fmt::Formatter) -> Result<(), fmt::Error> {
+        f.debug_struct("DebugServer")
+            .field("clients", &self.clients.lock().unwrap().len())
+            .field("tcp_addr", &self.tcp_addr)
+            .field("path", &self.path)
+            .finish()
+    }
+}
+
+impl DebugServer {
+    /// Listens on a TCP address, which must be a loopback address.
+    pub fn bind_tcp<A: ToSocketAddrs>(addr: A) -> io::Result<Self> {
+        // checked before binding, the socket never listens on another address
+        let addrs: Vec<_> = addr.to_socket_addrs()?.collect();
+        if addrs.is_empty() || addrs.iter().any(|a| !a.ip().is_loopback()) {
+            return Err(io::Error::new(io::ErrorKind::InvalidInput,
+                                      "the debug server only listens on loopback addresses"));
+        }
+        let listener = TcpListener::bind(&addrs[..])?;
+        let local = listener.local_addr()?;
+        let mut server = DebugServer::spawn(move || {
+            let (stream, _) = listener.accept()?;
+            stream.set_write_timeout(Some(WRITE_TIMEOUT))?;
+            let closer = stream.try_clone()?;
+            Ok((Box::new(stream.try_clone()?), Box::new(stream), Box::new(move || { let _ = closer.shutdown(Shutdown::Both); })))
+        }, Box::new(move || TcpStream::connect(local).map(|_| ())));
+        server.tcp_addr = Some(local);
+        Ok(server)
+    }
+
+    /// Listens on a Unix domain socket, the socket file is removed when the
+    /// server is dropped.
+    #[cfg(unix)]
+    pub fn bind_unix<P: Into<PathBuf>>(path: P) -> io::Result<Self> {
+        let path = path.into();
+        let listener = UnixListener::bind(&path)?;
+        let wake_path = path.clone();
+        let mut server = DebugServer::spawn(move || {
+            let (stream, _) = listener.accept()?;
+            stream.set_write_timeout(Some(WRITE_TIMEOUT))?;
+            let closer = stream.try_clone()?;
+            Ok((Box::new(stream.try_clone()?), Box::new(stream), Box::new(move || { let _ = closer.shutdown(Shutdown::Both); })))
+        }, Box::new(move || UnixStream::connect(&wake_path).map(|_| ())));
+        server.path = Some(path);
+        Ok(server)
+    }
+
+    fn spawn<F>(mut accept: F, wake: Box<dyn Fn() -> io::Result<()> + Send>) -> Self
+        where F: FnMut() -> io::Result<Streams> + Send + 'static
+    {
+        let clients: Clients = Arc::new(Mutex::new(Vec::new()));
+        let (tx, rx) = mpsc::channel();
+        let accepted = clients.clone();
+        let shutdown = Arc::new(AtomicBool::new(false));
+        let stop = shutdown.clone();
+        let accepter = thread::spawn(move || {
+            let mut next_id = 0;
+            let mut backoff = None;
+            while !stop.load(Ordering::SeqCst) {
+                match accept() {
+                    _ if stop.load(Ordering::SeqCst) => break,
+                    Ok((input, out, close)) => {
+                        backoff = None;
+                        accepted.lock().unwrap().push(Client { id: next_id, out, close });
+                        read_commands(next_id, input, tx.clone());
+                        next_id += 1;
+                    },
+                    Err(e) => {
+                        // Only the first failure in a row is logged
+                        let delay = match backoff {
+                            Some(delay) => cmp::min(delay * 2, MAX_BACKOFF),
+                            None        => { warn!("debug server: accept failed, retrying: {}", e); MIN_BACKOFF }
+                        };
+                        backoff = Some(delay);
+                        thread::sleep(delay);
+                    }
+                }
+            }
+        });
+        DebugServer {
+            clients,
+            commands : rx,
+            tcp_addr : None,
+            path     : None,
+            shutdown,
+            wake,
+            accepter : Some(accepter),
+        }
+    }
+
+    /// The bound TCP address, useful when binding to port 0.
+    pub fn tcp_addr(&self) -> Option<SocketAddr> {
+        self.tcp_addr
+    }
+
+    /// A listener streaming the machine's activity to all clients, attach it
+    /// with `StateMachine::add_listener`.
+    pub fn listener(&self) -> DebugListener {
+        DebugListener { clients: self.clients.clone() }
+    }
+
+    /// Executes the commands received since the last call, returns how many
+    /// there were. `parse` turns the `event` of an inject command into an
+    /// event. Never blocks.
+    pub fn poll<UsrStStr, UsrStEnum, UsrEvtEnum, UsrShrData, F>(&self, sm: &mut StateMachine<UsrStStr, UsrStEnum, UsrEvtEnum, UsrShrData>, mut parse: F) -> usize
+        where UsrStStr   : fmt::Debug + StateLookup<UsrStEnum, UsrEvtEnum, UsrShrData>,
+              UsrStEnum  : fmt::Debug + fmt::Display + Eq + Clone,
+              UsrEvtEnum : fmt::Debug + Clone,
+              UsrShrData : fmt::Debug,
+              F          : FnMut(&str) -> Option<UsrEvtEnum>,
+    {
+        let mut count = 0;
+        loop {
+            let (id, line) = match self.commands.try_recv() {
+                Ok(cmd)                         => cmd,
+                Err(TryRecvError::Empty)        => return count,
+                Err(TryRecvError::Disconnected) => return count,
+            };
+            count += 1;
+            let reply = execute(sm, &line, &mut parse);
+            let mut clients = self.clients.lock().unwrap();
+            clients.retain_mut(|c| c.id != id || send(c, &reply));
+        }
+    }
+}
+
+impl Drop for DebugServer {
+    fn drop(&mut self) {
+        self.shutdown.store(true, Ordering::SeqCst);
+        // Without the wake up connection the thread may never return from
+        // accept, it's left behind rather than blocking the drop.
+        match (self.wake)() {
+            Ok(()) => if let Some(accepter) = self.accepter.take() {
+                let _ = accepter.join();
+            },
+            Err(e) => warn!("debug server: couldn't stop accepting connections: {}", e)
+        }
+        for client in self.clients.lock().unwrap().drain(..) {
+            (client.close)();
+        }
+        if let Some(ref path) = self.path {
+            let _ = ::std::fs::remove_file(path);
+        }
+    }
+}
+
+/// Forwards a machine's activity to the clients of a `DebugServer`.
+#[derive(Clone)]
+pub struct DebugListener {
+    clients : Clients,
+}
+
+impl fmt::Debug for DebugListener {
+    fn fmt(&self, f: &mut fmt::Formatter) -> Result<(), fmt::Error> {
+        write!(f, "DebugListener")
+    }
+}
+
+impl DebugListener {
+    fn broadcast(&self, msg: Value) {
+        let mut clients = self.clients.lock().unwrap();
+        clients.retain_mut(|c| send(c, &msg));
+    }
+}
+
+impl<UsrStEnum, UsrEvtEnum> Listener<UsrStEnum, UsrEvtEnum> for DebugListener
+    where UsrStEnum  : fmt::Debug + fmt::Display,
+          UsrEvtEnum : fmt::Debug + Clone,
+{
+    fn event(&mut self, state: &UsrStEnum, evt: &UsrEvtEnum) {
+        self.broadcast(serde_json::json!({"type": "event", "state": state.to_string(), "event": format!("{:?}", evt)}));
+    }
+    fn handled(&mut self, state: &UsrStEnum, evt: &UsrEvtEnum, action: &Action<UsrStEnum>) {
+        self.broadcast(serde_json::json!({"type": "handled", "state": state.to_string(), "event": format!("{:?}", evt),
+                              "action": format!("{:?}", action)}));
+    }
+    fn unhandled(&mut self, state: &UsrStEnum, evt: &UsrEvtEnum) {
+        self.broadcast(serde_json::json!({"type": "unhandled", "state": state.to_string(), "event": format!("{:?}", evt)}));
+    }
+    fn transition(&mut self, source: &UsrStEnum, target: &UsrStEnum) {
+        self.broadcast(serde_json::json!({"type": "transition", "source": source.to_string(), "target": target.to_string()}));
+    }
+    fn enter(&mut self, state: &UsrStEnum) {
+        self.broadcast(serde_json::json!({"type": "enter", "state": state.to_string()}));
+    }
+    fn exit(&mut self, state: &UsrStEnum) {
+        self.broadcast(serde_json::json!({"type": "exit", "state": state.to_string()}));
+    }
+}
+
+// Writes one JSON line, false if the client is gone.
+fn send(client: &mut Client, msg: &Value) -> bool {
+    let mut line = msg.to_string();
+    line.push('\n');
+    client.out.write_all(line.as_bytes()).and_then(|_| client.out.flush()).is_ok()
+}
+
+fn read_commands(id: usize, input: Box<dyn Read + Send>, tx: Sender<(usize, String)>) {
+    thread::spawn(move || {
+        for line in BufReader::new(input).lines() {
+            match line {
+                Ok(ref line) if line.trim().is_empty() => {},
+                Ok(line)                                => if tx.send((id, line)).is_err() { break },
+                Err(_)                                  => break
+            }
+        }
+    });
+}
+
+fn execute<UsrStStr, UsrStEnum, UsrEvtEnum, UsrShrData, F>(sm: &mut StateMachine<UsrStStr, UsrStEnum, UsrEvtEnum, UsrShrData>, line: &str, parse: &mut F) -> Value
+    where UsrStStr   : fmt::Debug + StateLookup<UsrStEnum, UsrEvtEnum, UsrShrData>,
+          UsrStEnum  : fmt::Debug + fmt::Display + Eq + Clone,
+          UsrEvtEnum : fmt::Debug + Clone,
+          UsrShrData : fmt::Debug,
+          F          : FnMut(&str) -> Option<UsrEvtEnum>,
+{
+    let cmd: Value = match serde_json::from_str(line) {
+        Ok(cmd) => cmd,
+        Err(e)  => return error(format!("invalid command: {}", e))
+    };
+    match cmd["cmd"].as_str() {
+        Some("query")  => configuration(sm),
+        Some("inject") => {
+            let name = match cmd["event"].as_str() {
+                Some(name) => name,
+                None       => return error("inject needs an \"event\" string".to_string())
+            };
+            if !sm.is_started() {
+                return error("the machine is not started".to_string());
+            }
+            match parse(name) {
+                Some(evt) => match panic::catch_unwind(AssertUnwindSafe(|| sm.input(evt))) {
+                    Ok(Ok(()))  => configuration(sm),
+                    Ok(Err(e))  => error(e.to_string()),
+                    Err(e)      => error(format!("the machine panicked: {}", panic_message(e)))
+                },
+                None      => error(format!("unknown event: {}", name))
+            }
+        },
+        _              => error(format!("unknown command: {}", line))
+    }
+}
+
+fn configuration<UsrStStr, UsrStEnum, UsrEvtEnum, UsrShrData>(sm: &StateMachine<UsrStStr, UsrStEnum, UsrEvtEnum, UsrShrData>) -> Value
+    where UsrStStr   : fmt::Debug + StateLookup<UsrStEnum, UsrEvtEnum, UsrShrData>,
+          UsrStEnum  : fmt::Debug + fmt::Display + Eq + Clone,
+          UsrEvtEnum : fmt::Debug + Clone,
+          UsrShrData : fmt::Debug,
+{
+    let active: Vec<String> = sm.active_states().iter().map(|s| s.to_string()).collect();
+    serde_json::json!({"type": "configuration", "started": sm.is_started(), "current": sm.current().to_string(), "active": active})
+}
+
+fn error(message: String) -> Value {
+    serde_json::json!({"type": "error", "message": message})
+}
@@ -0,0 +1,117 @@
+/*
+ * The MIT License (MIT)
+ *
+ * Copyright (c) 2015 Mattis Marjak (mattis.marjak@gmail.com)
+ *
+ * Permission is hereby granted, free of charge, to any person obtaining a copy
+ * of this software and associated documentation files (the "Software"), to deal
+ * in the Software without restriction, including without limitation the rights
+ * to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+ * copies of the Software, and to permit persons to whom the Software is
+ * furnished to do so, subject to the following conditions:
+ *
+ * The above copyright notice and this permission notice shall be included in all
+ * copies or substantial portions of the Software.
+ *
+ * THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+ * IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+ * FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+ * AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+ * LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+ * OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+ * SOFTWARE.
+ */
+
+use std::fmt;
+use super::{Core, Initializer, InputError, Listener, StateLookup, UnhandledPolicy, ValidationError};
+
+
+/// State machine without shared data of its own, the data is borrowed for
+/// each call instead, so handlers can work on resources owned elsewhere.
+/// Everything that runs handlers takes the data, see `StateMachine` for
+/// what the methods do.
+#[derive(Debug, Clone)]
+pub struct DetachedStateMachine<UsrStStr, UsrStEnum, UsrEvtEnum, UsrShrData>
+    where UsrStStr:   fmt::Debug,
+          UsrStEnum:  fmt::Debug,
+          UsrEvtEnum: fmt::Debug,
+          UsrShrData: fmt::Debug,
+{
+    core : Core<UsrStStr, UsrStEnum, UsrEvtEnum, UsrShrData>,
+}
+impl<UsrStStr, UsrStEnum, UsrEvtEnum, UsrShrData> DetachedStateMachine<UsrStStr, UsrStEnum, UsrEvtEnum, UsrShrData>
+    where UsrStStr   : fmt::Debug + Initializer + StateLookup<UsrStEnum, UsrEvtEnum, UsrShrData>,
+          UsrStEnum  : fmt::Debug + Eq + Clone,
+          UsrEvtEnum : fmt::Debug + Clone,
+          UsrShrData : fmt::Debug,
+{
+    pub fn new(initial: UsrStEnum) -> Self {
+        Self::with_states(initial, UsrStStr::new())
+    }
+
+    pub fn reset(&mut self) {
+        self.core.reset();
+    }
+
+    pub fn restart_with(&mut self, shr_data: &mut UsrShrData) {
+        self.core.restart_with(shr_data);
+    }
+}
+impl<UsrStStr, UsrStEnum, UsrEvtEnum, UsrShrData> DetachedStateMachine<UsrStStr, UsrStEnum, UsrEvtEnum, UsrShrData>
+    where UsrStStr   : fmt::Debug + StateLookup<UsrStEnum, UsrEvtEnum, UsrShrData>,
+          UsrStEnum  : fmt::Debug + Eq + Clone,
+          UsrEvtEnum : fmt::Debug + Clone,
+          UsrShrData : fmt::Debug,
+{
+    pub fn with_states(initial: UsrStEnum, states: UsrStStr) -> Self {
+        DetachedStateMachine { core: Core::with_states(initial, states) }
+    }
+
+    pub fn current(&self) -> &UsrStEnum {
+        &self.core.current
+    }
+
+    pub fn active_states(&self) -> Vec<UsrStEnum> {
+        self.core.active_states()
+    }
+
+    pub fn is_started(&self) -> bool {
+        self.core.started
+    }
+
+    pub fn set_leaf_only(&mut self, leaf_only: bool) {
+        self.core.set_leaf_only(leaf_only);
+    }
+
+    pub fn is_leaf_only(&self) -> bool {
+        self.core.leaf_only
+    }
+
+    pub fn set_unhandled_policy(&mut self, policy: UnhandledPolicy<UsrStEnum, UsrEvtEnum>) {
+        self.core.unhandled = policy;
+    }
+
+    pub fn add_listener(&mut self, listener: Box<dyn Listener<UsrStEnum, UsrEvtEnum> + Send>) {
+        self.core.listeners.0.push(listener);
+    }
+
+    pub fn all_states(&self) -> Vec<UsrStEnum> {
+        self.core.states.states()
+    }
+
+    pub fn validate(&self) -> Result<(), ValidationError<UsrStEnum>> {
+        self.core.validate()
+    }
+
+    pub fn start_with(&mut self, shr_data: &mut UsrShrData) {
+        self.core.start_with(shr_data);
+    }
+
+    pub fn stop_with(&mut self, shr_data: &mut UsrShrData) {
+        self.core.stop_with(shr_data);
+    }
+
+    pub fn input_with(&mut self, shr_data: &mut UsrShrData, evt: UsrEvtEnum) -> Result<(), InputError<UsrStEnum, UsrEvtEnum>> {
+        self.core.input_with(shr_data, evt)
+    }
+}
@@ -0,0 +1,243 @@
+/*
+ * The MIT License (MIT)
+ *
+ * Copyright (c) 2015 Mattis Marjak (mattis.marjak@gmail.com)
+ *
+ * Permission is hereby granted, free of charge, to any person obtaining a copy
+ * of this software and associated documentation files (the "Software"), to deal
+ * in the Software without restriction, including without limitation the rights
+ * to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+ * copies of the Software, and to permit persons to whom the Software is
+ * furnished to do so, subject to the following conditions:
+ *
+ * The above copyright notice and this permission notice shall be included in all
+ * copies or substantial portions of the Software.
+ *
+ * THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+ * IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+ * FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+ * AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+ * LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+ * OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+ * SOFTWARE.
+ */
+
+use std::collections::HashMap;
+use std::error::Error;
+use std::fmt;
+use super::{State, StateLookup, StateMachine, ValidationError};
+use validate;
+
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct StateId(String);
+impl StateId {
+    pub fn new(name: &str) -> Self {
+        StateId(name.to_string())
+    }
+
+    pub fn name(&self) -> &str {
+        &self.0
+    }
+}
+impl<'a> From<&'a str> for StateId {
+    fn from(name: &'a str) -> Self {
+        StateId::new(name)
+    }
+}
+impl From<String> for StateId {
+    fn from(name: String) -> Self {
+        StateId(name)
+    }
+}
+impl fmt::Display for StateId {
+    fn fmt(&self, f: &mut fmt::Formatter) -> Result<(), fmt::Error> {
+        fmt::Display::fmt(&self.0, f)
+    }
+}
+
+/// States of a `DynamicStateMachine`, registered at runtime by `DynamicBuilder`.
+pub struct DynamicStates<UsrEvtEnum, UsrShrData> {
+    ids      : Vec<StateId>,
+    parents  : Vec<Option<StateId>>,
+    initials : Vec<Option<StateId>>,
+    states   : Vec<Box<dyn State<UsrEvtEnum, StateId, UsrShrData>>>,
+    index    : HashMap<StateId, usize>,
+}
+impl<UsrEvtEnum, UsrShrData> fmt::Debug for DynamicStates<UsrEvtEnum, UsrShrData> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> Result<(), fmt::Error> {
+        f.debug_map()
+            .entries(self.ids.iter().map(|id| id.name()).zip(self.states.iter().map(|s| s.name())))
+            .finish()
+    }
+}
+impl<UsrEvtEnum, UsrShrData> DynamicStates<UsrEvtEnum, UsrShrData>
+    where UsrEvtEnum : fmt::Debug,
+          UsrShrData : fmt::Debug,
+{
+    fn index_of(&self, typ: &StateId) -> usize {
+        match self.index.get(typ) {
+            Some(&i) => i,
+            None     => panic!("State {} is not registered in the dynamic state machine", typ)
+        }
+    }
+}
+impl<UsrEvtEnum, UsrShrData> StateLookup<StateId, UsrEvtEnum, UsrShrData> for DynamicStates<UsrEvtEnum, UsrShrData>
+    where UsrEvtEnum : fmt::Debug,
+          UsrShrData : fmt::Debug,
+{
+    fn lookup(&mut self, typ: &StateId) -> &mut dyn State<UsrEvtEnum, StateId, UsrShrData> {
+        let i = self.index_of(typ);
+        &mut *self.states[i]
+    }
+
+    fn parent(&self, typ: &StateId) -> Option<StateId> {
+        self.parents[self.index_of(typ)].clone()
+    }
+
+    fn states(&self) -> Vec<StateId> {
+        self.ids.clone()
+    }
+
+    fn initial_child(&self, typ: &StateId) -> Option<StateId> {
+        self.initials[self.index_of(typ)].clone()
+    }
+}
+
+pub type DynamicStateMachine<UsrEvtEnum, UsrShrData> = StateMachine<DynamicStates<UsrEvtEnum, UsrShrData>, StateId, UsrEvtEnum, UsrShrData>;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum BuildError {
+    DuplicateState(StateId),
+    UnknownState(StateId),
+    UnknownParent  { state: StateId, parent: StateId },
+    NoInitial,
+    UnknownInitial(StateId),
+    Invalid(ValidationError<StateId>),
+}
+impl fmt::Display for BuildError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> Result<(), fmt::Error> {
+        match *self {
+            BuildError::DuplicateState(ref state) =>
+                write!(f, "state {} is registered more than once", state),
+            BuildError::UnknownState(ref state) =>
+                write!(f, "state {} is not registered", state),
+            BuildError::UnknownParent { ref state, ref parent } =>
+                write!(f, "parent {} of state {} is not registered", parent, state),
+            BuildError::NoInitial =>
+                write!(f, "no initial state given"),
+            BuildError::UnknownInitial(ref state) =>
+                write!(f, "initial state {} is not registered", state),
+            BuildError::Invalid(ref e) =>
+                fmt::Display::fmt(e, f),
+        }
+    }
+}
+impl Error for BuildError {}
+
+/// Builds a `DynamicStateMachine` from states registered by name at runtime.
+/// Handlers refer to other states with `StateId`s, e.g.
+/// `Action::Transition("Idle".into())`.
+pub struct DynamicBuilder<UsrEvtEnum, UsrShrData> {
+    states  : DynamicStates<UsrEvtEnum, UsrShrData>,
+    initial : Option<StateId>,
+    errors  : Vec<BuildError>,
+}
+impl<UsrEvtEnum, UsrShrData> fmt::Debug for DynamicBuilder<UsrEvtEnum, UsrShrData> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> Result<(), fmt::Error> {
+        f.debug_struct("DynamicBuilder")
+            .field("states", &self.states)
+            .field("initial", &self.initial)
+            .field("errors", &self.errors)
+            .finish()
+    }
+}
+impl<UsrEvtEnum, UsrShrData> Default for DynamicBuilder<UsrEvtEnum, UsrShrData>
+    where UsrEvtEnum : fmt::Debug + Clone,
+          UsrShrData : fmt::Debug,
+{
+    fn default() -> Self {
+        Self::new()
+    }
+}
+impl<UsrEvtEnum, UsrShrData> DynamicBuilder<UsrEvtEnum, UsrShrData>
+    where UsrEvtEnum : fmt::Debug + Clone,
+          UsrShrData : fmt::Debug,
+{
+    pub fn new() -> Self {
+        DynamicBuilder {
+            states  : DynamicStates {
+                ids      : Vec::new(),
+                parents  : Vec::new(),
+                initials : Vec::new(),
+                states   : Vec::new(),
+                index    : HashMap::new(),
+            },
+            initial : None,
+            errors  : Vec::new(),
+        }
+    }
+
+    pub fn state<S>(self, name: &str, parent: Option<&str>, state: S) -> Self
+        where S: State<UsrEvtEnum, StateId, UsrShrData> + 'static
+    {
+        self.boxed_state(name, parent, Box::new(state))
+    }
+
+    pub fn boxed_state(mut self, name: &str, parent: Option<&str>, state: Box<dyn State<UsrEvtEnum, StateId, UsrShrData>>) -> Self {
+        let id = StateId::new(name);
+        if self.states.index.contains_key(&id) {
+            self.errors.push(BuildError::DuplicateState(id));
+            return self;
+        }
+        self.states.index.insert(id.clone(), self.states.ids.len());
+        self.states.ids.push(id);
+        self.states.parents.push(parent.map(StateId::new));
+        self.states.initials.push(None);
+        self.states.states.push(state);
+        self
+    }
+
+    pub fn initial_child(mut self, name: &str, child: &str) -> Self {
+        match self.states.index.get(&StateId::new(name)) {
+            Some(&i) => self.states.initials[i] = Some(StateId::new(child)),
+            None     => self.errors.push(BuildError::UnknownState(StateId::new(name)))
+        }
+        self
+    }
+
+    pub fn initial(mut self, name: &str) -> Self {
+        self.initial = Some(StateId::new(name));
+        self
+    }
+
+    pub fn build(mut self, shared_data: UsrShrData) -> Result<DynamicStateMachine<UsrEvtEnum, UsrShrData>, BuildError> {
+        if !self.errors.is_empty() {
+            return Err(self.errors.remove(0));
+        }
+        for (state, parent) in self.states.ids.iter().zip(self.states.parents.iter()) {
+            if let Some(ref parent) = *parent {
+                if !self.states.index.contains_key(parent) {
+                    return Err(BuildError::UnknownParent { state: state.clone(), parent: parent.clone() });
+                }
+            }
+        }
+        for child in self.states.initials.iter() {
+            if let Some(ref child) = *child {
+                if !self.states.index.contains_key(child) {
+                    return Err(BuildError::UnknownState(child.clone()));
+                }
+            }
+        }
+        let initial = match self.initial.take() {
+            Some(initial) => initial,
+            None          => return Err(BuildError::NoInitial)
+        };
+        if !self.states.index.contains_key(&initial) {
+            return Err(BuildError::UnknownInitial(initial));
+        }
+        validate::check(&initial, &self.states.ids, |s| self.states.parent(s), |s| self.states.initial_child(s))
+            .map_err(BuildError::Invalid)?;
+        Ok(StateMachine::with_states(initial, self.states, shared_data))
+    }
+}
@@ -0,0 +1,200 @@
+/*
+ * The MIT License (MIT)
+ *
+ * Copyright (c) 2015 Mattis Marjak (mattis.marjak@gmail.com)
+ *
+ * Permission is hereby granted, free of charge, to any person obtaining a copy
+ * of this software and associated documentation files (the "Software"), to deal
+ * in the Software without restriction, including without limitation the rights
+ * to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+ * copies of the Software, and to permit persons to whom the Software is
+ * furnished to do so, subject to the following conditions:
+ *
+ * The above copyright notice and this permission notice shall be included in all
+ * copies or substantial portions of the Software.
+ *
+ * THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+ * IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+ * FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+ * AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+ * LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+ * OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+ * SOFTWARE.
+ */
+
+use std::any::Any;
+use std::collections::{HashSet, VecDeque};
+use std::fmt;
+use std::panic::{self, AssertUnwindSafe};
+use super::{InputError, StateMachine, StateLookup};
+
+
+#[derive(Debug, Clone)]
+pub struct Deadlock<UsrStEnum, UsrEvtEnum> {
+    pub state : UsrStEnum,
+    pub path  : Vec<UsrEvtEnum>,
+}
+
+#[derive(Debug, Clone)]
+pub struct Panic<UsrEvtEnum> {
+    pub message : String,
+    pub path    : Vec<UsrEvtEnum>,
+}
+
+/// An event for which `input` returned `error`.
+#[derive(Debug, Clone)]
+pub struct Error<UsrStEnum, UsrEvtEnum> {
+    pub error : InputError<UsrStEnum, UsrEvtEnum>,
+    pub path  : Vec<UsrEvtEnum>,
+}
+
+#[derive(Debug, Clone)]
+pub struct Report<UsrStEnum, UsrEvtEnum> {
+    pub configurations : usize,
+    pub unreachable    : Vec<UsrStEnum>,
+    pub deadlocks      : Vec<Deadlock<UsrStEnum, UsrEvtEnum>>,
+    /// One entry per panic message, with the shortest path that caused it.
+    pub panics         : Vec<Panic<UsrEvtEnum>>,
+    /// One entry per distinct error, with the shortest path that caused it.
+    /// Unhandled events are told apart by state and event, so the same
+    /// event rejected in two states is reported twice.
+    pub errors         : Vec<Error<UsrStEnum, UsrEvtEnum>>,
+    pub truncated      : bool,
+}
+
+/// Breadth-first explorer of all configurations (active state, state
+/// fields and shared data) reachable with a finite event alphabet.
+/// Configurations are told apart by their `Debug` output, every path
+/// reported is the shortest one found.
+#[derive(Debug)]
+pub struct Explorer<UsrEvtEnum> {
+    events             : Vec<UsrEvtEnum>,
+    max_depth          : usize,
+    max_configurations : usize,
+}
+impl<UsrEvtEnum> Explorer<UsrEvtEnum>
+    where UsrEvtEnum : fmt::Debug + Clone
+{
+    pub fn new(events: Vec<UsrEvtEnum>) -> Self {
+        Explorer {
+            events,
+            max_depth          : 64,
+            max_configurations : 100_000,
+        }
+    }
+
+    pub fn max_depth(mut self, depth: usize) -> Self {
+        self.max_depth = depth;
+        self
+    }
+
+    pub fn max_configurations(mut self, configurations: usize) -> Self {
+        self.max_configurations = configurations;
+        self
+    }
+
+    pub fn explore<UsrStStr, UsrStEnum, UsrShrData>(&self, sm: &StateMachine<UsrStStr, UsrStEnum, UsrEvtEnum, UsrShrData>) -> Report<UsrStEnum, UsrEvtEnum>
+        where UsrStStr   : fmt::Debug + Clone + StateLookup<UsrStEnum, UsrEvtEnum, UsrShrData>,
+              UsrStEnum  : fmt::Debug + Eq + Clone,
+              UsrShrData : fmt::Debug + Clone,
+    {
+        let mut report = Report {
+            configurations : 0,
+            unreachable    : Vec::new(),
+            deadlocks      : Vec::new(),
+            panics         : Vec::new(),
+            errors         : Vec::new(),
+            truncated      : false,
+        };
+        let mut reached: Vec<UsrStEnum> = Vec::new();
+        let mut seen = HashSet::new();
+        let mut queue = VecDeque::new();
+
+        let mut root = sm.clone();
+        if !root.is_started() {
+            if let Err(e) = panic::catch_unwind(AssertUnwindSafe(|| root.start())) {
+                report.panics.push(Panic { message: panic_message(e), path: Vec::new() });
+                report.unreachable = sm.all_states();
+                return report;
+            }
+        }
+        seen.insert(fingerprint(&root));
+        queue.push_back((root, Vec::new()));
+
+        while let Some((sm, path)) = queue.pop_front() {
+            report.configurations += 1;
+            for state in sm.active_states() {
+                if !reached.contains(&state) {
+                    reached.push(state);
+                }
+            }
+            if path.len() >= self.max_depth {
+                report.truncated = true;
+                continue;
+            }
+            let before = fingerprint(&sm);
+            let mut progress = false;
+            for evt in self.events.iter() {
+                let mut next = sm.clone();
+                let mut next_path = path.clone();
+                next_path.push(evt.clone());
+                match panic::catch_unwind(AssertUnwindSafe(|| next.input(evt.clone()))) {
+                    Ok(Ok(())) => {},
+                    Ok(Err(error)) => {
+                        // InputError holds the state and event, its Debug output tells errors apart
+                        let key = format!("{:?}", error);
+                        if !report.errors.iter().any(|e| format!("{:?}", e.error) == key) {
+                            report.errors.push(Error { error, path: next_path });
+                        }
+                        continue;
+                    },
+                    Err(e) => {
+                        let message = panic_message(e);
+                        if !report.panics.iter().any(|p| p.message == message) {
+                            report.panics.push(Panic { message, path: next_path });
+                        }
+                        continue;
+                    }
+                }
+                let after = fingerprint(&next);
+                if after == before {
+                    continue;
+                }
+                progress = true;
+                if seen.contains(&after) {
+                    continue;
+                }
+                if seen.len() >= self.max_configurations {
+                    report.truncated = true;
+                    continue;
+                }
+                seen.insert(after);
+                queue.push_back((next, next_path));
+            }
+            if !progress {
+                report.deadlocks.push(Deadlock { state: sm.current().clone(), path });
+            }
+        }
+        report.unreachable = sm.all_states().into_iter().filter(|s| !reached.contains(s)).collect();
+        report
+    }
+}
+
+fn fingerprint<UsrStStr, UsrStEnum, UsrEvtEnum, UsrShrData>(sm: &StateMachine<UsrStStr, UsrStEnum, UsrEvtEnum, UsrShrData>) -> String
+    where UsrStStr   : fmt::Debug,
+          UsrStEnum  : fmt::Debug,
+          UsrEvtEnum : fmt::Debug,
+          UsrShrData : fmt::Debug,
+{
+    format!("{:?}", sm)
+}
+
+pub(crate) fn panic_message(e: Box<dyn Any + Send>) -> String {
+    if let Some(s) = e.downcast_ref::<&str>() {
+        s.to_string()
+    } else if let Some(s) = e.downcast_ref::<String>() {
+        s.clone()
+    } else {
+        "<non-string panic payload>".to_string()
+    }
+}
@@ -0,0 +1,195 @@
+/*
+ * The MIT License (MIT)
+ *
+ * Copyright (c) 2015 Mattis Marjak (mattis.marjak@gmail.com)
+ *
+ * Permission is hereby granted, free of charge, to any person obtaining a copy
+ * of this software and associated documentation files (the "Software"), to deal
+ * in the Software without restriction, including without limitation the rights
+ * to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+ * copies of the Software, and to permit persons to whom the Software is
+ * furnished to do so, subject to the following conditions:
+ *
+ * The above copyright notice and this permission notice shall be included in all
+ * copies or substantial portions of the Software.
+ *
+ * THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+ * IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+ * FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+ * AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+ * LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+ * OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+ * SOFTWARE.
+ */
+
+//! C API for machines defined with the hsm macros, see `hsm_ffi!`.
+//!
+//! `hsm_ffi!(prefix ; ...)` generates a module `prefix` with these functions,
+//! exported unmangled as `prefix_<name>`:
+//!
+//! * `new()` and `free(m)` - create and destroy a machine
+//! * `start(m)` - start it, returns `OK`, `INVALID` or `PANICKED`
+//! * `input(m, event, data, len)` - decode event `event` with payload
+//!   `data[..len]` and feed it to the machine, returns one of the codes below
+//! * `current(m)` - the current state as an integer ID, `NO_STATE` for a
+//!   null machine
+//!
+//! plus `c_header()`, which returns the matching C header.
+
+use std::fmt::Write;
+
+
+/// `start` and `input` return code: the machine started or handled the event.
+pub const OK: i32 = 0;
+/// `start` and `input` return code: null machine, or the event ID or payload
+/// wasn't recognised.
+pub const INVALID: i32 = -1;
+/// `start` and `input` return code: the machine panicked, it should be freed.
+pub const PANICKED: i32 = -2;
+/// `input` return code: the machine returned an error, it's still usable.
+pub const REJECTED: i32 = -3;
+/// What `current` returns for a null machine, no state has this ID.
+pub const NO_STATE: u32 = u32::MAX;
+
+/// Renders a C header for the functions generated by `hsm_ffi!`.
+pub fn render_header(prefix: &str, states: &[(String, u32)], events: &[(&str, u32)]) -> String {
+    let upper = prefix.to_uppercase();
+    let mut out = String::new();
+    let _ = writeln!(out, "/* Generated by hsm, do not edit. */");
+    let _ = writeln!(out, "#ifndef {}_H", upper);
+    let _ = writeln!(out, "#define {}_H", upper);
+    let _ = writeln!(out);
+    let _ = writeln!(out, "#include <stddef.h>");
+    let _ = writeln!(out, "#include <stdint.h>");
+    let _ = writeln!(out);
+    let _ = writeln!(out, "#ifdef __cplusplus");
+    let _ = writeln!(out, "extern \"C\" {{");
+    let _ = writeln!(out, "#endif");
+    let _ = writeln!(out);
+    let _ = writeln!(out, "typedef struct {0}_machine {0}_machine;", prefix);
+    let _ = writeln!(out);
+    for &(ref name, id) in states {
+        let _ = writeln!(out, "#define {}_STATE_{} {}", upper, name.to_uppercase(), id);
+    }
+    let _ = writeln!(out);
+    for &(name, id) in events {
+        let _ = writeln!(out, "#define {}_EVENT_{} {}", upper, name.to_uppercase(), id);
+    }
+    let _ = writeln!(out);
+    let _ = writeln!(out, "#define {}_OK {}", upper, OK);
+    let _ = writeln!(out, "#define {}_INVALID {}", upper, INVALID);
+    let _ = writeln!(out, "#define {}_PANICKED {}", upper, PANICKED);
+    let _ = writeln!(out, "#define {}_REJECTED {}", upper, REJECTED);
+    let _ = writeln!(out, "#define {}_NO_STATE UINT32_MAX", upper);
+    let _ = writeln!(out);
+    let _ = writeln!(out, "{0}_machine *{0}_new(void);", prefix);
+    let _ = writeln!(out, "void {0}_free({0}_machine *m);", prefix);
+    let _ = writeln!(out, "int32_t {0}_start({0}_machine *m);", prefix);
+    let _ = writeln!(out, "int32_t {0}_input({0}_machine *m, uint32_t event, const uint8_t *data, size_t len);", prefix);
+    let _ = writeln!(out, "uint32_t {0}_current(const {0}_machine *m);", prefix);
+    let _ = writeln!(out);
+    let _ = writeln!(out, "#ifdef __cplusplus");
+    let _ = writeln!(out, "}}");
+    let _ = writeln!(out, "#endif");
+    let _ = writeln!(out);
+    let _ = writeln!(out, "#endif");
+    out
+}
+
+/// Generates the C API for a machine, see the module docs.
+///
+/// ```ignore
+/// hsm_ffi!(calc ; StateStruct, States, Events, SharedData ;
+///     new => hsm::StateMachine::new(States::Idle, SharedData::new()) ;
+///     events {
+///         QUIT  = 0 => |_| Some(Events::Quit),
+///         DIGIT = 1 => |data: &[u8]| data.first().map(|d| Events::Digit(*d))
+///     }
+/// );
+/// ```
+///
+/// The state enum must be fieldless, its discriminants are the state IDs.
+#[macro_export]
+macro_rules! hsm_ffi {
+    ($prefix:ident ; $st_str:ty, $st_en:ty, $st_evt:ty, $shr_dat:ty ;
+     new => $new:expr ;
+     events { $($evt_name:ident = $evt_id:expr => $decode:expr),* $(,)* }) => {
+        #[allow(unsafe_code, dead_code, unused_imports)]
+        pub mod $prefix {
+            use super::*;
+
+            pub type Machine = $crate::StateMachine<$st_str, $st_en, $st_evt, $shr_dat>;
+
+            #[export_name = concat!(stringify!($prefix), "_new")]
+            pub extern "C" fn new() -> *mut Machine {
+                let machine: Machine = $new;
+                ::std::boxed::Box::into_raw(::std::boxed::Box::new(machine))
+            }
+
+            /// # Safety
+            /// `m` must come from `new` and not be used afterwards.
+            #[export_name = concat!(stringify!($prefix), "_free")]
+            pub unsafe extern "C" fn free(m: *mut Machine) {
+                if !m.is_null() {
+                    drop(::std::boxed::Box::from_raw(m));
+                }
+            }
+
+            /// # Safety
+            /// `m` must come from `new` or be null.
+            #[export_name = concat!(stringify!($prefix), "_start")]
+            pub unsafe extern "C" fn start(m: *mut Machine) -> i32 {
+                let m = match m.as_mut() {
+                    Some(m) => m,
+                    None    => return $crate::ffi::INVALID
+                };
+                match ::std::panic::catch_unwind(::std::panic::AssertUnwindSafe(|| m.start())) {
+                    Ok(())  => $crate::ffi::OK,
+                    Err(_)  => $crate::ffi::PANICKED
+                }
+            }
+
+            /// # Safety
+            /// `m` must come from `new` or be null, `data` must point to `len` bytes or be null.
+            #[export_name = concat!(stringify!($prefix), "_input")]
+            pub unsafe extern "C" fn input(m: *mut Machine, event: u32, data: *const u8, len: usize) -> i32 {
+                let m = match m.as_mut() {
+                    Some(m) => m,
+                    None    => return $crate::ffi::INVALID
+                };
+                let data: &[u8] = if data.is_null() { &[] } else { ::std::slice::from_raw_parts(data, len) };
+                let evt: Option<$st_evt> = match event {
+                    $( id if id == $evt_id => ($decode)(data), )*
+                    _ => None
+                };
+                match evt {
+                    Some(evt) => match ::std::panic::catch_unwind(::std::panic::AssertUnwindSafe(|| m.input(evt))) {
+                        Ok(Ok(()))  => $crate::ffi::OK,
+                        Ok(Err(_))  => $crate::ffi::REJECTED,
+                        Err(_)      => $crate::ffi::PANICKED
+                    },
+                    None      => $crate::ffi::INVALID
+                }
+            }
+
+            /// # Safety
+            /// `m` must come from `new` or be null.
+            #[export_name = concat!(stringify!($prefix), "_current")]
+            pub unsafe extern "C" fn current(m: *const Machine) -> u32 {
+                match m.as_ref() {
+                    Some(m) => m.current().clone() as u32,
+                    None    => $crate::ffi::NO_STATE
+                }
+            }
+
+            /// The C header declaring this module's functions.
+            pub fn c_header() -> ::std::string::String {
+                let states: ::std::vec::Vec<(::std::string::String, u32)> =
+                    <$st_en as $crate::StateList<$st_en>>::states().into_iter()
+                        .map(|s| (s.to_string(), s as u32))
+                        .collect();
+                $crate::ffi::render_header(stringify!($prefix), &states, &[ $( (stringify!($evt_name), $evt_id) ),* ])
+            }
+        }
+    }
+}
@@ -0,0 +1,233 @@
+/*
+ * The MIT License (MIT)
+ *
+ * Copyright (c) 2015 Mattis Marjak (mattis.marjak@gmail.com)
+ *
+ * Permission is hereby granted, free of charge, to any person obtaining a copy
+ * of this software and associated documentation files (the "Software"), to deal
+ * in the Software without restriction, including without limitation the rights
+ * to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+ * copies of the Software, and to permit persons to whom the Software is
+ * furnished to do so, subject to the following conditions:
+ *
+ * The above copyright notice and this permission notice shall be included in all
+ * copies or substantial portions of the Software.
+ *
+ * THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+ * IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+ * FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+ * AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+ * LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+ * OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+ * SOFTWARE.
+ */
+
+use std::fmt;
+use std::panic::{self, AssertUnwindSafe};
+use super::{StateMachine, StateLookup};
+use explore::panic_message;
+
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum FailureKind {
+    Invariant(String),
+    Panic(String),
+    /// `input` returned an error.
+    Error(String),
+}
+
+#[derive(Debug, Clone)]
+pub struct Failure<UsrEvtEnum> {
+    pub kind         : FailureKind,
+    pub seed         : u64,
+    pub original_len : usize,
+    pub events       : Vec<UsrEvtEnum>,
+}
+
+type Invariant<UsrStEnum, UsrShrData> = (String, Box<dyn Fn(&UsrStEnum, &UsrShrData) -> bool>);
+
+/// Feeds random event sequences to clones of a state machine, checks the
+/// invariants after every event and shrinks the first failing sequence.
+pub struct Fuzzer<UsrStEnum, UsrEvtEnum, UsrShrData> {
+    events     : Vec<(UsrEvtEnum, u32)>,
+    invariants : Vec<Invariant<UsrStEnum, UsrShrData>>,
+    seed       : u64,
+    runs       : usize,
+    length     : usize,
+}
+impl<UsrStEnum, UsrEvtEnum, UsrShrData> fmt::Debug for Fuzzer<UsrStEnum, UsrEvtEnum, UsrShrData>
+    where UsrEvtEnum : fmt::Debug
+{
+    fn fmt(&self, f: &mut fmt::Formatter) -> Result<(), fmt::Error> {
+        f.debug_struct("Fuzzer")
+            .field("events", &self.events)
+            .field("invariants", &self.invariants.iter().map(|i| &i.0).collect::<Vec<_>>())
+            .field("seed", &self.seed)
+            .field("runs", &self.runs)
+            .field("length", &self.length)
+            .finish()
+    }
+}
+impl<UsrStEnum, UsrEvtEnum, UsrShrData> Fuzzer<UsrStEnum, UsrEvtEnum, UsrShrData>
+    where UsrStEnum  : fmt::Debug + Eq + Clone,
+          UsrEvtEnum : fmt::Debug + Clone,
+          UsrShrData : fmt::Debug + Clone,
+{
+    pub fn new(events: Vec<UsrEvtEnum>) -> Self {
+        Self::weighted(events.into_iter().map(|e| (e, 1)).collect())
+    }
+
+    pub fn weighted(events: Vec<(UsrEvtEnum, u32)>) -> Self {
+        assert!(events.iter().any(|e| e.1 > 0), "Fuzzer needs at least one event with a non-zero weight");
+        Fuzzer {
+            events,
+            invariants : Vec::new(),
+            seed       : 0x2545_f491_4f6c_dd1d,
+            runs       : 100,
+            length     : 100,
+        }
+    }
+
+    pub fn seed(mut self, seed: u64) -> Self {
+        self.seed = seed;
+        self
+    }
+
+    pub fn runs(mut self, runs: usize) -> Self {
+        self.runs = runs;
+        self
+    }
+
+    pub fn sequence_length(mut self, length: usize) -> Self {
+        self.length = length;
+        self
+    }
+
+    pub fn invariant<F>(mut self, name: &str, check: F) -> Self
+        where F: Fn(&UsrStEnum, &UsrShrData) -> bool + 'static
+    {
+        self.invariants.push((name.to_string(), Box::new(check)));
+        self
+    }
+
+    pub fn run<UsrStStr>(&self, sm: &StateMachine<UsrStStr, UsrStEnum, UsrEvtEnum, UsrShrData>) -> Result<(), Failure<UsrEvtEnum>>
+        where UsrStStr : fmt::Debug + Clone + StateLookup<UsrStEnum, UsrEvtEnum, UsrShrData>
+    {
+        let mut rng = Rng::new(self.seed);
+        for _ in 0..self.runs {
+            let seed = rng.state;
+            let events: Vec<UsrEvtEnum> = (0..self.length).map(|_| self.pick(&mut rng)).collect();
+            if let Some((kind, len)) = self.execute(sm, &events) {
+                let original_len = len;
+                let events = self.shrink(sm, &kind, events[..len].to_vec());
+                return Err(Failure { kind, seed, original_len, events });
+            }
+        }
+        Ok(())
+    }
+
+    fn pick(&self, rng: &mut Rng) -> UsrEvtEnum {
+        let total: u64 = self.events.iter().map(|e| e.1 as u64).sum();
+        let mut r = rng.next() % total;
+        for &(ref evt, weight) in self.events.iter() {
+            if r < weight as u64 {
+                return evt.clone();
+            }
+            r -= weight as u64;
+        }
+        unreachable!()
+    }
+
+    // Returns the failure and the number of events it took to trigger it.
+    fn execute<UsrStStr>(&self, sm: &StateMachine<UsrStStr, UsrStEnum, UsrEvtEnum, UsrShrData>, events: &[UsrEvtEnum]) -> Option<(FailureKind, usize)>
+        where UsrStStr : fmt::Debug + Clone + StateLookup<UsrStEnum, UsrEvtEnum, UsrShrData>
+    {
+        let mut sm = sm.clone();
+        if !sm.is_started() {
+            if let Err(e) = panic::catch_unwind(AssertUnwindSafe(|| sm.start())) {
+                return Some((FailureKind::Panic(panic_message(e)), 0));
+            }
+        }
+        if let Some(kind) = self.check(&sm) {
+            return Some((kind, 0));
+        }
+        for (i, evt) in events.iter().enumerate() {
+            match panic::catch_unwind(AssertUnwindSafe(|| sm.input(evt.clone()))) {
+                Ok(Ok(()))  => {},
+                Ok(Err(e))  => return Some((FailureKind::Error(e.to_string()), i + 1)),
+                Err(e)      => return Some((FailureKind::Panic(panic_message(e)), i + 1)),
+            }
+            if let Some(kind) = self.check(&sm) {
+                return Some((kind, i + 1));
+            }
+        }
+        None
+    }
+
+    fn check<UsrStStr>(&self, sm: &StateMachine<UsrStStr, UsrStEnum, UsrEvtEnum, UsrShrData>) -> Option<FailureKind>
+        where UsrStStr : fmt::Debug + Clone + StateLookup<UsrStEnum, UsrEvtEnum, UsrShrData>
+    {
+        let shr_data = sm.shared_data();
+        self.invariants.iter()
+            .find(|i| !(i.1)(sm.current(), shr_data))
+            .map(|i| FailureKind::Invariant(i.0.clone()))
+    }
+
+    fn fails_same<UsrStStr>(&self, sm: &StateMachine<UsrStStr, UsrStEnum, UsrEvtEnum, UsrShrData>, kind: &FailureKind, events: &[UsrEvtEnum]) -> bool
+        where UsrStStr : fmt::Debug + Clone + StateLookup<UsrStEnum, UsrEvtEnum, UsrShrData>
+    {
+        match (self.execute(sm, events), kind) {
+            (Some((FailureKind::Invariant(ref a), _)), FailureKind::Invariant(b)) => a == b,
+            (Some((FailureKind::Panic(_), _)), FailureKind::Panic(_))             => true,
+            (Some((FailureKind::Error(ref a), _)), FailureKind::Error(b))         => a == b,
+            _                                                                     => false,
+        }
+    }
+
+    // Removes chunks of halving size for as long as the sequence keeps failing the same way.
+    fn shrink<UsrStStr>(&self, sm: &StateMachine<UsrStStr, UsrStEnum, UsrEvtEnum, UsrShrData>, kind: &FailureKind, mut events: Vec<UsrEvtEnum>) -> Vec<UsrEvtEnum>
+        where UsrStStr : fmt::Debug + Clone + StateLookup<UsrStEnum, UsrEvtEnum, UsrShrData>
+    {
+        let mut chunk = ::std::cmp::max(events.len() / 2, 1);
+        while !events.is_empty() {
+            chunk = ::std::cmp::min(chunk, events.len());
+            let mut reduced = false;
+            let mut i = 0;
+            while i < events.len() {
+                let end = ::std::cmp::min(i + chunk, events.len());
+                let mut candidate = events[..i].to_vec();
+                candidate.extend_from_slice(&events[end..]);
+                if self.fails_same(sm, kind, &candidate) {
+                    events = candidate;
+                    reduced = true;
+                } else {
+                    i += chunk;
+                }
+            }
+            if !reduced {
+                if chunk == 1 {
+                    break;
+                }
+                chunk /= 2;
+            }
+        }
+        events
+    }
+}
+
+#[derive(Debug)]
+struct Rng {
+    state: u64
+}
+impl Rng {
+    fn new(seed: u64) -> Self {
+        Rng { state: if seed == 0 { 1 } else { seed } }
+    }
+
+    fn next(&mut self) -> u64 {
+        self.state ^= self.state >> 12;
+        self.state ^= self.state << 25;
+        self.state ^= self.state >> 27;
+        self.state.wrapping_mul(0x2545_f491_4f6c_dd1d)
+    }
+}
@@ -28,32 +28,153 @@
 
 #[macro_use]
 extern crate log;
+#[cfg(any(feature = "json", feature = "toml"))]
+#[macro_use]
+extern crate serde;
+#[cfg(any(feature = "json", feature = "debug-server"))]
+extern crate serde_json;
+#[cfg(feature = "toml")]
+extern crate toml;
 #[macro_use]
 mod macros;
+mod validate;
+pub mod explore;
+pub mod fuzz;
+mod submachine;
+mod detached;
+mod listener;
+pub mod metrics;
+pub mod coverage;
+pub mod dynamic;
+pub mod bus;
+#[cfg(any(feature = "json", feature = "toml"))]
+pub mod config;
+#[cfg(feature = "json")]
+pub mod trace;
+#[cfg(feature = "ffi")]
+#[macro_use]
+pub mod ffi;
+#[cfg(feature = "debug-server")]
+pub mod debug_server;
+use std::collections::HashMap;
+use std::error::Error;
 use std::fmt;
+use std::mem::{self, Discriminant};
+use std::rc::Rc;
 
+pub use validate::{ValidationError, MAX_DEPTH};
+pub use submachine::Submachine;
+pub use detached::DetachedStateMachine;
+pub use listener::{Listener, variant_name};
+use listener::Listeners;
+#[doc(hidden)]
+pub use validate::in_parent_cycle as _hsm_in_parent_cycle;
 
+
+/// Name of a state or event. The macros return the type or variant name,
+/// states loaded at runtime their own.
 pub trait Name {
-    fn name(&self) -> &'static str;
+    fn name(&self) -> &str;
 }
 
 pub trait Initializer {
     fn new() -> Self;
 }
 
+/// Builds a state, or all states of a machine, from runtime settings.
+pub trait ConfigInitializer<Cfg> {
+    fn with_config(config: &Cfg) -> Self;
+}
+
 #[derive(Debug)]
 pub enum Action<UsrStEnum: fmt::Debug> {
     Ignore,
     Parent,
     DelayedTransition,
+    /// A `DelayedTransition` whose target is known while probing, exit
+    /// handlers see it and a leaf only machine checks it before they run.
+    DelayedTransitionTo(UsrStEnum),
     Transition(UsrStEnum),
+    /// The event isn't for this machine, parents aren't asked and the
+    /// machine's `UnhandledPolicy` decides what happens to it.
+    Unhandled,
 }
 
-#[derive(Debug)]
-pub enum Event<UsrEvtEnum: fmt::Debug> {
-    Enter,
+/// Closure for `UnhandledPolicy::Callback`.
+pub type UnhandledCallback<UsrStEnum, UsrEvtEnum> = Rc<dyn Fn(&UsrStEnum, &UsrEvtEnum)>;
+
+/// What `input` does with an event no state claimed, because a state
+/// returned `Action::Unhandled` or the root returned `Action::Parent`.
+/// Listeners are told through `Listener::unhandled` under every policy.
+#[derive(Clone)]
+pub enum UnhandledPolicy<UsrStEnum, UsrEvtEnum> {
+    Ignore,
+    /// Logs a warning.
+    Log,
+    /// Called with the state that gave up on the event, clones of the
+    /// machine share the closure.
+    Callback(UnhandledCallback<UsrStEnum, UsrEvtEnum>),
+    /// `input` returns `InputError::Unhandled`, the default.
+    Error,
+}
+impl<UsrStEnum, UsrEvtEnum> fmt::Debug for UnhandledPolicy<UsrStEnum, UsrEvtEnum> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> Result<(), fmt::Error> {
+        match *self {
+            UnhandledPolicy::Ignore      => f.write_str("Ignore"),
+            UnhandledPolicy::Log         => f.write_str("Log"),
+            UnhandledPolicy::Callback(_) => f.write_str("Callback"),
+            UnhandledPolicy::Error       => f.write_str("Error"),
+        }
+    }
+}
+
+/// Why `input` couldn't process an event.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum InputError<UsrStEnum, UsrEvtEnum> {
+    /// A leaf only machine was asked to transition into this state, which
+    /// has children but no initial child. The machine stays where it was.
+    CompositeTarget(UsrStEnum),
+    /// No state claimed `event`, `state` is the one that gave up on it.
+    Unhandled { state: UsrStEnum, event: UsrEvtEnum },
+}
+impl<UsrStEnum: fmt::Debug, UsrEvtEnum: fmt::Debug> fmt::Display for InputError<UsrStEnum, UsrEvtEnum> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> Result<(), fmt::Error> {
+        match *self {
+            InputError::CompositeTarget(ref state) =>
+                write!(f, "transition target {:?} has children but no initial child", state),
+            InputError::Unhandled { ref state, ref event } =>
+                write!(f, "event {:?} was not handled, state {:?} gave up on it", event, state),
+        }
+    }
+}
+impl<UsrStEnum: fmt::Debug, UsrEvtEnum: fmt::Debug> Error for InputError<UsrStEnum, UsrEvtEnum> {}
+
+#[derive(Debug, Clone)]
+pub enum Event<UsrEvtEnum: fmt::Debug, UsrStEnum: fmt::Debug> {
+    Enter(Rc<Transition<UsrStEnum, UsrEvtEnum>>),
     User(UsrEvtEnum),
-    Exit
+    Exit(Rc<Transition<UsrStEnum, UsrEvtEnum>>)
+}
+impl<UsrEvtEnum: fmt::Debug, UsrStEnum: fmt::Debug> Event<UsrEvtEnum, UsrStEnum> {
+    pub fn user(&self) -> Option<&UsrEvtEnum> {
+        match *self {
+            Event::User(ref evt) => Some(evt),
+            _                    => None
+        }
+    }
+}
+
+/// Context of the transition an `Event::Enter` or `Event::Exit` belongs to.
+/// `source` is the state whose handler returned the transition, it is `None`
+/// when the machine is started. `target` is `None` for exits that happen
+/// before a `DelayedTransition` handler has picked its target. `trigger`
+/// is the user event that caused the transition, exits that run before the
+/// handler of a `DelayedTransition` get a clone of it.
+#[derive(Debug)]
+pub struct Transition<UsrStEnum, UsrEvtEnum> {
+    pub source  : Option<UsrStEnum>,
+    pub target  : Option<UsrStEnum>,
+    pub trigger : Option<UsrEvtEnum>,
 }
 
 pub trait Parent<UsrStEnum> {
@@ -64,186 +185,678 @@ pub trait InstanceParent<UsrStEnum> {
     fn get_parent(&self) -> Option<UsrStEnum>;
 }
 
+pub trait StateList<UsrStEnum> {
+    fn states() -> Vec<UsrStEnum>;
+}
+
 pub trait State<UsrEvtEnum, UsrStEnum, UsrShrData>
     where Self: Name,
           UsrEvtEnum: fmt::Debug,
           UsrStEnum:  fmt::Debug,
           UsrShrData: fmt::Debug,
 {
-    fn handle_event(&mut self, shr_data: &mut UsrShrData, evt: &Event<UsrEvtEnum>, probe: bool) -> Action<UsrStEnum>;
+    fn handle_event(&mut self, shr_data: &mut UsrShrData, evt: &Event<UsrEvtEnum, UsrStEnum>, probe: bool) -> Action<UsrStEnum>;
+
+    /// Polled on every active state after an event has been processed,
+    /// a returned event is fed to the machine before `input` returns.
+    fn take_event(&mut self) -> Option<UsrEvtEnum> {
+        None
+    }
+
+    /// Whether the state handles `evt`, `None` if it has to be probed.
+    /// Dispatch passes events answered with `Some(false)` straight to the
+    /// parent, as if `handle_event` had returned `Action::Parent`. The
+    /// answers are cached per state and event variant, so dispatch jumps
+    /// from the current state to the first ancestor that doesn't decline
+    /// the event; they must not depend on the event's payload. Enter and
+    /// Exit events always reach `handle_event`.
+    fn handles(&self, _evt: &UsrEvtEnum) -> Option<bool> {
+        None
+    }
 }
-impl<'a, UsrEvtEnum, UsrStEnum, UsrShrData> fmt::Debug for &'a State<UsrEvtEnum, UsrStEnum, UsrShrData> {
+impl<UsrEvtEnum, UsrStEnum, UsrShrData> fmt::Debug for &dyn State<UsrEvtEnum, UsrStEnum, UsrShrData> {
     fn fmt(&self, f:&mut fmt::Formatter) -> Result<(), fmt::Error> {
-        try!(fmt::Debug::fmt(self.name(), f));
-        Ok(())
+        fmt::Debug::fmt(self.name(), f)
     }
 }
 
 
 pub trait StateLookup<UsrStEnum, UsrEvtEnum, UsrShrData> {
-    fn lookup(&mut self, typ: &UsrStEnum) -> &mut State<UsrEvtEnum, UsrStEnum, UsrShrData>;
+    fn lookup(&mut self, typ: &UsrStEnum) -> &mut dyn State<UsrEvtEnum, UsrStEnum, UsrShrData>;
+    fn parent(&self, typ: &UsrStEnum) -> Option<UsrStEnum>;
+    fn states(&self) -> Vec<UsrStEnum>;
+
+    /// Child that is entered when a transition targets this state.
+    fn initial_child(&self, _typ: &UsrStEnum) -> Option<UsrStEnum> {
+        None
+    }
+
+    /// Calls `handle_event` of state `typ`. The macros override the
+    /// `handle_event`, `take_event` and `handles` defaults with a `match`,
+    /// so the state's handler is called directly instead of through `lookup`.
+    fn handle_event(&mut self, typ: &UsrStEnum, shr_data: &mut UsrShrData, evt: &Event<UsrEvtEnum, UsrStEnum>, probe: bool) -> Action<UsrStEnum>
+        where UsrEvtEnum : fmt::Debug,
+              UsrStEnum  : fmt::Debug,
+              UsrShrData : fmt::Debug,
+    {
+        self.lookup(typ).handle_event(shr_data, evt, probe)
+    }
+
+    fn take_event(&mut self, typ: &UsrStEnum) -> Option<UsrEvtEnum>
+        where UsrEvtEnum : fmt::Debug,
+              UsrStEnum  : fmt::Debug,
+              UsrShrData : fmt::Debug,
+    {
+        self.lookup(typ).take_event()
+    }
+
+    fn handles(&mut self, typ: &UsrStEnum, evt: &UsrEvtEnum) -> Option<bool>
+        where UsrEvtEnum : fmt::Debug,
+              UsrStEnum  : fmt::Debug,
+              UsrShrData : fmt::Debug,
+    {
+        self.lookup(typ).handles(evt)
+    }
 }
 
-#[derive(Debug)]
-struct Task<UsrStEnum, UsrEvtEnum>
-    where UsrStEnum:  fmt::Debug,
+/// Hierarchical state machine that owns its shared data, see
+/// `DetachedStateMachine` for one that borrows it for each call.
+#[derive(Debug, Clone)]
+pub struct StateMachine<UsrStStr, UsrStEnum, UsrEvtEnum, UsrShrData>
+    where UsrStStr:   fmt::Debug,
+          UsrStEnum:  fmt::Debug,
           UsrEvtEnum: fmt::Debug,
+          UsrShrData: fmt::Debug,
 {
-    state: UsrStEnum,
-    event: Event<UsrEvtEnum>
+    core     : Core<UsrStStr, UsrStEnum, UsrEvtEnum, UsrShrData>,
+    shr_data : UsrShrData,
 }
-impl<UsrStEnum, UsrEvtEnum> Task<UsrStEnum, UsrEvtEnum>
-    where UsrStEnum:  fmt::Debug,
-          UsrEvtEnum: fmt::Debug
+impl<UsrStStr, UsrStEnum, UsrEvtEnum, UsrShrData> StateMachine<UsrStStr, UsrStEnum, UsrEvtEnum, UsrShrData>
+    where UsrStStr   : fmt::Debug + Initializer + StateLookup<UsrStEnum, UsrEvtEnum, UsrShrData>,
+          UsrStEnum  : fmt::Debug + Eq + Clone,
+          UsrEvtEnum : fmt::Debug + Clone,
+          UsrShrData : fmt::Debug,
 {
-    fn new(state:  UsrStEnum, event: Event<UsrEvtEnum>) -> Self {
-        Task { state:  state, event: event }
+    pub fn new(initial: UsrStEnum, shared_data: UsrShrData) -> Self {
+        Self::with_states(initial, UsrStStr::new(), shared_data)
+    }
+
+    /// Puts the machine back to its initial state with fresh states from
+    /// `Initializer::new`, without running any Exit or Enter actions.
+    /// The shared data is kept.
+    pub fn reset(&mut self) {
+        self.core.reset();
+    }
+
+    /// Stops the machine, resets it and starts it again.
+    pub fn restart(&mut self) {
+        self.core.restart_with(&mut self.shr_data);
     }
 }
+impl<UsrStStr, UsrStEnum, UsrEvtEnum, UsrShrData> StateMachine<UsrStStr, UsrStEnum, UsrEvtEnum, UsrShrData>
+    where UsrStStr   : fmt::Debug + StateLookup<UsrStEnum, UsrEvtEnum, UsrShrData>,
+          UsrStEnum  : fmt::Debug + Eq + Clone,
+          UsrEvtEnum : fmt::Debug + Clone,
+          UsrShrData : fmt::Debug,
+{
+    pub fn with_states(initial: UsrStEnum, states: UsrStStr, shared_data: UsrShrData) -> Self {
+        StateMachine {
+            core     : Core::with_states(initial, states),
+            shr_data : shared_data,
+        }
+    }
 
-#[derive(Debug)]
-pub struct StateMachine<UsrStStr, UsrStEnum, UsrEvtEnum, UsrShrData>
+    /// Machine whose states are built from `config`, see `ConfigInitializer`.
+    pub fn with_config<Cfg>(initial: UsrStEnum, config: &Cfg, shared_data: UsrShrData) -> Self
+        where UsrStStr : ConfigInitializer<Cfg>
+    {
+        Self::with_states(initial, UsrStStr::with_config(config), shared_data)
+    }
+
+    /// Like `with_config`, with the shared data also built from `config`.
+    pub fn from_config<Cfg>(initial: UsrStEnum, config: &Cfg) -> Self
+        where UsrStStr   : ConfigInitializer<Cfg>,
+              UsrShrData : ConfigInitializer<Cfg>
+    {
+        Self::with_config(initial, config, UsrShrData::with_config(config))
+    }
+
+    /// Like `reset`, with fresh states built from `config`.
+    pub fn reset_with_config<Cfg>(&mut self, config: &Cfg)
+        where UsrStStr : ConfigInitializer<Cfg>
+    {
+        self.core.reset_states(UsrStStr::with_config(config));
+    }
+
+    pub fn current(&self) -> &UsrStEnum {
+        &self.core.current
+    }
+
+    /// The current state and its ancestors, leaf to root.
+    pub fn active_states(&self) -> Vec<UsrStEnum> {
+        self.core.active_states()
+    }
+
+    pub fn is_started(&self) -> bool {
+        self.core.started
+    }
+
+    /// Keeps `current` a leaf: a transition into a state with children and
+    /// no initial child makes `input` return `InputError::CompositeTarget`
+    /// instead of stopping in that state. The transition is refused before
+    /// any Exit action runs. A plain `DelayedTransition` only picks its target
+    /// in the action, after the exits, so the exited states are entered again
+    /// before `input` returns the error; name the target with
+    /// `DelayedTransitionTo` to refuse it up front.
+    pub fn set_leaf_only(&mut self, leaf_only: bool) {
+        self.core.set_leaf_only(leaf_only);
+    }
+
+    pub fn is_leaf_only(&self) -> bool {
+        self.core.leaf_only
+    }
+
+    pub fn set_unhandled_policy(&mut self, policy: UnhandledPolicy<UsrStEnum, UsrEvtEnum>) {
+        self.core.unhandled = policy;
+    }
+
+    pub fn shared_data(&self) -> &UsrShrData {
+        &self.shr_data
+    }
+
+    pub fn shared_data_mut(&mut self) -> &mut UsrShrData {
+        &mut self.shr_data
+    }
+
+    /// Attaches a listener, clones of the machine don't keep it.
+    pub fn add_listener(&mut self, listener: Box<dyn Listener<UsrStEnum, UsrEvtEnum> + Send>) {
+        self.core.listeners.0.push(listener);
+    }
+
+    pub fn all_states(&self) -> Vec<UsrStEnum> {
+        self.core.states.states()
+    }
+
+    pub fn validate(&self) -> Result<(), ValidationError<UsrStEnum>> {
+        self.core.validate()
+    }
+
+    /// Enters the initial state and its ancestors. Panics if the machine is
+    /// invalid, or if it is leaf only and the initial state has children but
+    /// no initial child.
+    pub fn start(&mut self) {
+        self.core.start_with(&mut self.shr_data);
+    }
+
+    /// Runs the Exit actions of all active states, leaf to root, does
+    /// nothing if the machine isn't started.
+    pub fn stop(&mut self) {
+        self.core.stop_with(&mut self.shr_data);
+    }
+
+    /// Events taken after a failed event stay pending until the next input.
+    pub fn input(&mut self, evt: UsrEvtEnum) -> Result<(), InputError<UsrStEnum, UsrEvtEnum>> {
+        self.core.input_with(&mut self.shr_data, evt)
+    }
+}
+
+// The machine without its shared data, which every call that runs handlers
+// borrows. `StateMachine` lends it its own, `DetachedStateMachine` the
+// caller's.
+#[derive(Debug, Clone)]
+pub(crate) struct Core<UsrStStr, UsrStEnum, UsrEvtEnum, UsrShrData>
     where UsrStStr:   fmt::Debug,
           UsrStEnum:  fmt::Debug,
           UsrEvtEnum: fmt::Debug,
           UsrShrData: fmt::Debug,
 {
     current     : UsrStEnum,
+    initial     : UsrStEnum,
     started     : bool,
+    leaf_only   : bool,
+    composites  : Vec<UsrStEnum>,
+    unhandled   : UnhandledPolicy<UsrStEnum, UsrEvtEnum>,
     states      : UsrStStr,
-    shr_data    : UsrShrData,
-    exit_tasks  : Vec<Task<UsrStEnum, UsrEvtEnum>>,
-    enter_tasks : Vec<Task<UsrStEnum, UsrEvtEnum>>,
-    _phantom    : ::std::marker::PhantomData<UsrEvtEnum>
+    exit_tasks  : Vec<UsrStEnum>,
+    enter_tasks : Vec<UsrStEnum>,
+    skips       : Skips<UsrStEnum, UsrEvtEnum>,
+    listeners   : Listeners<UsrStEnum, UsrEvtEnum>,
+    _phantom    : ::std::marker::PhantomData<(UsrEvtEnum, UsrShrData)>
 }
-impl<UsrStStr, UsrStEnum, UsrEvtEnum, UsrShrData> StateMachine<UsrStStr, UsrStEnum, UsrEvtEnum, UsrShrData>
-    where UsrStStr   : fmt::Debug +Initializer + StateLookup<UsrStEnum, UsrEvtEnum, UsrShrData>,
-          UsrStEnum  : fmt::Debug + Eq + Clone + InstanceParent<UsrStEnum>,
-          UsrEvtEnum : fmt::Debug,
+
+// The states from `from` up that decline an event variant, and the state
+// dispatch goes on with. `to` declines it too if it is the root.
+#[derive(Debug, Clone)]
+struct Skip<UsrStEnum> {
+    from     : UsrStEnum,
+    skipped  : Vec<UsrStEnum>,
+    to       : UsrStEnum,
+    declined : bool,
+}
+
+// Skips by event variant. Left out of the `Debug` output, which the
+// explorer compares configurations by, as it doesn't change behaviour.
+#[derive(Clone)]
+struct Skips<UsrStEnum, UsrEvtEnum>(HashMap<Discriminant<UsrEvtEnum>, Vec<Skip<UsrStEnum>>>);
+impl<UsrStEnum, UsrEvtEnum> fmt::Debug for Skips<UsrStEnum, UsrEvtEnum> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> Result<(), fmt::Error> {
+        f.write_str("Skips")
+    }
+}
+impl<UsrStStr, UsrStEnum, UsrEvtEnum, UsrShrData> Core<UsrStStr, UsrStEnum, UsrEvtEnum, UsrShrData>
+    where UsrStStr   : fmt::Debug + Initializer + StateLookup<UsrStEnum, UsrEvtEnum, UsrShrData>,
+          UsrStEnum  : fmt::Debug + Eq + Clone,
+          UsrEvtEnum : fmt::Debug + Clone,
           UsrShrData : fmt::Debug,
 {
-    pub fn new(initial: UsrStEnum, shared_data: UsrShrData) -> Self {
-        StateMachine {
-            current     : initial,
+    pub fn reset(&mut self) {
+        self.reset_states(UsrStStr::new());
+    }
+
+    pub fn restart_with(&mut self, shr_data: &mut UsrShrData) {
+        self.stop_with(shr_data);
+        self.reset();
+        self.start_with(shr_data);
+    }
+}
+impl<UsrStStr, UsrStEnum, UsrEvtEnum, UsrShrData> Core<UsrStStr, UsrStEnum, UsrEvtEnum, UsrShrData>
+    where UsrStStr   : fmt::Debug + StateLookup<UsrStEnum, UsrEvtEnum, UsrShrData>,
+          UsrStEnum  : fmt::Debug + Eq + Clone,
+          UsrEvtEnum : fmt::Debug + Clone,
+          UsrShrData : fmt::Debug,
+{
+    pub fn with_states(initial: UsrStEnum, states: UsrStStr) -> Self {
+        Core {
+            current     : initial.clone(),
+            initial,
             started     : false,
-            states      : UsrStStr::new(),
-            shr_data    : shared_data,
+            leaf_only   : false,
+            composites  : Vec::new(),
+            unhandled   : UnhandledPolicy::Error,
+            states,
             exit_tasks  : Vec::new(),
             enter_tasks : Vec::new(),
+            skips       : Skips(HashMap::new()),
+            listeners   : Listeners(Vec::new()),
             _phantom    : ::std::marker::PhantomData
         }
     }
 
-    pub fn start(&mut self) {
+    pub fn reset_states(&mut self, states: UsrStStr) {
+        self.states = states;
+        self.composites = self.find_composites();
+        self.skips.0.clear();
+        self.current = self.initial.clone();
+        self.started = false;
+        self.clear_tasks();
+    }
+
+    pub fn active_states(&self) -> Vec<UsrStEnum> {
+        let mut active = Vec::new();
+        let mut parent = Some(self.current.clone());
+        while let Some(state) = parent {
+            parent = self.states.parent(&state);
+            active.push(state);
+        }
+        active
+    }
+
+    pub fn set_leaf_only(&mut self, leaf_only: bool) {
+        self.leaf_only = leaf_only;
+        self.composites = self.find_composites();
+    }
+
+    // States with children, only collected for leaf only machines.
+    fn find_composites(&self) -> Vec<UsrStEnum> {
+        if !self.leaf_only {
+            return Vec::new();
+        }
+        let states = self.states.states();
+        states.iter().filter(|&s| states.iter().any(|c| self.states.parent(c).as_ref() == Some(s))).cloned().collect()
+    }
+
+    pub fn validate(&self) -> Result<(), ValidationError<UsrStEnum>> {
+        validate::check(&self.current, &self.states.states(),
+                        |s| self.states.parent(s), |s| self.states.initial_child(s))
+    }
+
+    pub fn start_with(&mut self, shr_data: &mut UsrShrData) {
+        self.clear_tasks();
+        if cfg!(debug_assertions) {
+            if let Err(e) = self.validate() {
+                panic!("Invalid state machine: {}", e);
+            }
+        }
+        let ctx = Rc::new(Transition { source: None, target: Some(self.current.clone()), trigger: None });
+        self.current = self.descend(self.current.clone());
+        if let Err(e) = self.check_leaf(&self.current) {
+            panic!("Invalid state machine: {}", e);
+        }
         let mut parent = Some(self.current.clone());
         while let Some(state) = parent {
-            parent = state.get_parent();
-            self.enter_tasks.push(Task::new(state, Event::Enter));
+            parent = self.states.parent(&state);
+            self.enter_tasks.push(state);
         }
-        self.process_enter_tasks();
+        self.process_enter_tasks(shr_data, &ctx);
         self.started = true;
+        self.notify_done();
+        if let Err(e) = self.process_taken_events(shr_data) {
+            panic!("Event taken on start failed: {}", e);
+        }
+    }
+
+    pub fn stop_with(&mut self, shr_data: &mut UsrShrData) {
+        if !self.started {
+            return;
+        }
+        self.clear_tasks();
+        let mut parent = Some(self.current.clone());
+        while let Some(state) = parent {
+            parent = self.states.parent(&state);
+            self.exit_tasks.push(state);
+        }
+        let ctx = Rc::new(Transition { source: Some(self.current.clone()), target: None, trigger: None });
+        self.process_exit_tasks(shr_data, &ctx);
+        self.started = false;
+        self.notify_done();
+    }
+
+    // Drops exits and enters left over by a handler that panicked, a caller
+    // that caught the panic may keep using the machine.
+    fn clear_tasks(&mut self) {
+        self.exit_tasks.clear();
+        self.enter_tasks.clear();
+    }
+
+    fn take_event(&mut self) -> Option<UsrEvtEnum> {
+        let mut parent = Some(self.current.clone());
+        while let Some(state) = parent {
+            if let Some(evt) = self.states.take_event(&state) {
+                return Some(evt);
+            }
+            parent = self.states.parent(&state);
+        }
+        None
     }
 
-    fn process_exit_tasks(&mut self) {
-        for task in self.exit_tasks.iter() {
-            debug!("send {:?} to {:?}", task.event, task.state);
-            match self.states.lookup(&task.state).handle_event(
-                  &mut self.shr_data, &task.event, false){
-                Action::Ignore | Action::Parent => {},
+    fn process_taken_events(&mut self, shr_data: &mut UsrShrData) -> Result<(), InputError<UsrStEnum, UsrEvtEnum>> {
+        while let Some(evt) = self.take_event() {
+            debug!("taken:  {:?}", evt);
+            self.dispatch(shr_data, evt)?;
+        }
+        Ok(())
+    }
+
+    fn process_exit_tasks(&mut self, shr_data: &mut UsrShrData, ctx: &Rc<Transition<UsrStEnum, UsrEvtEnum>>) {
+        for state in self.exit_tasks.iter() {
+            for l in self.listeners.0.iter_mut() {
+                l.exit(state);
+            }
+            let event = Event::Exit(ctx.clone());
+            debug!("send {:?} to {:?}", event, state);
+            match self.states.handle_event(
+                  state, shr_data, &event, false){
+                Action::Ignore | Action::Parent | Action::Unhandled => {},
                 _ => panic!("Transitions from exit events are not allowed, \
                             ignoring transition from state {:?} on event {:?}",
-                            task.state, task.event)
+                            state, event)
             };
         }
         self.exit_tasks.clear();
     }
 
-    fn process_enter_tasks(&mut self) {
+    fn process_enter_tasks(&mut self, shr_data: &mut UsrShrData, ctx: &Rc<Transition<UsrStEnum, UsrEvtEnum>>) {
         self.enter_tasks.reverse();
-        for task in self.enter_tasks.iter() {
-            debug!("send {:?} to {:?}", task.event, task.state);
-            match self.states.lookup(&task.state).handle_event(
-                  &mut self.shr_data, &task.event, false){
-                Action::Ignore | Action::Parent => {},
+        for state in self.enter_tasks.iter() {
+            for l in self.listeners.0.iter_mut() {
+                l.enter(state);
+            }
+            let event = Event::Enter(ctx.clone());
+            debug!("send {:?} to {:?}", event, state);
+            match self.states.handle_event(
+                  state, shr_data, &event, false){
+                Action::Ignore | Action::Parent | Action::Unhandled => {},
                 _ => panic!("Transitions from enter events are not allowed, \
                             ignoring transition from state {:?} on event {:?}",
-                            task.state, task.event)
+                            state, event)
             }
         }
         self.enter_tasks.clear();
     }
 
-    fn transition(&mut self, from_state: UsrStEnum, to_state: UsrStEnum) {
+    fn notify_handled(&mut self, state: &UsrStEnum, evt: &UsrEvtEnum, action: &Action<UsrStEnum>) {
+        for l in self.listeners.0.iter_mut() {
+            l.handled(state, evt, action);
+        }
+    }
+
+    fn notify_done(&mut self) {
+        for l in self.listeners.0.iter_mut() {
+            l.done(&self.current);
+        }
+    }
+
+    // Follows initial children down from `state`.
+    fn descend(&self, mut state: UsrStEnum) -> UsrStEnum {
+        for _ in 0..MAX_DEPTH {
+            match self.states.initial_child(&state) {
+                Some(child) => state = child,
+                None        => break
+            }
+        }
+        state
+    }
+
+    fn unhandled(&mut self, state: UsrStEnum, evt: Event<UsrEvtEnum, UsrStEnum>) -> Result<(), InputError<UsrStEnum, UsrEvtEnum>> {
+        self.exit_tasks.clear();
+        let evt = match trigger(evt) {
+            Some(evt) => evt,
+            None      => return Ok(())
+        };
+        for l in self.listeners.0.iter_mut() {
+            l.unhandled(&state, &evt);
+        }
+        match self.unhandled {
+            UnhandledPolicy::Ignore      => Ok(()),
+            UnhandledPolicy::Log         => {
+                warn!("Event {:?} was not handled, state {:?} gave up on it", evt, state);
+                Ok(())
+            },
+            UnhandledPolicy::Callback(ref f) => {
+                f(&state, &evt);
+                Ok(())
+            },
+            UnhandledPolicy::Error       => Err(InputError::Unhandled { state, event: evt }),
+        }
+    }
+
+    // Fails if `state`, already descended, is composite in a leaf only machine.
+    fn check_leaf(&self, state: &UsrStEnum) -> Result<(), InputError<UsrStEnum, UsrEvtEnum>> {
+        if self.composites.contains(state) {
+            return Err(InputError::CompositeTarget(state.clone()));
+        }
+        Ok(())
+    }
+
+    fn transition(&mut self, shr_data: &mut UsrShrData, from_state: UsrStEnum, to_state: UsrStEnum, ctx: &Rc<Transition<UsrStEnum, UsrEvtEnum>>) {
+        let to_state = self.descend(to_state);
+        for l in self.listeners.0.iter_mut() {
+            l.transition(&self.current, &to_state);
+        }
+        self.current = to_state.clone();
         let mut parent = Some(from_state);
         while let Some(state) = parent {
-            parent = state.get_parent();
-            self.exit_tasks.push(Task::new(state, Event::Exit));
+            parent = self.states.parent(&state);
+            self.exit_tasks.push(state);
         }
         let mut same_idx: Option<usize> = None;
         parent = Some(to_state);
-        'outer: while let Some(state) = parent {
-            for (i, task) in self.exit_tasks.iter().enumerate() {
-                if task.state == state {
-                    same_idx = Some(i);
-                    break 'outer;
-                }
+        while let Some(state) = parent {
+            if let Some(i) = self.exit_tasks.iter().position(|s| *s == state) {
+                same_idx = Some(i);
+                break;
             }
-            parent = state.get_parent();
-            self.enter_tasks.push(Task::new(state, Event::Enter));
+            parent = self.states.parent(&state);
+            self.enter_tasks.push(state);
         }
         if let Some(i) = same_idx {
-            let drop_num = self.exit_tasks.len() - i;
-            for _ in 0..drop_num {
-                self.exit_tasks.pop();
-            }
+            self.exit_tasks.truncate(i);
         }
-        self.process_exit_tasks();
-        self.process_enter_tasks();
+        self.process_exit_tasks(shr_data, ctx);
+        self.process_enter_tasks(shr_data, ctx);
     }
 
-    pub fn input(&mut self, evt: UsrEvtEnum) {
+    pub fn input_with(&mut self, shr_data: &mut UsrShrData, evt: UsrEvtEnum) -> Result<(), InputError<UsrStEnum, UsrEvtEnum>> {
         assert!(self.started, "Can't call input before starting the state machine with start()");
+        self.dispatch(shr_data, evt)?;
+        self.process_taken_events(shr_data)
+    }
+
+    fn dispatch(&mut self, shr_data: &mut UsrShrData, evt: UsrEvtEnum) -> Result<(), InputError<UsrStEnum, UsrEvtEnum>> {
+        self.clear_tasks();
+        for l in self.listeners.0.iter_mut() {
+            l.event(&self.current, &evt);
+        }
         let evt = Event::User(evt);
         debug!("state:  {:?}", self.current);
         debug!("input:  {:?}", evt);
         let mut action;
-        let mut state = self.current.clone();
+        let mut result = Ok(());
+        let (mut state, mut skip) = self.skip_declined(self.current.clone(), &evt);
         loop {
-            action = self.states.lookup(&state).handle_event(&mut self.shr_data, &evt, true);
+            action = if skip {
+                Action::Parent
+            } else {
+                self.states.handle_event(&state, shr_data, &evt, true)
+            };
+            if let (Some(usr), false) = (evt.user(), self.listeners.0.is_empty()) {
+                match action {
+                    Action::Ignore | Action::Transition(_) => self.notify_handled(&state, usr, &action),
+                    _                                      => {}
+                }
+            }
             match action {
-                Action::Ignore               => {
+                Action::Ignore                 => {
                     self.exit_tasks.clear();
                     break;
                 },
-                Action::Parent               => {
-                    if let Some(parent) = state.get_parent() {
-                        self.exit_tasks.push(Task::new(state.clone(), Event::Exit));
-                        state = parent;
+                Action::Parent                 => {
+                    if let Some(parent) = self.states.parent(&state) {
+                        self.exit_tasks.push(state.clone());
+                        let (next, declined) = self.skip_declined(parent, &evt);
+                        state = next;
+                        skip = declined;
                     } else {
-                        panic!("State {:?} responded with Action::Parent to event {:?}, but the state has no parent", state, evt);
-                        // break;
+                        result = self.unhandled(state, evt);
+                        break;
                     }
                 },
-                Action::Transition(x)        => {
-                    debug!("send {:?} to {:?}", evt, state);
-                    self.process_exit_tasks();  // exit until in the parent that handles the signal
-                    self.current = x.clone(); // signal allready handled
-                    self.transition(state.clone(), x);
+                Action::Unhandled              => {
+                    result = self.unhandled(state, evt);
                     break;
                 },
-                Action::DelayedTransition => {
-                    self.process_exit_tasks(); // exit until in the parent that handles the signal
-                    debug!("send {:?} to {:?}", evt, state);
-                    if let Action::Transition(x) = self.states.lookup(&state).handle_event(&mut self.shr_data, &evt, false) { // handle the signal
-                        self.current = x.clone();
-                        self.transition(state.clone(), x);
-                    } else {
-                        panic!("State {:?} probed Action::DelayedTransition to event {:?}, but doesn't return Action::Transition", state, evt);
-                        // self.current = state;
+                Action::Transition(x)          => {
+                    if let Err(e) = self.check_leaf(&self.descend(x.clone())) {
+                        self.exit_tasks.clear();
+                        result = Err(e);
+                        break;
                     }
+                    debug!("send {:?} to {:?}", evt, state);
+                    let ctx = Rc::new(Transition { source: Some(state.clone()), target: Some(x.clone()), trigger: trigger(evt) });
+                    self.process_exit_tasks(shr_data, &ctx);  // exit until in the parent that handles the signal
+                    self.transition(shr_data, state, x, &ctx); // signal allready handled
+                    break;
+                },
+                Action::DelayedTransition      => {
+                    result = self.delayed_transition(shr_data, state, evt, None);
+                    break;
+                },
+                Action::DelayedTransitionTo(x) => {
+                    result = self.delayed_transition(shr_data, state, evt, Some(x));
                     break;
                 },
             }
         }
+        self.notify_done();
+        result
+    }
+
+    // Moves up from `state` past the states whose `handles` declines `evt`,
+    // queueing their exits, and returns the state to probe next and whether
+    // it declines `evt` as well. The walk is cached per event variant.
+    fn skip_declined(&mut self, state: UsrStEnum, evt: &Event<UsrEvtEnum, UsrStEnum>) -> (UsrStEnum, bool) {
+        let usr = match *evt {
+            Event::User(ref usr) => usr,
+            _                    => return (state, false)
+        };
+        let key = mem::discriminant(usr);
+        if let Some(skip) = self.skips.0.get(&key).and_then(|skips| skips.iter().find(|skip| skip.from == state)) {
+            self.exit_tasks.extend(skip.skipped.iter().cloned());
+            return (skip.to.clone(), skip.declined);
+        }
+        let mut skipped = Vec::new();
+        let mut to = state.clone();
+        let mut declined = self.states.handles(&to, usr) == Some(false);
+        while declined {
+            match self.states.parent(&to) {
+                Some(parent) => {
+                    skipped.push(to);
+                    declined = self.states.handles(&parent, usr) == Some(false);
+                    to = parent;
+                },
+                None         => break
+            }
+        }
+        self.exit_tasks.extend(skipped.iter().cloned());
+        self.skips.0.entry(key).or_default().push(Skip { from: state, skipped, to: to.clone(), declined });
+        (to, declined)
+    }
+
+    // The exits up to `state` run before the handler is asked again for the
+    // target, with a target given while probing it is checked first. A
+    // target picked by the action is checked after it, the states exited for
+    // it are then entered again.
+    fn delayed_transition(&mut self, shr_data: &mut UsrShrData, state: UsrStEnum, evt: Event<UsrEvtEnum, UsrStEnum>, target: Option<UsrStEnum>) -> Result<(), InputError<UsrStEnum, UsrEvtEnum>> {
+        if let Some(ref x) = target {
+            if let Err(e) = self.check_leaf(&self.descend(x.clone())) {
+                self.exit_tasks.clear();
+                return Err(e);
+            }
+        }
+        let ctx = Rc::new(Transition { source: Some(state.clone()), target: target.clone(), trigger: evt.user().cloned() });
+        let exited = match target {
+            Some(_) => Vec::new(),
+            None    => self.exit_tasks.clone()
+        };
+        self.process_exit_tasks(shr_data, &ctx); // exit until in the parent that handles the signal
+        debug!("send {:?} to {:?}", evt, state);
+        let x = match self.states.handle_event(&state, shr_data, &evt, false) { // handle the signal
+            Action::Transition(x) => x,
+            _ => panic!("State {:?} probed Action::DelayedTransition to event {:?}, but doesn't return Action::Transition", state, evt)
+        };
+        match target {
+            Some(ref target) => assert!(*target == x, "State {:?} probed a delayed transition to {:?} on event {:?}, but returned {:?}", state, target, evt, x),
+            None => if let Err(e) = self.check_leaf(&self.descend(x.clone())) {
+                self.enter_tasks = exited;
+                self.process_enter_tasks(shr_data, &ctx);
+                return Err(e);
+            }
+        }
+        let ctx = Rc::new(Transition { source: Some(state.clone()), target: Some(x.clone()), trigger: trigger(evt) });
+        if let Some(usr) = ctx.trigger.as_ref() {
+            self.notify_handled(&state, usr, &Action::Transition(x.clone()));
+        }
+        self.transition(shr_data, state, x, &ctx);
+        Ok(())
+    }
+}
+
+fn trigger<UsrEvtEnum, UsrStEnum>(evt: Event<UsrEvtEnum, UsrStEnum>) -> Option<UsrEvtEnum>
+    where UsrEvtEnum: fmt::Debug,
+          UsrStEnum:  fmt::Debug,
+{
+    match evt {
+        Event::User(evt) => Some(evt),
+        _                => None
     }
 }
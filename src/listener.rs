@@ -0,0 +1,97 @@
+/*
+ * The MIT License (MIT)
+ *
+ * Copyright (c) 2015 Mattis Marjak (mattis.marjak@gmail.com)
+ *
+ * Permission is hereby granted, free of charge, to any person obtaining a copy
+ * of this software and associated documentation files (the "Software"), to deal
+ * in the Software without restriction, including without limitation the rights
+ * to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+ * copies of the Software, and to permit persons to whom the Software is
+ * furnished to do so, subject to the following conditions:
+ *
+ * The above copyright notice and this permission notice shall be included in all
+ * copies or substantial portions of the Software.
+ *
+ * THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+ * IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+ * FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+ * AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+ * LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+ * OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+ * SOFTWARE.
+ */
+
+use std::fmt;
+use std::sync::{Arc, Mutex};
+use super::Action;
+
+
+/// Observer of a `StateMachine`, attached with `StateMachine::add_listener`.
+/// All methods do nothing by default.
+pub trait Listener<UsrStEnum: fmt::Debug, UsrEvtEnum> {
+    /// `evt` was received while `state` was the current state.
+    fn event(&mut self, _state: &UsrStEnum, _evt: &UsrEvtEnum) {}
+    /// `state` claimed `evt` and returned `action`.
+    fn handled(&mut self, _state: &UsrStEnum, _evt: &UsrEvtEnum, _action: &Action<UsrStEnum>) {}
+    /// No state claimed `evt`, `state` is the one that gave up on it.
+    fn unhandled(&mut self, _state: &UsrStEnum, _evt: &UsrEvtEnum) {}
+    /// The current state changed from `source` to `target`.
+    fn transition(&mut self, _source: &UsrStEnum, _target: &UsrStEnum) {}
+    fn enter(&mut self, _state: &UsrStEnum) {}
+    fn exit(&mut self, _state: &UsrStEnum) {}
+    /// The machine finished starting, stopping or handling an event and
+    /// `state` is current.
+    fn done(&mut self, _state: &UsrStEnum) {}
+}
+
+// Lets the owner keep a handle to a listener that is attached to a machine.
+impl<UsrStEnum, UsrEvtEnum, L> Listener<UsrStEnum, UsrEvtEnum> for Arc<Mutex<L>>
+    where UsrStEnum : fmt::Debug,
+          L         : Listener<UsrStEnum, UsrEvtEnum>,
+{
+    fn event(&mut self, state: &UsrStEnum, evt: &UsrEvtEnum) {
+        self.lock().unwrap().event(state, evt)
+    }
+    fn handled(&mut self, state: &UsrStEnum, evt: &UsrEvtEnum, action: &Action<UsrStEnum>) {
+        self.lock().unwrap().handled(state, evt, action)
+    }
+    fn unhandled(&mut self, state: &UsrStEnum, evt: &UsrEvtEnum) {
+        self.lock().unwrap().unhandled(state, evt)
+    }
+    fn transition(&mut self, source: &UsrStEnum, target: &UsrStEnum) {
+        self.lock().unwrap().transition(source, target)
+    }
+    fn enter(&mut self, state: &UsrStEnum) {
+        self.lock().unwrap().enter(state)
+    }
+    fn exit(&mut self, state: &UsrStEnum) {
+        self.lock().unwrap().exit(state)
+    }
+    fn done(&mut self, state: &UsrStEnum) {
+        self.lock().unwrap().done(state)
+    }
+}
+
+/// Variant name of an enum value's `Debug` output, `Digit(3)` and
+/// `Key { code: 1 }` give `Digit` and `Key`. Listeners that record events
+/// by their `Debug` output use it to group events by variant.
+pub fn variant_name(debug: &str) -> &str {
+    match debug.find(['(', ' ', '{']) {
+        Some(i) => &debug[..i],
+        None    => debug
+    }
+}
+
+/// Listeners attached to a machine. Clones of a machine start without any.
+pub(crate) struct Listeners<UsrStEnum, UsrEvtEnum>(pub Vec<Box<dyn Listener<UsrStEnum, UsrEvtEnum> + Send>>);
+impl<UsrStEnum, UsrEvtEnum> fmt::Debug for Listeners<UsrStEnum, UsrEvtEnum> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> Result<(), fmt::Error> {
+        write!(f, "{} listeners", self.0.len())
+    }
+}
+impl<UsrStEnum, UsrEvtEnum> Clone for Listeners<UsrStEnum, UsrEvtEnum> {
+    fn clone(&self) -> Self {
+        Listeners(Vec::new())
+    }
+}
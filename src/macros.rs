@@ -24,6 +24,9 @@
 
 #[macro_export]
 macro_rules! hsm_define_objects_noparents {
+    (mod $module:ident, $($rest:tt)*) => {
+        _hsm_module!($module, hsm_define_objects_noparents!($($rest)*));
+    };
     ($st_str:ident, $st_en:ident, $st_evt:ty, $shr_dat:ty, ( $($s:ident),* ) ) => {
         hsm_define_objects!($st_str, $st_en, $st_evt, $shr_dat, ($($s),*) );
         hsm_state_parents!($st_en ; $($s -> None),*);
@@ -31,27 +34,140 @@ macro_rules! hsm_define_objects_noparents {
     ($st_str:ident, $st_en:ident, $st_evt:ty, $shr_dat:ty, ( $($s:ident $x:tt),*)) => {
         hsm_define_objects!($st_str, $st_en, $st_evt, $shr_dat, ($($s $x),*) );
         hsm_state_parents!($st_en ; $($s -> None),*);
+    };
+    ($st_str:ident, $st_en:ident, $st_evt:ty, $shr_dat:ty, ( $($s:ident $x:tt),*) with $cfg:ident : $cfg_ty:ty) => {
+        hsm_define_objects!($st_str, $st_en, $st_evt, $shr_dat, ($($s $x),*) with $cfg : $cfg_ty);
+        hsm_state_parents!($st_en ; $($s -> None),*);
+    };
+    ($st_str:ident < $($g:ident),* >, $st_en:ident, $st_evt:ty, $shr_dat:ty, ( $($states:tt)* ) $($wc:tt)*) => {
+        hsm_define_objects!($st_str < $($g),* >, $st_en, $st_evt, $shr_dat, { $($states)* } $($wc)*);
     }
 }
 
 #[macro_export]
 macro_rules! hsm_define_objects {
+    // `mod name, ..` puts the generated items into `pub mod name`, so several
+    // machines can share a module
+    (mod $module:ident, $($rest:tt)*) => {
+        _hsm_module!($module, hsm_define_objects!($($rest)*));
+    };
     ($st_str:ident, $st_en:ident, $st_evt:ty, $shr_dat:ty, ( $($s:ident),* ) ) => {
-        use $crate::Parent;
         _hsm_create_states!($($s),*);
         _hsm_create_state_enum!($st_en, ($($s),*));
         _hsm_create_state_struct!($st_str, $st_en, $st_evt, $shr_dat, ($($s),*) );
     };
     ($st_str:ident, $st_en:ident, $st_evt:ty, $shr_dat:ty, ( $($s:ident $x:tt),*)) => {
-        use $crate::Parent;
         _hsm_create_states!( $($s $x),* );
         _hsm_create_state_enum!($st_en, ($($s),*));
         _hsm_create_state_struct!($st_str, $st_en, $st_evt, $shr_dat, ($($s),*) );
+    };
+    // `(..) with cfg: Settings` lets field defaults use `cfg`, the states then
+    // implement `ConfigInitializer<Settings>` instead of `Initializer`, see
+    // `StateMachine::with_config`
+    ($st_str:ident, $st_en:ident, $st_evt:ty, $shr_dat:ty, ( $($s:ident $x:tt),*) with $cfg:ident : $cfg_ty:ty) => {
+        $( _hsm_create_config_state!($cfg : $cfg_ty ; $s $x); )*
+        _hsm_create_state_enum!($st_en, ($($s),*));
+        _hsm_create_state_struct!($st_str [] [] [config $cfg_ty], $st_en, $st_evt, $shr_dat, ($($s),*) ; );
+    };
+    // Nested hierarchy, `{ Root { Idle, Active { Connecting, Connected } } }`.
+    // The first child of a state is its initial child, fields go in braces
+    // before the children: `Active {retries: u8 = 0} { Connecting, Connected }`
+    ($st_str:ident, $st_en:ident, $st_evt:ty, $shr_dat:ty, { $($tree:tt)* }) => {
+        _hsm_tree!(@munch [$st_str [] [], $st_en, $st_evt, $shr_dat] [] [None (0) $($tree)* ,] []);
+    };
+    // Generic machine, `StateStruct<T>, States, Events<T>, Shared<T>, { .. } where T: Clone`.
+    // Every state gets the type parameters, the where clause applies to the
+    // generated impls. Hierarchies are given with the nested syntax.
+    ($st_str:ident < $($g:ident),* >, $st_en:ident, $st_evt:ty, $shr_dat:ty, { $($tree:tt)* }) => {
+        _hsm_tree!(@munch [$st_str [$($g),*] [], $st_en, $st_evt, $shr_dat] [] [None (0) $($tree)* ,] []);
+    };
+    ($st_str:ident < $($g:ident),* >, $st_en:ident, $st_evt:ty, $shr_dat:ty, { $($tree:tt)* } where $($wc:tt)*) => {
+        _hsm_tree!(@munch [$st_str [$($g),*] [$($wc)*], $st_en, $st_evt, $shr_dat] [] [None (0) $($tree)* ,] []);
     }
 }
 
+// Flattens a nested hierarchy in declaration order. The third group holds the
+// parent, depth and remaining siblings being worked on, the fourth the sibling
+// lists of the ancestors still to finish. Every state is collected as
+// `(name parent depth [children] (name {fields}))`.
+#[macro_export]
+macro_rules! _hsm_tree {
+    (@munch $cfg:tt [$($done:tt)*] [$p:tt $dep:tt] [$next:tt $($stack:tt)*]) => {
+        _hsm_tree!(@munch $cfg [$($done)*] $next [$($stack)*]);
+    };
+    (@munch $cfg:tt [$($done:tt)*] [$p:tt $dep:tt] []) => {
+        _hsm_tree!(@emit $cfg $($done)*);
+    };
+    (@munch $cfg:tt [$($done:tt)*] [$p:tt $dep:tt , $($rest:tt)*] [$($stack:tt)*]) => {
+        _hsm_tree!(@munch $cfg [$($done)*] [$p $dep $($rest)*] [$($stack)*]);
+    };
+    (@munch $cfg:tt [$($done:tt)*]
+     [$p:tt $dep:tt $nam:ident { $($f:ident : $t:ty = $d:expr),* } { $($ch:tt)* } , $($rest:tt)*] [$($stack:tt)*]) => {
+        _hsm_tree!(@munch $cfg [$($done)* ($nam $p $dep [$($ch)*] ($nam { $($f : $t = $d),* }))]
+                   [$nam ($dep + 1) $($ch)* ,] [[$p $dep $($rest)*] $($stack)*]);
+    };
+    (@munch $cfg:tt [$($done:tt)*]
+     [$p:tt $dep:tt $nam:ident { $($f:ident : $t:ty = $d:expr),* } , $($rest:tt)*] [$($stack:tt)*]) => {
+        _hsm_tree!(@munch $cfg [$($done)* ($nam $p $dep [] ($nam { $($f : $t = $d),* }))]
+                   [$p $dep $($rest)*] [$($stack)*]);
+    };
+    (@munch $cfg:tt [$($done:tt)*]
+     [$p:tt $dep:tt $nam:ident { $($ch:tt)* } , $($rest:tt)*] [$($stack:tt)*]) => {
+        _hsm_tree!(@munch $cfg [$($done)* ($nam $p $dep [$($ch)*] ($nam))]
+                   [$nam ($dep + 1) $($ch)* ,] [[$p $dep $($rest)*] $($stack)*]);
+    };
+    (@munch $cfg:tt [$($done:tt)*]
+     [$p:tt $dep:tt $nam:ident , $($rest:tt)*] [$($stack:tt)*]) => {
+        _hsm_tree!(@munch $cfg [$($done)* ($nam $p $dep [] ($nam))]
+                   [$p $dep $($rest)*] [$($stack)*]);
+    };
+    (@emit [$st_str:ident $g:tt $wc:tt, $st_en:ident, $st_evt:ty, $shr_dat:ty]
+     $( ($nam:ident $p:tt $dep:tt $ch:tt $def:tt) )*) => {
+        $( _hsm_create_generic_state!($g $wc $def); )*
+        _hsm_create_state_enum!($st_en, ($($nam -> $p),*));
+        $( _hsm_impl_state_parent!($g $st_en ; $nam -> $p); )*
+        impl $st_en {
+            /// Number of ancestors, 0 for roots.
+            pub fn depth(&self) -> usize {
+                match *self {
+                    $( $st_en::$nam => $dep ),*
+                }
+            }
+        }
+        _hsm_create_state_struct!($st_str $g $wc [new], $st_en, $st_evt, $shr_dat, ($($nam),*) ;
+            fn initial_child(&self, typ: &$st_en) -> Option<$st_en> {
+                match *typ {
+                    $( $st_en::$nam => _hsm_first_child!($st_en ; $ch) ),*
+                }
+            }
+        );
+    }
+}
+
+#[macro_export]
+macro_rules! _hsm_first_child {
+    ($st_en:ident ; []) => {
+        None
+    };
+    ($st_en:ident ; [$first:ident $($rest:tt)*]) => {
+        Some($st_en::$first)
+    }
+}
+
+/// Transition whose action runs after the exits, `$x` returns the target.
+/// With the target given up front, `$x` only runs the action and the
+/// probe returns `Action::DelayedTransitionTo`.
 #[macro_export]
 macro_rules! hsm_delayed_transition {
+    ($probe:ident, $target:expr, $x:block) => {
+        match $probe {
+            true  => $crate::Action::DelayedTransitionTo($target),
+            false => {
+                $x;
+                $crate::Action::Transition($target)
+            }
+        }
+    };
     ($probe:ident, $x:block) => {
         match $probe {
             true  => $crate::Action::DelayedTransition,
@@ -60,36 +176,186 @@ macro_rules! hsm_delayed_transition {
     }
 }
 
+/// Implements `hsm::State` from match arms on the event. Optionally takes
+/// `handles Pat | Pat,` with patterns matching whole event variants (see
+/// `State::handles`), names for `self`, the shared
+/// data, the event and probe, and with `self` named, `pre` and `post` blocks
+/// that run before and after the match. States of generic machines start
+/// with the type parameters and an optional where clause, as in
+/// `<T> where [T: Clone] Idle<T>, Events<T>, States, Shared<T>, ..`:
+///
+/// ```ignore
+/// hsm_impl_state!(Counting, Events, States, Shared, self, shr, evt, probe,
+///     pre { self.seen += 1; },
+///     hsm::Event::User(Events::Stop) => hsm::Action::Transition(States::Idle),
+///     _ => hsm::Action::Ignore
+/// );
+/// ```
 #[macro_export]
 macro_rules! hsm_impl_state {
-    ($state:ty, $events:ty, $states:ty, $shr_data:ty,
-     $($pat:pat => $result:expr),*) => {
-        impl<'a, 'b, 'c, 'd, 'e> $crate::State<$events, $states, $shr_data> for $state {
+    (@impl [[[$($g:ident),*] [$($wc:tt)*]] $state:ty, $events:ty, $states:ty, $shr_data:ty] [$($handled:pat)|*]
+     $slf:ident, $shr:ident, $evt:ident, $probe:ident, $pre:block, $post:block, $($pat:pat => $result:expr),*) => {
+        impl<'a, 'b, 'c, 'd, 'e, $($g),*> $crate::State<$events, $states, $shr_data> for $state
+            where $events   : ::std::fmt::Debug,
+                  $shr_data : ::std::fmt::Debug,
+                  $($wc)*
+        {
             #[allow(unused_variables)]
-            fn handle_event(&mut self, shr_data: &mut $shr_data, evt: &$crate::Event<$events>, probe: bool) -> $crate::Action<$states> {
-                match *evt {
+            fn handle_event(&mut $slf, $shr: &mut $shr_data, $evt: &$crate::Event<$events, $states>, $probe: bool) -> $crate::Action<$states> {
+                $pre
+                let action = match *$evt {
                     $( $pat => $result),*
-                }
+                };
+                $post
+                action
             }
+
+            _hsm_impl_handles!($events ; $($handled)|*);
         }
     };
-    ($state:ty, $events:ty, $states:ty, $shr_data:ty,
-     $shr:ident, $evt:ident, $probe:ident, $($pat:pat => $result:expr),*) => {
-        impl<'a, 'b, 'c, 'd, 'e> $crate::State<$events, $states, $shr_data> for $state {
+    (@args $head:tt $handled:tt $slf:ident, $shr:ident, $evt:ident, $probe:ident,
+     pre $pre:block, post $post:block, $($pat:pat => $result:expr),*) => {
+        hsm_impl_state!(@impl $head $handled $slf, $shr, $evt, $probe, $pre, $post, $($pat => $result),*);
+    };
+    (@args $head:tt $handled:tt $slf:ident, $shr:ident, $evt:ident, $probe:ident,
+     pre $pre:block, $($pat:pat => $result:expr),*) => {
+        hsm_impl_state!(@impl $head $handled $slf, $shr, $evt, $probe, $pre, {}, $($pat => $result),*);
+    };
+    (@args $head:tt $handled:tt $slf:ident, $shr:ident, $evt:ident, $probe:ident,
+     post $post:block, $($pat:pat => $result:expr),*) => {
+        hsm_impl_state!(@impl $head $handled $slf, $shr, $evt, $probe, {}, $post, $($pat => $result),*);
+    };
+    (@args $head:tt $handled:tt $slf:ident, $shr:ident, $evt:ident, $probe:ident,
+     $($pat:pat => $result:expr),*) => {
+        hsm_impl_state!(@impl $head $handled $slf, $shr, $evt, $probe, {}, {}, $($pat => $result),*);
+    };
+    (@args $head:tt $handled:tt $shr:ident, $evt:ident, $probe:ident,
+     $($pat:pat => $result:expr),*) => {
+        hsm_impl_state!(@impl $head $handled self, $shr, $evt, $probe, {}, {}, $($pat => $result),*);
+    };
+    (@args $head:tt $handled:tt $($pat:pat => $result:expr),*) => {
+        hsm_impl_state!(@impl $head $handled self, shr_data, evt, probe, {}, {}, $($pat => $result),*);
+    };
+    (@split $gen:tt $state:ty, $events:ty, $states:ty, $shr_data:ty, handles $($handled:pat)|+ , $($rest:tt)*) => {
+        hsm_impl_state!(@args [$gen $state, $events, $states, $shr_data] [$($handled)|+] $($rest)*);
+    };
+    (@split $gen:tt $state:ty, $events:ty, $states:ty, $shr_data:ty, $($rest:tt)*) => {
+        hsm_impl_state!(@args [$gen $state, $events, $states, $shr_data] [] $($rest)*);
+    };
+    (< $($g:ident),* > where [$($wc:tt)*] $($rest:tt)*) => {
+        hsm_impl_state!(@split [[$($g),*] [$($wc)*]] $($rest)*);
+    };
+    (< $($g:ident),* > $($rest:tt)*) => {
+        hsm_impl_state!(@split [[$($g),*] []] $($rest)*);
+    };
+    ($state:ty, $($rest:tt)*) => {
+        hsm_impl_state!(@split [[] []] $state, $($rest)*);
+    }
+}
+
+#[macro_export]
+macro_rules! _hsm_impl_handles {
+    ($events:ty ; ) => {};
+    ($events:ty ; $($handled:pat)|+) => {
+        fn handles(&self, evt: &$events) -> Option<bool> {
+            match *evt {
+                $( $handled )|+ => Some(true),
+                _               => Some(false)
+            }
+        }
+    }
+}
+
+/// Implements `hsm::State` for a state whose `$field` is a `Submachine`.
+/// The arms get the events the submachine passes on, optionally with names
+/// for the shared data, the event and probe as in `hsm_impl_state!`.
+#[macro_export]
+macro_rules! hsm_impl_submachine {
+    ($state:ty, $field:ident, $events:ty, $states:ty, $shr_data:ty) => {
+        hsm_impl_submachine!($state, $field, $events, $states, $shr_data,
+            _ => $crate::Action::Parent
+        );
+    };
+    ($state:ty, $field:ident, $events:ty, $states:ty, $shr_data:ty, $shr:ident, $evt:ident, $probe:ident,
+     $($pat:pat => $result:expr),*) => {
+        impl $crate::State<$events, $states, $shr_data> for $state {
             #[allow(unused_variables)]
-            fn handle_event(&mut self, $shr: &mut $shr_data, $evt: &$crate::Event<$events>, $probe: bool) -> $crate::Action<$states> {
-                match *$evt {
-                    $( $pat => $result),*
+            fn handle_event(&mut self, $shr: &mut $shr_data, $evt: &$crate::Event<$events, $states>, $probe: bool) -> $crate::Action<$states> {
+                match self.$field.handle_event($evt, $probe) {
+                    $crate::Action::Parent => match *$evt {
+                        $( $pat => $result),*
+                    },
+                    action => action
                 }
             }
+
+            fn take_event(&mut self) -> Option<$events> {
+                self.$field.take_event()
+            }
+        }
+    };
+    ($state:ty, $field:ident, $events:ty, $states:ty, $shr_data:ty,
+     $($pat:pat => $result:expr),*) => {
+        hsm_impl_submachine!($state, $field, $events, $states, $shr_data, shr_data, evt, probe,
+            $($pat => $result),*
+        );
+    }
+}
+
+#[macro_export]
+macro_rules! _hsm_module {
+    ($module:ident, $($def:tt)*) => {
+        pub mod $module {
+            #[allow(unused_imports)]
+            use super::*;
+            $($def)*;
         }
     }
 }
 
+/// Declares the parent of every state. Parent cycles fail to compile:
+///
+/// ```compile_fail,E0080
+/// #[macro_use]
+/// extern crate hsm;
+///
+/// #[derive(Debug, Clone)]
+/// pub enum Events { Go }
+///
+/// hsm_define_objects!(StateStruct, States, Events, (), (A, B));
+/// hsm_impl_state!(A, Events, States, (), _ => hsm::Action::Ignore);
+/// hsm_impl_state!(B, Events, States, (), _ => hsm::Action::Ignore);
+/// hsm_state_parents!(States ; A -> B, B -> A);
+///
+/// fn main() {}
+/// ```
 #[macro_export]
 macro_rules! hsm_state_parents {
+    (mod $module:ident, $($rest:tt)*) => {
+        const _: () = {
+            use self::$module::*;
+            hsm_state_parents!($($rest)*);
+        };
+    };
     ($st_en:ident ; $($nam:ident -> $parent:ident),*) => {
         $(_hsm_impl_state_parent!($st_en ; $nam -> $parent);)*
+        const _: () = {
+            const PARENTS: &[(usize, Option<usize>)] = &[
+                $( ($st_en::$nam as usize, _hsm_parent_index!($st_en ; $parent)) ),*
+            ];
+            $( assert!(!$crate::_hsm_in_parent_cycle(PARENTS, $st_en::$nam as usize),
+                       concat!("hsm_state_parents: state ", stringify!($nam), " is part of a parent cycle")); )*
+        };
+    }
+}
+
+#[macro_export]
+macro_rules! _hsm_parent_index {
+    ($st_en:ident ; None) => {
+        None
+    };
+    ($st_en:ident ; $parent:ident) => {
+        Some($st_en::$parent as usize)
     }
 }
 
@@ -104,6 +370,24 @@ macro_rules! _hsm_impl_state_parent {
         impl $crate::Parent<$st_en> for $nam {
             fn get_parent() -> Option<$st_en> { Some($st_en::$parent) }
         }
+    };
+    ([] $st_en:ident ; $nam:ident -> $parent:ident) => {
+        _hsm_impl_state_parent!($st_en ; $nam -> $parent);
+    };
+    ([$($g:ident),+] $st_en:ident ; $nam:ident -> $parent:ident) => {
+        impl<$($g),+> $crate::Parent<$st_en> for $nam<$($g),+> {
+            fn get_parent() -> Option<$st_en> { _hsm_parent_state!($st_en ; $parent) }
+        }
+    }
+}
+
+#[macro_export]
+macro_rules! _hsm_parent_state {
+    ($st_en:ident ; None) => {
+        None
+    };
+    ($st_en:ident ; $parent:ident) => {
+        Some($st_en::$parent)
     }
 }
 
@@ -128,10 +412,28 @@ macro_rules! _hsm_create_state_common {
     }
 }
 
+// Clone only applies when every field is Clone, so states holding sockets or
+// boxed trait objects still compile, they just can't be explored or fuzzed.
+// The `for<'hsm>` stops rustc from rejecting bounds that don't hold.
+#[macro_export]
+macro_rules! _hsm_impl_clone {
+    ($nam:ident [$($g:ident),*] { $($field:ident : $t:ty),* }) => {
+        impl<$($g),*> ::std::clone::Clone for $nam<$($g),*>
+            where $( for<'hsm> $t : ::std::clone::Clone ),*
+        {
+            fn clone(&self) -> Self {
+                $nam {
+                    $( $field : ::std::clone::Clone::clone(&self.$field) ),*
+                }
+            }
+        }
+    }
+}
+
 #[macro_export]
 macro_rules! _hsm_create_state {
     ($nam:ident) => {
-        #[derive(Debug)]
+        #[derive(Debug, Clone)]
         pub struct $nam;
         impl $crate::Initializer for $nam {
             fn new() -> Self {
@@ -146,6 +448,7 @@ macro_rules! _hsm_create_state {
             _phantom        : ::std::marker::PhantomData<u8>,
             $( $field_name  : $field_type ),*
         }
+        _hsm_impl_clone!($nam [] { _phantom : ::std::marker::PhantomData<u8> $(, $field_name : $field_type)* });
         impl $crate::Initializer for $nam {
             fn new() -> Self {
                 $nam {
@@ -158,9 +461,111 @@ macro_rules! _hsm_create_state {
     }
 }
 
+// States of generic machines, a phantom field carries the type parameters
+#[macro_export]
+macro_rules! _hsm_create_generic_state {
+    ([] [] $def:tt) => {
+        _hsm_create_state! $def ;
+    };
+    ($g:tt $wc:tt ($nam:ident)) => {
+        _hsm_create_generic_state!($g $wc ($nam {}));
+    };
+    ([$($g:ident),+] [$($wc:tt)*] ($nam:ident { $($field_name:ident : $field_type:ty = $field_default:expr),* })) => {
+        #[derive(Debug)]
+        pub struct $nam<$($g),+> {
+            _phantom        : ::std::marker::PhantomData<($($g,)+)>,
+            $( $field_name  : $field_type ),*
+        }
+        _hsm_impl_clone!($nam [$($g),+] { _phantom : ::std::marker::PhantomData<($($g,)+)> $(, $field_name : $field_type)* });
+        impl<$($g),+> $crate::Initializer for $nam<$($g),+>
+            where $($wc)*
+        {
+            fn new() -> Self {
+                $nam {
+                    _phantom        : ::std::marker::PhantomData,
+                    $( $field_name  : $field_default ),*
+                }
+            }
+        }
+        impl<$($g),+> $crate::Name for $nam<$($g),+> {
+            fn name(&self) -> &'static str {
+                stringify!($nam)
+            }
+        }
+    }
+}
+
+#[macro_export]
+macro_rules! _hsm_state_struct_initializer {
+    ([new] $st_str:ident [$($g:ident),*] [$($wc:tt)*] ; $($s:ident),*) => {
+        impl<$($g),*> $crate::Initializer for $st_str<$($g),*>
+            where $($wc)*
+        {
+            fn new() -> Self {
+                $st_str {
+                    $( $s : $crate::Initializer::new() ),*
+                }
+            }
+        }
+    };
+    ([config $cfg_ty:ty] $st_str:ident [$($g:ident),*] [$($wc:tt)*] ; $($s:ident),*) => {
+        impl<$($g),*> $crate::ConfigInitializer<$cfg_ty> for $st_str<$($g),*>
+            where $($wc)*
+        {
+            fn with_config(config: &$cfg_ty) -> Self {
+                $st_str {
+                    $( $s : $crate::ConfigInitializer::with_config(config) ),*
+                }
+            }
+        }
+    }
+}
+
+#[macro_export]
+macro_rules! _hsm_create_config_state {
+    ($cfg:ident : $cfg_ty:ty ; $nam:ident { $($field_name:ident : $field_type:ty = $field_default:expr),* }) => {
+        #[derive(Debug)]
+        pub struct $nam {
+            _phantom        : ::std::marker::PhantomData<u8>,
+            $( $field_name  : $field_type ),*
+        }
+        _hsm_impl_clone!($nam [] { _phantom : ::std::marker::PhantomData<u8> $(, $field_name : $field_type)* });
+        impl $crate::ConfigInitializer<$cfg_ty> for $nam {
+            #[allow(unused_variables)]
+            fn with_config($cfg: &$cfg_ty) -> Self {
+                $nam {
+                    _phantom        : ::std::marker::PhantomData,
+                    $( $field_name  : $field_default ),*
+                }
+            }
+        }
+        _hsm_create_state_common!($nam);
+    }
+}
+
 #[macro_export]
 macro_rules! _hsm_create_state_enum {
+    ($st_en:ident, ($($s:ident -> $p:tt),*) ) => {
+        _hsm_create_state_enum!(@common $st_en, ($($s),*));
+        impl $crate::InstanceParent<$st_en> for $st_en {
+            fn get_parent(&self) -> Option<$st_en> {
+                match *self {
+                    $( $st_en::$s => _hsm_parent_state!($st_en ; $p) ),*
+                }
+            }
+        }
+    };
     ($st_en:ident, ($($s:ident),*) ) => {
+        _hsm_create_state_enum!(@common $st_en, ($($s),*));
+        impl $crate::InstanceParent<$st_en> for $st_en {
+            fn get_parent(&self) -> Option<$st_en> {
+                match *self {
+                    $( $st_en::$s => <$s as $crate::Parent<$st_en>>::get_parent() ),*
+                }
+            }
+        }
+    };
+    (@common $st_en:ident, ($($s:ident),*) ) => {
         #[derive(Debug, Clone, Eq, PartialEq)]
         pub enum $st_en {
             $( $s ),*
@@ -168,16 +573,13 @@ macro_rules! _hsm_create_state_enum {
         impl ::std::fmt::Display for $st_en {
             fn fmt(&self, f:&mut ::std::fmt::Formatter) -> Result<(), ::std::fmt::Error> {
                 match *self {
-                    $( $st_en::$s => try!(::std::fmt::Display::fmt(stringify!($s), f)) ),*
-                };
-                Ok(())
+                    $( $st_en::$s => ::std::fmt::Display::fmt(stringify!($s), f) ),*
+                }
             }
         }
-        impl $crate::InstanceParent<$st_en> for $st_en {
-            fn get_parent(&self) -> Option<$st_en> {
-                match *self {
-                    $( $st_en::$s => $s::get_parent() ),*
-                }
+        impl $crate::StateList<$st_en> for $st_en {
+            fn states() -> Vec<$st_en> {
+                vec![ $( $st_en::$s ),* ]
             }
         }
     }
@@ -186,24 +588,59 @@ macro_rules! _hsm_create_state_enum {
 #[macro_export]
 macro_rules! _hsm_create_state_struct {
     ($st_str:ident, $st_en:ident, $st_evt:ty, $shr_dat:ty, ($($s:ident),*) ) => {
+        _hsm_create_state_struct!($st_str [] [] [new], $st_en, $st_evt, $shr_dat, ($($s),*) ; );
+    };
+    // Type parameters, where clause and how to initialize the states follow
+    // the name, extra StateLookup methods follow the `;`
+    ($st_str:ident $g:tt $wc:tt $init:tt, $st_en:ident, $st_evt:ty, $shr_dat:ty, ($($s:ident),*) ; $($extra:tt)*) => {
+        _hsm_create_state_struct!(@types [$st_str $g $wc $init, $st_en, $st_evt, $shr_dat, ($($s),*) ; $($extra)*] $g [] $($s)*);
+    };
+    // Builds the state types, `Name<T, ..>`, one at a time
+    (@types $args:tt [$($g:ident),*] [$($t:tt)*] $s:ident $($rest:ident)*) => {
+        _hsm_create_state_struct!(@types $args [$($g),*] [$($t)* ($s<$($g),*>)] $($rest)*);
+    };
+    (@types [$st_str:ident [$($g:ident),*] [$($wc:tt)*] $init:tt, $st_en:ident, $st_evt:ty, $shr_dat:ty, ($($s:ident),*) ; $($extra:tt)*]
+     $gs:tt [$(($t:ty))*]) => {
         #[derive(Debug)]
         #[allow(non_snake_case)]
-        pub struct $st_str {
-            $( $s : $s ),*
+        pub struct $st_str<$($g),*> {
+            $( $s : $t ),*
         }
-        impl $crate::Initializer for $st_str {
-            fn new() -> Self {
-                $st_str {
-                    $( $s : $s::new() ),*
+        _hsm_impl_clone!($st_str [$($g),*] { $($s : $t),* });
+        _hsm_state_struct_initializer!($init $st_str $gs [$($wc)*] ; $($s),*);
+        impl<'a, 'b, 'c, 'd, 'e, $($g),*> $crate::StateLookup<$st_en, $st_evt, $shr_dat> for $st_str<$($g),*>
+            where $st_evt  : ::std::fmt::Debug,
+                  $shr_dat : ::std::fmt::Debug,
+                  $( $t : $crate::State<$st_evt, $st_en, $shr_dat>, )*
+                  $($wc)*
+        {
+            fn lookup(&mut self, typ: &$st_en) -> &mut dyn $crate::State<$st_evt, $st_en, $shr_dat> {
+                match *typ {
+                    $($st_en::$s => &mut self.$s ),*
                 }
             }
-        }
-        impl<'a, 'b, 'c, 'd, 'e> $crate::StateLookup<$st_en, $st_evt, $shr_dat> for $st_str {
-            fn lookup(&mut self, typ: &$st_en) -> &mut $crate::State<$st_evt, $st_en, $shr_dat> {
+            fn parent(&self, typ: &$st_en) -> Option<$st_en> {
+                $crate::InstanceParent::get_parent(typ)
+            }
+            fn states(&self) -> Vec<$st_en> {
+                <$st_en as $crate::StateList<$st_en>>::states()
+            }
+            fn handle_event(&mut self, typ: &$st_en, shr_data: &mut $shr_dat, evt: &$crate::Event<$st_evt, $st_en>, probe: bool) -> $crate::Action<$st_en> {
                 match *typ {
-                    $($st_en::$s => &mut self.$s ),*
+                    $($st_en::$s => $crate::State::handle_event(&mut self.$s, shr_data, evt, probe) ),*
+                }
+            }
+            fn take_event(&mut self, typ: &$st_en) -> Option<$st_evt> {
+                match *typ {
+                    $($st_en::$s => $crate::State::take_event(&mut self.$s) ),*
+                }
+            }
+            fn handles(&mut self, typ: &$st_en, evt: &$st_evt) -> Option<bool> {
+                match *typ {
+                    $($st_en::$s => $crate::State::<$st_evt, $st_en, $shr_dat>::handles(&self.$s, evt) ),*
                 }
             }
+            $($extra)*
         }
     }
 }
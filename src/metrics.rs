@@ -0,0 +1,135 @@
+/*
+ * The MIT License (MIT)
+ *
+ * Copyright (c) 2015 Mattis Marjak (mattis.marjak@gmail.com)
+ *
+ * Permission is hereby granted, free of charge, to any person obtaining a copy
+ * of this software and associated documentation files (the "Software"), to deal
+ * in the Software without restriction, including without limitation the rights
+ * to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+ * copies of the Software, and to permit persons to whom the Software is
+ * furnished to do so, subject to the following conditions:
+ *
+ * The above copyright notice and this permission notice shall be included in all
+ * copies or substantial portions of the Software.
+ *
+ * THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+ * IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+ * FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+ * AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+ * LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+ * OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+ * SOFTWARE.
+ */
+
+use std::collections::BTreeMap;
+use std::fmt::{self, Write};
+use std::time::{Duration, Instant};
+use super::Listener;
+
+
+/// Listener counting events, transitions and unhandled events per state and
+/// summing the time spent in each state.
+///
+/// Attach it through an `Arc<Mutex<Metrics>>` to keep a handle for scraping.
+#[derive(Debug, Clone, Default)]
+pub struct Metrics {
+    events      : BTreeMap<String, u64>,
+    transitions : BTreeMap<(String, String), u64>,
+    unhandled   : BTreeMap<String, u64>,
+    dwell       : BTreeMap<String, Duration>,
+    entered     : BTreeMap<String, Instant>,
+}
+
+impl Metrics {
+    pub fn new() -> Self {
+        Metrics::default()
+    }
+
+    pub fn events(&self, state: &str) -> u64 {
+        self.events.get(state).cloned().unwrap_or(0)
+    }
+
+    pub fn transitions(&self, source: &str, target: &str) -> u64 {
+        self.transitions.get(&(source.to_string(), target.to_string())).cloned().unwrap_or(0)
+    }
+
+    pub fn unhandled(&self, state: &str) -> u64 {
+        self.unhandled.get(state).cloned().unwrap_or(0)
+    }
+
+    /// Total time spent in `state`, including the current visit if it is active.
+    pub fn dwell(&self, state: &str) -> Duration {
+        let done = self.dwell.get(state).cloned().unwrap_or_default();
+        match self.entered.get(state) {
+            Some(at) => done + at.elapsed(),
+            None     => done,
+        }
+    }
+
+    /// Renders all counters in the Prometheus text exposition format, labelled
+    /// with `machine`.
+    pub fn render_prometheus(&self, machine: &str) -> String {
+        let machine = escape(machine);
+        let mut out = String::new();
+
+        header(&mut out, "hsm_events_total", "Events received while the state was current.", "counter");
+        for (state, n) in self.events.iter() {
+            let _ = writeln!(out, "hsm_events_total{{machine=\"{}\",state=\"{}\"}} {}", machine, escape(state), n);
+        }
+
+        header(&mut out, "hsm_transitions_total", "Transitions between leaf states.", "counter");
+        for ((source, target), n) in self.transitions.iter() {
+            let _ = writeln!(out, "hsm_transitions_total{{machine=\"{}\",source=\"{}\",target=\"{}\"}} {}",
+                             machine, escape(source), escape(target), n);
+        }
+
+        header(&mut out, "hsm_unhandled_events_total", "Events no state handled, by the state that gave up on them.", "counter");
+        for (state, n) in self.unhandled.iter() {
+            let _ = writeln!(out, "hsm_unhandled_events_total{{machine=\"{}\",state=\"{}\"}} {}", machine, escape(state), n);
+        }
+
+        header(&mut out, "hsm_state_dwell_seconds_total", "Time spent in the state.", "counter");
+        let mut states: Vec<&String> = self.dwell.keys().chain(self.entered.keys()).collect();
+        states.sort();
+        states.dedup();
+        for state in states {
+            let _ = writeln!(out, "hsm_state_dwell_seconds_total{{machine=\"{}\",state=\"{}\"}} {}",
+                             machine, escape(state), self.dwell(state).as_secs_f64());
+        }
+        out
+    }
+}
+
+impl<UsrStEnum, UsrEvtEnum> Listener<UsrStEnum, UsrEvtEnum> for Metrics
+    where UsrStEnum : fmt::Debug + fmt::Display,
+{
+    fn event(&mut self, state: &UsrStEnum, _evt: &UsrEvtEnum) {
+        *self.events.entry(state.to_string()).or_insert(0) += 1;
+    }
+    fn unhandled(&mut self, state: &UsrStEnum, _evt: &UsrEvtEnum) {
+        *self.unhandled.entry(state.to_string()).or_insert(0) += 1;
+    }
+    fn transition(&mut self, source: &UsrStEnum, target: &UsrStEnum) {
+        *self.transitions.entry((source.to_string(), target.to_string())).or_insert(0) += 1;
+    }
+    fn enter(&mut self, state: &UsrStEnum) {
+        self.entered.insert(state.to_string(), Instant::now());
+    }
+    fn exit(&mut self, state: &UsrStEnum) {
+        let state = state.to_string();
+        if let Some(at) = self.entered.remove(&state) {
+            *self.dwell.entry(state).or_default() += at.elapsed();
+        }
+    }
+}
+
+fn header(out: &mut String, name: &str, help: &str, typ: &str) {
+    let _ = writeln!(out, "# HELP {} {}", name, help);
+    let _ = writeln!(out, "# TYPE {} {}", name, typ);
+}
+
+// Label values escape backslash, double quote and newline.
+fn escape(value: &str) -> String {
+    value.replace('\\', "\\\\").replace('"', "\\\"").replace('\n', "\\n")
+}
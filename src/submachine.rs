@@ -0,0 +1,137 @@
+/*
+ * The MIT License (MIT)
+ *
+ * Copyright (c) 2015 Mattis Marjak (mattis.marjak@gmail.com)
+ *
+ * Permission is hereby granted, free of charge, to any person obtaining a copy
+ * of this software and associated documentation files (the "Software"), to deal
+ * in the Software without restriction, including without limitation the rights
+ * to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+ * copies of the Software, and to permit persons to whom the Software is
+ * furnished to do so, subject to the following conditions:
+ *
+ * The above copyright notice and this permission notice shall be included in all
+ * copies or substantial portions of the Software.
+ *
+ * THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+ * IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+ * FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+ * AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+ * LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+ * OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+ * SOFTWARE.
+ */
+
+use std::fmt;
+use super::{Action, Event, Initializer, InputError, StateMachine, StateLookup};
+
+
+/// A separately defined state machine mounted as a composite state of an
+/// outer machine. Entering the outer state starts the inner machine afresh,
+/// with new states and a clone of the shared data it was created with, and
+/// exiting it runs the inner Exit actions. User events that
+/// `forward` maps are fed to the inner machine, for the rest `handle_event`
+/// returns `Action::Parent`. Once `complete` returns an event for the inner configuration it
+/// is handed to the outer machine through `State::take_event`. Events the
+/// inner machine leaves unhandled go on to the outer parents, for any other
+/// inner error `handle_event` returns `Action::Unhandled` and keeps the error
+/// for `take_error`. An event the inner machine left unhandled while the
+/// outer machine probed isn't fed to it again when the outer state handles
+/// the event for a delayed transition.
+#[derive(Debug, Clone)]
+pub struct Submachine<UsrStStr, UsrStEnum, UsrEvtEnum, UsrShrData, OutEvtEnum>
+    where UsrStStr:   fmt::Debug,
+          UsrStEnum:  fmt::Debug,
+          UsrEvtEnum: fmt::Debug,
+          UsrShrData: fmt::Debug,
+{
+    machine  : StateMachine<UsrStStr, UsrStEnum, UsrEvtEnum, UsrShrData>,
+    forward  : fn(&OutEvtEnum) -> Option<UsrEvtEnum>,
+    complete : fn(&UsrStEnum, &UsrShrData) -> Option<OutEvtEnum>,
+    fresh    : UsrShrData,
+    pending  : Option<OutEvtEnum>,
+    error    : Option<InputError<UsrStEnum, UsrEvtEnum>>,
+    probed   : bool,
+}
+impl<UsrStStr, UsrStEnum, UsrEvtEnum, UsrShrData, OutEvtEnum> Submachine<UsrStStr, UsrStEnum, UsrEvtEnum, UsrShrData, OutEvtEnum>
+    where UsrStStr   : fmt::Debug + Initializer + StateLookup<UsrStEnum, UsrEvtEnum, UsrShrData>,
+          UsrStEnum  : fmt::Debug + Eq + Clone,
+          UsrEvtEnum : fmt::Debug + Clone,
+          UsrShrData : fmt::Debug + Clone,
+          OutEvtEnum : fmt::Debug,
+{
+    pub fn new(machine  : StateMachine<UsrStStr, UsrStEnum, UsrEvtEnum, UsrShrData>,
+               forward  : fn(&OutEvtEnum) -> Option<UsrEvtEnum>,
+               complete : fn(&UsrStEnum, &UsrShrData) -> Option<OutEvtEnum>) -> Self {
+        Submachine {
+            fresh    : machine.shared_data().clone(),
+            machine,
+            forward,
+            complete,
+            pending  : None,
+            error    : None,
+            probed   : false,
+        }
+    }
+
+    pub fn machine(&self) -> &StateMachine<UsrStStr, UsrStEnum, UsrEvtEnum, UsrShrData> {
+        &self.machine
+    }
+
+    pub fn handle_event<OutStEnum: fmt::Debug>(&mut self, evt: &Event<OutEvtEnum, OutStEnum>, probe: bool) -> Action<OutStEnum> {
+        match *evt {
+            Event::Enter(_) => {
+                *self.machine.shared_data_mut() = self.fresh.clone();
+                self.machine.reset();
+                self.machine.start();
+                self.check_complete();
+                Action::Ignore
+            },
+            Event::Exit(_) => {
+                self.machine.stop();
+                self.pending = None;
+                Action::Ignore
+            },
+            Event::User(_) if !probe && self.probed => {
+                self.probed = false;
+                Action::Parent
+            },
+            Event::User(ref evt) => {
+                self.probed = false;
+                match (self.forward)(evt) {
+                    Some(evt) => match self.machine.input(evt) {
+                        Ok(()) => {
+                            self.check_complete();
+                            Action::Ignore
+                        },
+                        Err(InputError::Unhandled { .. }) => {
+                            self.probed = probe;
+                            Action::Parent
+                        },
+                        Err(e) => {
+                            warn!("Submachine input failed: {}", e);
+                            self.error = Some(e);
+                            Action::Unhandled
+                        }
+                    },
+                    None => Action::Parent
+                }
+            }
+        }
+    }
+
+    pub fn take_event(&mut self) -> Option<OutEvtEnum> {
+        self.pending.take()
+    }
+
+    /// The last inner error that made `handle_event` return `Action::Unhandled`.
+    pub fn take_error(&mut self) -> Option<InputError<UsrStEnum, UsrEvtEnum>> {
+        self.error.take()
+    }
+
+    fn check_complete(&mut self) {
+        if self.pending.is_none() {
+            self.pending = (self.complete)(self.machine.current(), self.machine.shared_data());
+        }
+    }
+}
@@ -0,0 +1,193 @@
+/*
+ * The MIT License (MIT)
+ *
+ * Copyright (c) 2015 Mattis Marjak (mattis.marjak@gmail.com)
+ *
+ * Permission is hereby granted, free of charge, to any person obtaining a copy
+ * of this software and associated documentation files (the "Software"), to deal
+ * in the Software without restriction, including without limitation the rights
+ * to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+ * copies of the Software, and to permit persons to whom the Software is
+ * furnished to do so, subject to the following conditions:
+ *
+ * The above copyright notice and this permission notice shall be included in all
+ * copies or substantial portions of the Software.
+ *
+ * THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+ * IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+ * FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+ * AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+ * LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+ * OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+ * SOFTWARE.
+ */
+
+//! Trace files, one JSON object per line, written by `TraceWriter` and read
+//! by `read` and the `hsm-trace` tool.
+//!
+//! Each line is a `Record` for one handled event, or for starting or stopping
+//! the machine (then `event` and `state` are null):
+//!
+//! ```text
+//! {"ts":1700000000.25,"event":"Open","state":"Closed","unhandled":false,"exits":["Closed"],"enters":["Opened"],"current":"Opened"}
+//! ```
+//!
+//! States are written with `Display`, events with `Debug`. Fields are only
+//! ever added, readers ignore the ones they don't know.
+
+use std::fmt;
+use std::fs::File;
+use std::io::{self, BufRead, BufWriter, Write};
+use std::path::Path;
+use std::time::{SystemTime, UNIX_EPOCH};
+use serde_json;
+use super::{Action, Listener};
+
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Record {
+    /// Seconds since the Unix epoch when handling started.
+    pub ts        : f64,
+    /// The event, null for start and stop.
+    pub event     : Option<String>,
+    /// The state that handled the event, or the one that gave up on it.
+    pub state     : Option<String>,
+    /// No state handled the event.
+    #[serde(default)]
+    pub unhandled : bool,
+    /// Exited states, in order.
+    #[serde(default)]
+    pub exits     : Vec<String>,
+    /// Entered states, in order.
+    #[serde(default)]
+    pub enters    : Vec<String>,
+    /// The current state afterwards.
+    pub current   : String,
+}
+
+impl Record {
+    pub fn is_start(&self) -> bool {
+        self.event.is_none() && self.exits.is_empty()
+    }
+
+    pub fn is_stop(&self) -> bool {
+        self.event.is_none() && self.enters.is_empty()
+    }
+}
+
+/// Reads all records, errors name the offending line.
+pub fn read<R: BufRead>(input: R) -> io::Result<Vec<Record>> {
+    let mut records = Vec::new();
+    for (i, line) in input.lines().enumerate() {
+        let line = line?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        let record = serde_json::from_str(&line)
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, format!("line {}: {}", i + 1, e)))?;
+        records.push(record);
+    }
+    Ok(records)
+}
+
+/// Listener writing a trace. Write errors are logged and otherwise ignored.
+pub struct TraceWriter<W: Write> {
+    out       : W,
+    ts        : Option<f64>,
+    event     : Option<String>,
+    state     : Option<String>,
+    unhandled : bool,
+    exits     : Vec<String>,
+    enters    : Vec<String>,
+}
+
+impl<W: Write> fmt::Debug for TraceWriter<W> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> Result<(), fmt::Error> {
+        f.debug_struct("TraceWriter")
+            .field("event", &self.event)
+            .field("state", &self.state)
+            .finish()
+    }
+}
+
+impl TraceWriter<BufWriter<File>> {
+    pub fn create<P: AsRef<Path>>(path: P) -> io::Result<Self> {
+        Ok(TraceWriter::new(BufWriter::new(File::create(path)?)))
+    }
+}
+
+impl<W: Write> TraceWriter<W> {
+    pub fn new(out: W) -> Self {
+        TraceWriter {
+            out,
+            ts        : None,
+            event     : None,
+            state     : None,
+            unhandled : false,
+            exits     : Vec::new(),
+            enters    : Vec::new(),
+        }
+    }
+
+    pub fn into_inner(self) -> W {
+        self.out
+    }
+
+    fn begin(&mut self) {
+        if self.ts.is_none() {
+            let now = SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default();
+            self.ts = Some(now.as_secs_f64());
+        }
+    }
+
+    fn write(&mut self, current: String) {
+        let unhandled = ::std::mem::replace(&mut self.unhandled, false);
+        let ts = match self.ts.take() {
+            Some(ts) => ts,
+            None     => return
+        };
+        let record = Record {
+            ts,
+            event  : self.event.take(),
+            state  : self.state.take(),
+            unhandled,
+            exits  : self.exits.drain(..).collect(),
+            enters : self.enters.drain(..).collect(),
+            current,
+        };
+        let mut line = serde_json::to_string(&record).expect("trace records always serialize");
+        line.push('\n');
+        if let Err(e) = self.out.write_all(line.as_bytes()).and_then(|_| self.out.flush()) {
+            warn!("trace: write failed: {}", e);
+        }
+    }
+}
+
+impl<UsrStEnum, UsrEvtEnum, W> Listener<UsrStEnum, UsrEvtEnum> for TraceWriter<W>
+    where UsrStEnum  : fmt::Debug + fmt::Display,
+          UsrEvtEnum : fmt::Debug,
+          W          : Write,
+{
+    fn event(&mut self, _state: &UsrStEnum, evt: &UsrEvtEnum) {
+        self.begin();
+        self.event = Some(format!("{:?}", evt));
+    }
+    fn handled(&mut self, state: &UsrStEnum, _evt: &UsrEvtEnum, _action: &Action<UsrStEnum>) {
+        self.state = Some(state.to_string());
+    }
+    fn unhandled(&mut self, state: &UsrStEnum, _evt: &UsrEvtEnum) {
+        self.state = Some(state.to_string());
+        self.unhandled = true;
+    }
+    fn enter(&mut self, state: &UsrStEnum) {
+        self.begin();
+        self.enters.push(state.to_string());
+    }
+    fn exit(&mut self, state: &UsrStEnum) {
+        self.begin();
+        self.exits.push(state.to_string());
+    }
+    fn done(&mut self, state: &UsrStEnum) {
+        self.write(state.to_string());
+    }
+}
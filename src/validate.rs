@@ -0,0 +1,170 @@
+/*
+ * The MIT License (MIT)
+ *
+ * Copyright (c) 2015 Mattis Marjak (mattis.marjak@gmail.com)
+ *
+ * Permission is hereby granted, free of charge, to any person obtaining a copy
+ * of this software and associated documentation files (the "Software"), to deal
+ * in the Software without restriction, including without limitation the rights
+ * to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+ * copies of the Software, and to permit persons to whom the Software is
+ * furnished to do so, subject to the following conditions:
+ *
+ * The above copyright notice and this permission notice shall be included in all
+ * copies or substantial portions of the Software.
+ *
+ * THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+ * IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+ * FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+ * AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+ * LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+ * OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+ * SOFTWARE.
+ */
+
+use std::error::Error;
+use std::fmt;
+
+
+/// Maximum number of states on a path from a leaf to the root.
+pub const MAX_DEPTH: usize = 32;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ValidationError<UsrStEnum> {
+    ParentCycle    { states: Vec<UsrStEnum> },
+    TooDeep        { state: UsrStEnum },
+    InitialNotLeaf { initial: UsrStEnum, child: UsrStEnum },
+    NotAChild      { state: UsrStEnum, initial_child: UsrStEnum },
+}
+impl<UsrStEnum: fmt::Debug> fmt::Display for ValidationError<UsrStEnum> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> Result<(), fmt::Error> {
+        match *self {
+            ValidationError::ParentCycle { ref states } =>
+                write!(f, "states {:?} form a parent cycle", states),
+            ValidationError::TooDeep { ref state } =>
+                write!(f, "state {:?} is nested deeper than {} levels", state, MAX_DEPTH),
+            ValidationError::InitialNotLeaf { ref initial, ref child } =>
+                write!(f, "initial state {:?} is not a leaf, {:?} is its child", initial, child),
+            ValidationError::NotAChild { ref state, ref initial_child } =>
+                write!(f, "initial child {:?} of state {:?} is not its child", initial_child, state),
+        }
+    }
+}
+impl<UsrStEnum: fmt::Debug> Error for ValidationError<UsrStEnum> {}
+
+/// Checks the hierarchy described by `states`, `parent` and `initial_child`
+/// without ever following more than `MAX_DEPTH` parent links, so broken
+/// hierarchies are reported instead of looping forever.
+pub fn check<UsrStEnum, F, G>(initial: &UsrStEnum, states: &[UsrStEnum], parent: F, initial_child: G) -> Result<(), ValidationError<UsrStEnum>>
+    where UsrStEnum: Eq + Clone,
+          F: Fn(&UsrStEnum) -> Option<UsrStEnum>,
+          G: Fn(&UsrStEnum) -> Option<UsrStEnum>,
+{
+    for state in states {
+        let mut path = vec![state.clone()];
+        while let Some(p) = parent(path.last().unwrap()) {
+            if let Some(i) = path.iter().position(|s| *s == p) {
+                return Err(ValidationError::ParentCycle { states: path.split_off(i) });
+            }
+            path.push(p);
+            if path.len() > MAX_DEPTH {
+                return Err(ValidationError::TooDeep { state: state.clone() });
+            }
+        }
+    }
+    for state in states {
+        if let Some(child) = initial_child(state) {
+            if parent(&child).as_ref() != Some(state) {
+                return Err(ValidationError::NotAChild { state: state.clone(), initial_child: child });
+            }
+        }
+    }
+    let mut initial = initial.clone();
+    while let Some(child) = initial_child(&initial) {
+        initial = child;
+    }
+    for state in states {
+        if parent(state).as_ref() == Some(&initial) {
+            return Err(ValidationError::InitialNotLeaf { initial, child: state.clone() });
+        }
+    }
+    Ok(())
+}
+
+/// Const counterpart of `check` used by `hsm_state_parents!`, `parents`
+/// holds `(state, parent)` pairs of enum discriminants.
+pub const fn in_parent_cycle(parents: &[(usize, Option<usize>)], state: usize) -> bool {
+    let mut current = state;
+    let mut steps = 0;
+    while steps < parents.len() {
+        let mut parent = None;
+        let mut i = 0;
+        while i < parents.len() {
+            if parents[i].0 == current {
+                parent = parents[i].1;
+                break;
+            }
+            i += 1;
+        }
+        match parent {
+            Some(p) if p == state => return true,
+            Some(p)               => current = p,
+            None                  => return false,
+        }
+        steps += 1;
+    }
+    false
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // (state, parent, initial child) rows, states are their index
+    fn run(initial: usize, rows: &[(usize, Option<usize>, Option<usize>)]) -> Result<(), ValidationError<usize>> {
+        let states: Vec<usize> = rows.iter().map(|r| r.0).collect();
+        check(&initial, &states, |s| rows[*s].1, |s| rows[*s].2)
+    }
+
+    #[test]
+    fn accepts_a_valid_hierarchy() {
+        assert_eq!(run(2, &[(0, None, Some(1)), (1, Some(0), None), (2, Some(0), None)]), Ok(()));
+        assert_eq!(run(0, &[(0, None, Some(1)), (1, Some(0), None)]), Ok(()));
+    }
+
+    #[test]
+    fn reports_parent_cycles() {
+        assert_eq!(run(0, &[(0, Some(1), None), (1, Some(0), None)]),
+                   Err(ValidationError::ParentCycle { states: vec![0, 1] }));
+        assert_eq!(run(0, &[(0, Some(0), None)]),
+                   Err(ValidationError::ParentCycle { states: vec![0] }));
+    }
+
+    #[test]
+    fn reports_hierarchies_deeper_than_the_limit() {
+        let rows: Vec<_> = (0..MAX_DEPTH + 1).map(|i| (i, i.checked_sub(1), None)).collect();
+        assert_eq!(run(MAX_DEPTH, &rows), Err(ValidationError::TooDeep { state: MAX_DEPTH }));
+        assert_eq!(run(MAX_DEPTH - 1, &rows[..MAX_DEPTH]), Ok(()));
+    }
+
+    #[test]
+    fn reports_initial_children_that_are_not_children() {
+        assert_eq!(run(1, &[(0, None, Some(2)), (1, Some(0), None), (2, None, None)]),
+                   Err(ValidationError::NotAChild { state: 0, initial_child: 2 }));
+    }
+
+    #[test]
+    fn reports_a_composite_initial_state() {
+        assert_eq!(run(0, &[(0, None, None), (1, Some(0), None)]),
+                   Err(ValidationError::InitialNotLeaf { initial: 0, child: 1 }));
+    }
+
+    #[test]
+    fn finds_parent_cycles_in_const_tables() {
+        const OK: &[(usize, Option<usize>)] = &[(0, None), (1, Some(0)), (2, Some(1))];
+        const CYCLE: &[(usize, Option<usize>)] = &[(0, None), (1, Some(2)), (2, Some(1))];
+        assert!(!in_parent_cycle(OK, 2));
+        assert!(in_parent_cycle(CYCLE, 1));
+        assert!(!in_parent_cycle(CYCLE, 0));
+    }
+}
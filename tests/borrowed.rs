@@ -0,0 +1,77 @@
+/*
+ * The MIT License (MIT)
+ *
+ * Copyright (c) 2015 Mattis Marjak (mattis.marjak@gmail.com)
+ *
+ * Permission is hereby granted, free of charge, to any person obtaining a copy
+ * of this software and associated documentation files (the "Software"), to deal
+ * in the Software without restriction, including without limitation the rights
+ * to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+ * copies of the Software, and to permit persons to whom the Software is
+ * furnished to do so, subject to the following conditions:
+ *
+ * The above copyright notice and this permission notice shall be included in all
+ * copies or substantial portions of the Software.
+ *
+ * THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+ * IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+ * FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+ * AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+ * LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+ * OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+ * SOFTWARE.
+ */
+
+#[macro_use]
+extern crate hsm;
+
+use std::panic::{self, AssertUnwindSafe};
+
+
+#[derive(Debug, Clone)]
+pub enum Events {
+    Open,
+    Write(&'static str),
+    Crash,
+}
+
+hsm_define_objects_noparents!(StateStruct, States, Events, Vec<String>, (
+    Closed,
+    Opened
+));
+
+hsm_impl_state!(Closed, Events, States, Vec<String>, log, evt, probe,
+    hsm::Event::User(Events::Open) => hsm::Action::Transition(States::Opened),
+    _ => hsm::Action::Ignore
+);
+
+hsm_impl_state!(Opened, Events, States, Vec<String>, log, evt, probe,
+    hsm::Event::Enter(_) => { log.push("opened".to_string()); hsm::Action::Ignore },
+    hsm::Event::User(Events::Write(data)) => { log.push(data.to_string()); hsm::Action::Ignore },
+    hsm::Event::User(Events::Crash) => panic!("crashed"),
+    _ => hsm::Action::Ignore
+);
+
+#[test]
+fn detached_machines_work_on_borrowed_data() {
+    let mut first = Vec::new();
+    let mut second = Vec::new();
+    let mut sm = hsm::DetachedStateMachine::<StateStruct, States, Events, Vec<String>>::new(States::Closed);
+    sm.start_with(&mut first);
+    sm.input_with(&mut first, Events::Open).unwrap();
+    sm.input_with(&mut second, Events::Write("hello")).unwrap();
+    assert_eq!(*sm.current(), States::Opened);
+    assert_eq!(first, ["opened"]);
+    assert_eq!(second, ["hello"]);
+}
+
+#[test]
+fn owned_data_survives_a_panicking_handler() {
+    let mut sm = hsm::StateMachine::<StateStruct, States, Events, Vec<String>>::new(States::Closed, Vec::new());
+    sm.start();
+    sm.input(Events::Open).unwrap();
+    assert!(panic::catch_unwind(AssertUnwindSafe(|| sm.input(Events::Crash))).is_err());
+    assert_eq!(*sm.shared_data(), ["opened"]);
+    sm.input(Events::Write("again")).unwrap();
+    assert_eq!(*sm.shared_data(), ["opened", "again"]);
+}
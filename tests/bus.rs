@@ -0,0 +1,167 @@
+/*
+ * The MIT License (MIT)
+ *
+ * Copyright (c) 2015 Mattis Marjak (mattis.marjak@gmail.com)
+ *
+ * Permission is hereby granted, free of charge, to any person obtaining a copy
+ * of this software and associated documentation files (the "Software"), to deal
+ * in the Software without restriction, including without limitation the rights
+ * to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+ * copies of the Software, and to permit persons to whom the Software is
+ * furnished to do so, subject to the following conditions:
+ *
+ * The above copyright notice and this permission notice shall be included in all
+ * copies or substantial portions of the Software.
+ *
+ * THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+ * IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+ * FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+ * AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+ * LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+ * OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+ * SOFTWARE.
+ */
+
+#[macro_use]
+extern crate hsm;
+
+use std::cell::RefCell;
+use std::rc::Rc;
+use hsm::bus::{Address, BusError, DeliveryError, EventBus, Publisher};
+
+
+#[derive(Debug, Clone)]
+pub enum Msg {
+    Note(u32),
+    // Handled by publishing Note(n) to "notes"
+    Relay(u32),
+    // Not handled, the machine's input fails
+    Reject,
+}
+
+#[derive(Debug)]
+pub struct Shared {
+    name : &'static str,
+    log  : Rc<RefCell<Vec<String>>>,
+    bus  : Publisher<Msg>,
+}
+
+hsm_define_objects_noparents!(StateStruct, States, Msg, Shared, (
+    Listening
+));
+
+hsm_impl_state!(Listening, Msg, States, Shared, shr, evt, probe,
+    hsm::Event::User(Msg::Note(n)) => {
+        shr.log.borrow_mut().push(format!("{} {}", shr.name, n));
+        hsm::Action::Ignore
+    },
+    hsm::Event::User(Msg::Relay(n)) => {
+        shr.log.borrow_mut().push(format!("{} relay {}", shr.name, n));
+        shr.bus.publish("notes", Msg::Note(n));
+        hsm::Action::Ignore
+    },
+    hsm::Event::User(Msg::Reject) => hsm::Action::Unhandled,
+    _ => hsm::Action::Ignore
+);
+
+fn decode_all(msg: &Msg) -> Option<Msg> {
+    Some(msg.clone())
+}
+
+fn decode_notes(msg: &Msg) -> Option<Msg> {
+    match *msg {
+        Msg::Note(_) => Some(msg.clone()),
+        _            => None
+    }
+}
+
+fn bus(log: &Rc<RefCell<Vec<String>>>) -> EventBus<Msg> {
+    let mut bus = EventBus::new();
+    for &(name, decode) in [("a", decode_all as fn(&Msg) -> Option<Msg>), ("b", decode_all), ("c", decode_notes)].iter() {
+        let topics: &[&str] = if name == "c" { &["quiet"] } else { &["notes"] };
+        let shared = Shared { name, log: log.clone(), bus: bus.publisher() };
+        bus.subscribe(name, topics, hsm::StateMachine::<StateStruct, _, _, _>::new(States::Listening, shared), decode).unwrap();
+    }
+    bus
+}
+
+#[test]
+fn messages_go_out_in_send_then_subscription_order() {
+    let log = Rc::new(RefCell::new(Vec::new()));
+    let mut bus = bus(&log);
+    bus.send(Address::Topic("notes".to_string()), Msg::Note(1));
+    bus.send(Address::Machine("b".to_string()), Msg::Note(2));
+    bus.send(Address::Broadcast, Msg::Note(3));
+
+    assert_eq!(bus.run(), Ok(6));
+    assert_eq!(*log.borrow(), ["a 1", "b 1", "b 2", "a 3", "b 3", "c 3"]);
+}
+
+#[test]
+fn messages_published_by_a_handler_queue_behind_waiting_ones() {
+    let log = Rc::new(RefCell::new(Vec::new()));
+    let mut bus = bus(&log);
+    bus.send(Address::Machine("a".to_string()), Msg::Relay(1));
+    bus.send(Address::Machine("b".to_string()), Msg::Note(2));
+
+    assert_eq!(bus.run(), Ok(4));
+    assert_eq!(*log.borrow(), ["a relay 1", "b 2", "a 1", "b 1"]);
+}
+
+#[test]
+fn undecoded_messages_are_skipped_without_error() {
+    let log = Rc::new(RefCell::new(Vec::new()));
+    let mut bus = bus(&log);
+    bus.send(Address::Topic("quiet".to_string()), Msg::Relay(1));
+
+    assert_eq!(bus.run(), Ok(0));
+    assert!(log.borrow().is_empty());
+}
+
+#[test]
+fn failures_are_reported_and_do_not_hold_up_the_queue() {
+    let log = Rc::new(RefCell::new(Vec::new()));
+    let mut bus = bus(&log);
+    bus.send(Address::Machine("a".to_string()), Msg::Reject);
+    bus.send(Address::Machine("nobody".to_string()), Msg::Note(1));
+    bus.send(Address::Topic("nothing".to_string()), Msg::Note(2));
+    bus.send(Address::Machine("b".to_string()), Msg::Note(3));
+
+    assert_eq!(bus.run(), Err(vec![
+        DeliveryError::Failed {
+            machine : "a".to_string(),
+            error   : "event Reject was not handled, state Listening gave up on it".to_string(),
+        },
+        DeliveryError::NoRecipient(Address::Machine("nobody".to_string())),
+        DeliveryError::NoRecipient(Address::Topic("nothing".to_string())),
+    ]));
+    assert_eq!(*log.borrow(), ["b 3"]);
+
+    // The failed message was dropped, the next run starts clean
+    bus.send(Address::Machine("a".to_string()), Msg::Note(4));
+    assert_eq!(bus.run(), Ok(1));
+    assert_eq!(*log.borrow(), ["b 3", "a 4"]);
+}
+
+#[test]
+fn a_broadcast_on_an_empty_bus_has_no_recipient() {
+    let mut bus: EventBus<Msg> = EventBus::new();
+    bus.send(Address::Broadcast, Msg::Note(1));
+    assert_eq!(bus.run(), Err(vec![DeliveryError::NoRecipient(Address::Broadcast)]));
+    assert_eq!(format!("{}", DeliveryError::NoRecipient(Address::Broadcast)), "no recipient for Broadcast");
+}
+
+#[test]
+fn a_taken_name_is_refused() {
+    let log = Rc::new(RefCell::new(Vec::new()));
+    let mut bus = bus(&log);
+    let shared = Shared { name: "b", log: log.clone(), bus: bus.publisher() };
+    let result = bus.subscribe("a", &[], hsm::StateMachine::<StateStruct, _, _, _>::new(States::Listening, shared), decode_all);
+    assert_eq!(result, Err(BusError::Duplicate("a".to_string())));
+    assert_eq!(format!("{}", result.unwrap_err()), "machine a is already subscribed to the event bus");
+
+    // The machine already subscribed as a still gets the messages
+    bus.send(Address::Machine("a".to_string()), Msg::Note(1));
+    assert_eq!(bus.run(), Ok(1));
+    assert_eq!(*log.borrow(), ["a 1"]);
+}
@@ -0,0 +1,158 @@
+/*
+ * The MIT License (MIT)
+ *
+ * Copyright (c) 2015 Mattis Marjak (mattis.marjak@gmail.com)
+ *
+ * Permission is hereby granted, free of charge, to any person obtaining a copy
+ * of this software and associated documentation files (the "Software"), to deal
+ * in the Software without restriction, including without limitation the rights
+ * to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+ * copies of the Software, and to permit persons to whom the Software is
+ * furnished to do so, subject to the following conditions:
+ *
+ * The above copyright notice and this permission notice shall be included in all
+ * copies or substantial portions of the Software.
+ *
+ * THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+ * IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+ * FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+ * AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+ * LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+ * OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+ * SOFTWARE.
+ */
+
+#![cfg(any(feature = "json", feature = "toml"))]
+
+extern crate hsm;
+
+use hsm::{Event, InputError, Name};
+use hsm::config::{LoadError, Registry};
+use hsm::dynamic::StateId;
+
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Events {
+    Start(u32),
+    Stop,
+}
+impl Name for Events {
+    fn name(&self) -> &'static str {
+        match *self {
+            Events::Start(_) => "Start",
+            Events::Stop     => "Stop",
+        }
+    }
+}
+
+fn registry() -> Registry<Events, Vec<String>> {
+    fn log(what: &'static str) -> impl Fn(&mut Vec<String>, &Event<Events, StateId>) {
+        move |log, evt| match *evt {
+            Event::Enter(ref t) => log.push(format!("{} {}", what, t.target.as_ref().unwrap())),
+            _                   => log.push(what.to_string()),
+        }
+    }
+    Registry::new()
+        .guard("big", |_, evt| *evt == Events::Start(10))
+        .action("enter", log("enter"))
+        .action("exit", log("exit"))
+        .action("count", log("count"))
+}
+
+#[cfg(feature = "json")]
+mod json {
+    use hsm::config::from_json;
+    use super::*;
+
+    const MACHINE: &str = r#"{
+        "initial": "Idle",
+        "states": [
+            { "name": "Root", "transitions": [ { "event": "Stop", "target": "Idle" } ] },
+            { "name": "Idle", "parent": "Root", "exit": "exit",
+              "transitions": [ { "event": "Start", "guard": "big", "action": "count", "target": "Active" } ] },
+            { "name": "Active", "parent": "Root", "initial": "Busy" },
+            { "name": "Busy", "parent": "Active", "enter": "enter" }
+        ]
+    }"#;
+
+    fn error(doc: &str) -> LoadError {
+        from_json(doc, &registry(), Vec::new()).unwrap_err()
+    }
+
+    #[test]
+    fn runs_guarded_transitions_with_actions() {
+        let mut sm = from_json(MACHINE, &registry(), Vec::new()).unwrap();
+        sm.start();
+        // the guard rejects it and Root has no row for Start either
+        assert_eq!(sm.input(Events::Start(1)), Err(InputError::Unhandled { state: "Root".into(), event: Events::Start(1) }));
+        assert_eq!(sm.current().name(), "Idle");
+
+        sm.input(Events::Start(10)).unwrap();
+        assert_eq!(sm.current().name(), "Busy");
+        // Idle handled Start, so it exits after the action. Busy is entered
+        // by descending into Active, which stays the transition's target.
+        assert_eq!(*sm.shared_data(), vec!["count", "exit", "enter Active"]);
+
+        sm.input(Events::Stop).unwrap();
+        assert_eq!(sm.current().name(), "Idle");
+    }
+
+    #[test]
+    fn table_states_are_named_after_their_definition() {
+        let sm = from_json(MACHINE, &registry(), Vec::new()).unwrap();
+        let debug = format!("{:?}", sm);
+        for name in ["Root", "Idle", "Active", "Busy"].iter() {
+            assert!(debug.contains(&format!("{:?}: {:?}", name, name)), "{} missing from {}", name, debug);
+        }
+    }
+
+    #[test]
+    fn reports_where_a_definition_is_wrong() {
+        assert!(matches!(error("{ \"initial\": 1 }"), LoadError::Parse(_)));
+        assert!(matches!(error(r#"{ "initial": "A", "states": [ { "name": "A", "colour": "red" } ] }"#), LoadError::Parse(_)));
+
+        let invalid = |path: &str, message: &str| LoadError::Invalid { path: path.to_string(), message: message.to_string() };
+        assert_eq!(error(r#"{ "initial": "B", "states": [ { "name": "A" } ] }"#),
+                   invalid("initial", "unknown state `B`"));
+        assert_eq!(error(r#"{ "initial": "A", "states": [ { "name": "A" }, { "name": "A" } ] }"#),
+                   invalid("states[1].name", "duplicate state `A`"));
+        assert_eq!(error(r#"{ "initial": "A", "states": [ { "name": "A", "transitions": [ { "event": "Stop", "target": "B" } ] } ] }"#),
+                   invalid("states[0].transitions[0].target", "unknown state `B`"));
+        assert_eq!(error(r#"{ "initial": "A", "states": [ { "name": "A", "transitions": [ { "event": "Stop", "guard": "small" } ] } ] }"#),
+                   invalid("states[0].transitions[0].guard", "unknown guard `small`"));
+        assert_eq!(error(r#"{ "initial": "A", "states": [ { "name": "A", "enter": "jump" } ] }"#),
+                   invalid("states[0].enter", "unknown action `jump`"));
+        assert_eq!(error(r#"{ "initial": "A", "states": [ { "name": "A", "initial": "B" }, { "name": "B" } ] }"#),
+                   invalid("states[0].initial", "`B` is not a child of `A`"));
+        match error(r#"{ "initial": "A", "states": [ { "name": "A", "parent": "B" }, { "name": "B", "parent": "A" } ] }"#) {
+            LoadError::Invalid { ref path, .. } => assert!(path.ends_with("].parent"), "{}", path),
+            e                                   => panic!("{:?}", e),
+        }
+    }
+}
+
+#[cfg(feature = "toml")]
+#[test]
+fn loads_toml_definitions() {
+    let doc = r#"
+        initial = "Idle"
+
+        [[states]]
+        name = "Idle"
+        enter = "enter"
+        transitions = [ { event = "Start", action = "count", target = "Active" } ]
+
+        [[states]]
+        name = "Active"
+        transitions = [ { event = "Stop", action = "count" } ]
+    "#;
+    let mut sm = hsm::config::from_toml(doc, &registry(), Vec::new()).unwrap();
+    sm.start();
+    sm.input(Events::Start(0)).unwrap();
+    // a row without a target runs its action and stays
+    sm.input(Events::Stop).unwrap();
+    assert_eq!(sm.current().name(), "Active");
+    assert_eq!(*sm.shared_data(), vec!["enter Idle", "count", "count"]);
+
+    assert!(matches!(hsm::config::from_toml("initial = ", &registry(), Vec::new()), Err(LoadError::Parse(_))));
+}
@@ -0,0 +1,124 @@
+/*
+ * The MIT License (MIT)
+ *
+ * Copyright (c) 2015 Mattis Marjak (mattis.marjak@gmail.com)
+ *
+ * Permission is hereby granted, free of charge, to any person obtaining a copy
+ * of this software and associated documentation files (the "Software"), to deal
+ * in the Software without restriction, including without limitation the rights
+ * to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+ * copies of the Software, and to permit persons to whom the Software is
+ * furnished to do so, subject to the following conditions:
+ *
+ * The above copyright notice and this permission notice shall be included in all
+ * copies or substantial portions of the Software.
+ *
+ * THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+ * IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+ * FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+ * AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+ * LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+ * OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+ * SOFTWARE.
+ */
+
+#[macro_use]
+extern crate hsm;
+
+use hsm::ConfigInitializer;
+
+
+#[derive(Debug)]
+pub struct Settings {
+    retries : u32,
+    banner  : String,
+}
+
+#[derive(Debug, Clone)]
+pub enum Events {
+    Fail,
+    Reset,
+}
+
+#[derive(Debug)]
+pub struct Shared {
+    banner : String,
+    log    : Vec<String>,
+}
+impl ConfigInitializer<Settings> for Shared {
+    fn with_config(config: &Settings) -> Self {
+        Shared { banner: config.banner.clone(), log: Vec::new() }
+    }
+}
+
+hsm_define_objects_noparents!(StateStruct, States, Events, Shared, (
+    Trying {
+        left  : u32 = cfg.retries,
+        label : String = format!("{} x{}", cfg.banner, cfg.retries)
+    },
+    GaveUp {}
+) with cfg: Settings);
+
+hsm_impl_state!(Trying, Events, States, Shared, self, shr, evt, probe,
+    hsm::Event::Enter(_) => { shr.log.push(self.label.clone()); hsm::Action::Ignore },
+    hsm::Event::User(Events::Fail) => {
+        if self.left == 0 {
+            hsm::Action::Transition(States::GaveUp)
+        } else {
+            self.left -= 1;
+            hsm::Action::Ignore
+        }
+    },
+    _ => hsm::Action::Ignore
+);
+
+hsm_impl_state!(GaveUp, Events, States, Shared, shr, evt, probe,
+    hsm::Event::Enter(_) => { shr.log.push(format!("{} gave up", shr.banner)); hsm::Action::Ignore },
+    hsm::Event::User(Events::Reset) => hsm::Action::Transition(States::Trying),
+    _ => hsm::Action::Ignore
+);
+
+type Machine = hsm::StateMachine<StateStruct, States, Events, Shared>;
+
+fn settings(retries: u32) -> Settings {
+    Settings { retries, banner: "worker".to_string() }
+}
+
+fn fails_until_giving_up(sm: &mut Machine) -> u32 {
+    let mut fails = 0;
+    while *sm.current() != States::GaveUp {
+        sm.input(Events::Fail).unwrap();
+        fails += 1;
+    }
+    fails
+}
+
+#[test]
+fn fields_and_shared_data_come_from_the_config() {
+    let mut sm = Machine::from_config(States::Trying, &settings(2));
+    sm.start();
+    assert_eq!(fails_until_giving_up(&mut sm), 3);
+    assert_eq!(sm.shared_data().log, ["worker x2", "worker gave up"]);
+}
+
+#[test]
+fn shared_data_can_be_given_separately() {
+    let shared = Shared { banner: "other".to_string(), log: Vec::new() };
+    let mut sm = Machine::with_config(States::Trying, &settings(0), shared);
+    sm.start();
+    assert_eq!(fails_until_giving_up(&mut sm), 1);
+    assert_eq!(sm.shared_data().log, ["worker x0", "other gave up"]);
+}
+
+#[test]
+fn reset_rebuilds_the_states_from_a_new_config() {
+    let mut sm = Machine::from_config(States::Trying, &settings(5));
+    sm.start();
+    sm.input(Events::Fail).unwrap();
+    sm.reset_with_config(&settings(1));
+    assert!(!sm.is_started());
+    sm.start();
+    assert_eq!(fails_until_giving_up(&mut sm), 2);
+    // the shared data is kept, only the states are new
+    assert_eq!(sm.shared_data().log, ["worker x5", "worker x1", "worker gave up"]);
+}
@@ -0,0 +1,128 @@
+/*
+ * The MIT License (MIT)
+ *
+ * Copyright (c) 2015 Mattis Marjak (mattis.marjak@gmail.com)
+ *
+ * Permission is hereby granted, free of charge, to any person obtaining a copy
+ * of this software and associated documentation files (the "Software"), to deal
+ * in the Software without restriction, including without limitation the rights
+ * to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+ * copies of the Software, and to permit persons to whom the Software is
+ * furnished to do so, subject to the following conditions:
+ *
+ * The above copyright notice and this permission notice shall be included in all
+ * copies or substantial portions of the Software.
+ *
+ * THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+ * IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+ * FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+ * AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+ * LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+ * OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+ * SOFTWARE.
+ */
+
+#[macro_use]
+extern crate hsm;
+
+use std::rc::Rc;
+
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Events {
+    Go(u8),
+    Later(u8),
+}
+
+// (state, event kind, source, target, trigger) as seen by the handlers
+type Seen = Vec<(&'static str, &'static str, Option<States>, Option<States>, Option<Events>)>;
+
+#[derive(Debug)]
+pub struct Log {
+    seen: Seen,
+    // Every context handed to an Enter or Exit, kept past the handler
+    kept: Vec<Rc<hsm::Transition<States, Events>>>,
+}
+
+fn record(log: &mut Log, state: &'static str, evt: &hsm::Event<Events, States>) {
+    let (kind, t) = match *evt {
+        hsm::Event::Enter(ref t) => ("enter", t),
+        hsm::Event::Exit(ref t)  => ("exit", t),
+        _                        => return
+    };
+    log.seen.push((state, kind, t.source.clone(), t.target.clone(), t.trigger.clone()));
+    log.kept.push(t.clone());
+}
+
+hsm_define_objects!(StateStruct, States, Events, Log, {
+    Root {
+        A,
+        B
+    }
+});
+
+// Children are exited before the action of a delayed transition runs
+hsm_impl_state!(Root, Events, States, Log, log, evt, probe,
+    hsm::Event::User(Events::Later(_)) => hsm_delayed_transition!(probe, {
+        log.seen.push(("Root", "action", None, None, None));
+        States::B
+    }),
+    _ => hsm::Action::Ignore
+);
+
+hsm_impl_state!(A, Events, States, Log, log, evt, probe,
+    hsm::Event::User(Events::Go(_)) => hsm::Action::Transition(States::B),
+    _ => {
+        record(log, "A", evt);
+        hsm::Action::Parent
+    }
+);
+
+hsm_impl_state!(B, Events, States, Log, log, evt, probe,
+    _ => {
+        record(log, "B", evt);
+        hsm::Action::Parent
+    }
+);
+
+fn started() -> hsm::StateMachine<StateStruct, States, Events, Log> {
+    let mut sm = hsm::StateMachine::new(States::Root, Log { seen: Vec::new(), kept: Vec::new() });
+    sm.start();
+    sm
+}
+
+#[test]
+fn start_has_no_source_or_trigger() {
+    let sm = started();
+    assert_eq!(sm.shared_data().seen, vec![("A", "enter", None, Some(States::Root), None)]);
+}
+
+#[test]
+fn transitions_pass_source_target_and_trigger() {
+    let mut sm = started();
+    sm.input(Events::Go(7)).unwrap();
+    assert_eq!(sm.shared_data().seen[1..], [
+        ("A", "exit", Some(States::A), Some(States::B), Some(Events::Go(7))),
+        ("B", "enter", Some(States::A), Some(States::B), Some(Events::Go(7))),
+    ]);
+}
+
+#[test]
+fn delayed_transitions_pass_the_trigger_to_exits() {
+    let mut sm = started();
+    sm.input(Events::Later(3)).unwrap();
+    assert_eq!(sm.shared_data().seen[1..], [
+        ("A", "exit", Some(States::Root), None, Some(Events::Later(3))),
+        ("Root", "action", None, None, None),
+        ("B", "enter", Some(States::Root), Some(States::B), Some(Events::Later(3))),
+    ]);
+}
+
+#[test]
+fn exits_of_a_delayed_transition_may_keep_the_context() {
+    let mut sm = started();
+    sm.input(Events::Later(3)).unwrap();
+    assert_eq!(*sm.current(), States::B);
+    let exit = &sm.shared_data().kept[1];
+    assert_eq!((exit.target.clone(), exit.trigger.clone()), (None, Some(Events::Later(3))));
+}
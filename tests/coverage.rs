@@ -0,0 +1,128 @@
+/*
+ * The MIT License (MIT)
+ *
+ * Copyright (c) 2015 Mattis Marjak (mattis.marjak@gmail.com)
+ *
+ * Permission is hereby granted, free of charge, to any person obtaining a copy
+ * of this software and associated documentation files (the "Software"), to deal
+ * in the Software without restriction, including without limitation the rights
+ * to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+ * copies of the Software, and to permit persons to whom the Software is
+ * furnished to do so, subject to the following conditions:
+ *
+ * The above copyright notice and this permission notice shall be included in all
+ * copies or substantial portions of the Software.
+ *
+ * THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+ * IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+ * FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+ * AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+ * LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+ * OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+ * SOFTWARE.
+ */
+
+#[macro_use]
+extern crate hsm;
+#[cfg(feature = "json")]
+extern crate serde_json;
+
+use std::sync::{Arc, Mutex};
+use hsm::coverage::{Coverage, Report};
+
+
+#[derive(Debug, Clone)]
+pub enum Events {
+    Digit(u8),
+    Clear,
+    Key { code: u8 },
+}
+
+hsm_define_objects!(StateStruct, States, Events, (), (
+    Calc {},
+    Empty {},
+    Typing {},
+    Broken {}
+));
+hsm_state_parents!(States; Calc -> None, Empty -> Calc, Typing -> Calc, Broken -> Calc);
+
+hsm_impl_state!(Calc, Events, States, (),
+    hsm::Event::User(Events::Clear) => hsm::Action::Transition(States::Empty),
+    _ => hsm::Action::Ignore
+);
+
+hsm_impl_state!(Empty, Events, States, (),
+    hsm::Event::User(Events::Digit(_)) => hsm::Action::Transition(States::Typing),
+    _ => hsm::Action::Parent
+);
+
+hsm_impl_state!(Typing, Events, States, (),
+    hsm::Event::User(Events::Digit(_)) => hsm::Action::Ignore,
+    _ => hsm::Action::Parent
+);
+
+hsm_impl_state!(Broken, Events, States, (),
+    _ => hsm::Action::Parent
+);
+
+fn covered() -> Report {
+    let coverage = Arc::new(Mutex::new(Coverage::new()));
+    let mut sm = hsm::StateMachine::<StateStruct, States, Events, ()>::new(States::Empty, ());
+    sm.add_listener(Box::new(coverage.clone()));
+    sm.start();
+    for evt in [Events::Digit(1), Events::Digit(2), Events::Key { code: 7 }, Events::Clear] {
+        sm.input(evt).unwrap();
+    }
+    let report = coverage.lock().unwrap().report(&sm.all_states());
+    report
+}
+
+#[test]
+fn reports_states_pairs_and_edges() {
+    let report = covered();
+    assert_eq!(report.states, ["Calc", "Empty", "Typing", "Broken"]);
+    assert_eq!(report.never_entered, ["Broken"]);
+    assert_eq!(report.never_transitioned, ["Typing", "Broken"]);
+    // events are grouped by variant, whatever their payload
+    let pairs: Vec<(&str, &str, u64)> = report.pairs.iter().map(|p| (p.0.as_str(), p.1.as_str(), p.2)).collect();
+    assert_eq!(pairs, [("Calc", "Clear", 1), ("Calc", "Key", 1), ("Empty", "Digit", 1), ("Typing", "Digit", 1)]);
+    let edges: Vec<(&str, &str, u64)> = report.edges.iter().map(|e| (e.0.as_str(), e.1.as_str(), e.2)).collect();
+    assert_eq!(edges, [("Calc", "Empty", 1), ("Empty", "Typing", 1)]);
+}
+
+#[test]
+fn renders_text() {
+    assert_eq!(covered().to_string(), "\
+states entered: 3/4
+never entered: Broken
+never transitioned: Typing, Broken
+handled:
+  Calc / Clear x1
+  Calc / Key x1
+  Empty / Digit x1
+  Typing / Digit x1
+transitions:
+  Calc -> Empty x1
+  Empty -> Typing x1
+");
+}
+
+#[test]
+fn variant_names_drop_the_payload() {
+    assert_eq!(hsm::variant_name("Digit(3)"), "Digit");
+    assert_eq!(hsm::variant_name("Key { code: 7 }"), "Key");
+    assert_eq!(hsm::variant_name("Clear"), "Clear");
+}
+
+#[cfg(feature = "json")]
+#[test]
+fn renders_json() {
+    let mut report = covered();
+    report.never_entered.push("Odd \"name\"".to_string());
+    let json: serde_json::Value = serde_json::from_str(&report.to_json()).unwrap();
+    assert_eq!(json["states"].as_array().unwrap().len(), 4);
+    assert_eq!(json["never_entered"][1], "Odd \"name\"");
+    assert_eq!(json["never_transitioned"], serde_json::json!(["Typing", "Broken"]));
+    assert_eq!(json["pairs"][1], serde_json::json!({"state": "Calc", "event": "Key", "count": 1}));
+    assert_eq!(json["edges"][0], serde_json::json!({"source": "Calc", "target": "Empty", "count": 1}));
+}
@@ -0,0 +1,197 @@
+/*
+ * The MIT License (MIT)
+ *
+ * Copyright (c) 2015 Mattis Marjak (mattis.marjak@gmail.com)
+ *
+ * Permission is hereby granted, free of charge, to any person obtaining a copy
+ * of this software and associated documentation files (the "Software"), to deal
+ * in the Software without restriction, including without limitation the rights
+ * to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+ * copies of the Software, and to permit persons to whom the Software is
+ * furnished to do so, subject to the following conditions:
+ *
+ * The above copyright notice and this permission notice shall be included in all
+ * copies or substantial portions of the Software.
+ *
+ * THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+ * IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+ * FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+ * AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+ * LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+ * OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+ * SOFTWARE.
+ */
+
+#![cfg(feature = "debug-server")]
+
+#[macro_use]
+extern crate hsm;
+extern crate serde_json;
+
+use std::io::{BufRead, BufReader, Read, Write};
+use std::net::TcpStream;
+use std::thread;
+use std::time::{Duration, Instant};
+use hsm::debug_server::DebugServer;
+use serde_json::Value;
+
+
+#[derive(Debug, Clone)]
+pub enum Events {
+    Open,
+    Close,
+    Crash,
+}
+
+// Exits run by the machine
+type Exits = Vec<&'static str>;
+
+hsm_define_objects!(StateStruct, States, Events, Exits, {
+    Root {
+        Closed,
+        Opened
+    }
+});
+
+// Crash reaches Root after Closed was queued for exit
+hsm_impl_state!(Root, Events, States, Exits, exits, evt, probe,
+    hsm::Event::User(Events::Crash) => panic!("crashed"),
+    hsm::Event::Exit(_) => { exits.push("Root"); hsm::Action::Ignore },
+    _ => hsm::Action::Parent
+);
+
+hsm_impl_state!(Closed, Events, States, Exits, exits, evt, probe,
+    hsm::Event::User(Events::Open) => hsm::Action::Transition(States::Opened),
+    hsm::Event::Exit(_) => { exits.push("Closed"); hsm::Action::Ignore },
+    _ => hsm::Action::Parent
+);
+
+hsm_impl_state!(Opened, Events, States, Exits, exits, evt, probe,
+    hsm::Event::User(Events::Close) => hsm::Action::Transition(States::Closed),
+    hsm::Event::Exit(_) => { exits.push("Opened"); hsm::Action::Ignore },
+    _ => hsm::Action::Ignore
+);
+
+type Machine = hsm::StateMachine<StateStruct, States, Events, Exits>;
+
+fn parse(name: &str) -> Option<Events> {
+    match name {
+        "Open"  => Some(Events::Open),
+        "Close" => Some(Events::Close),
+        "Crash" => Some(Events::Crash),
+        _       => None
+    }
+}
+
+fn connect(server: &DebugServer) -> (TcpStream, BufReader<TcpStream>) {
+    let stream = TcpStream::connect(server.tcp_addr().unwrap()).unwrap();
+    stream.set_read_timeout(Some(Duration::from_secs(5))).unwrap();
+    let reader = BufReader::new(stream.try_clone().unwrap());
+    (stream, reader)
+}
+
+// Sends `cmd` and polls the server until the client has its reply, skipping
+// the activity broadcast in between.
+fn command(server: &DebugServer, sm: &mut Machine, client: &mut (TcpStream, BufReader<TcpStream>), cmd: &str) -> Value {
+    client.0.write_all(format!("{}\n", cmd).as_bytes()).unwrap();
+    let deadline = Instant::now() + Duration::from_secs(5);
+    while server.poll(sm, parse) == 0 {
+        assert!(Instant::now() < deadline, "no command received");
+        thread::sleep(Duration::from_millis(5));
+    }
+    loop {
+        let mut line = String::new();
+        client.1.read_line(&mut line).unwrap();
+        let msg: Value = serde_json::from_str(&line).unwrap();
+        if msg["type"] == "configuration" || msg["type"] == "error" {
+            return msg;
+        }
+    }
+}
+
+// Waits until the accept thread has registered a client for the listener.
+fn started(server: &DebugServer) -> (Machine, (TcpStream, BufReader<TcpStream>)) {
+    let mut sm = Machine::new(States::Closed, Vec::new());
+    sm.add_listener(Box::new(server.listener()));
+    let mut client = connect(server);
+    sm.start();
+    assert_eq!(command(server, &mut sm, &mut client, "{\"cmd\": \"query\"}")["type"], "configuration");
+    (sm, client)
+}
+
+#[test]
+fn streams_activity_and_answers_commands() {
+    let server = DebugServer::bind_tcp("127.0.0.1:0").unwrap();
+    let (mut sm, mut client) = started(&server);
+
+    client.0.write_all(b"{\"cmd\": \"inject\", \"event\": \"Open\"}\n").unwrap();
+    while server.poll(&mut sm, parse) == 0 {
+        thread::sleep(Duration::from_millis(5));
+    }
+    let types: Vec<String> = (&mut client.1).lines().take(6)
+        .map(|l| serde_json::from_str::<Value>(&l.unwrap()).unwrap()["type"].as_str().unwrap().to_string())
+        .collect();
+    assert_eq!(types, ["event", "handled", "transition", "exit", "enter", "configuration"]);
+
+    let reply = command(&server, &mut sm, &mut client, "{\"cmd\": \"query\"}");
+    assert_eq!(reply["current"], "Opened");
+    assert_eq!(reply["started"], true);
+}
+
+#[test]
+fn reports_bad_commands_and_failed_events() {
+    let server = DebugServer::bind_tcp("127.0.0.1:0").unwrap();
+    let (mut sm, mut client) = started(&server);
+
+    for (cmd, message) in [("nonsense", "invalid command"),
+                           ("{\"cmd\": \"jump\"}", "unknown command"),
+                           ("{\"cmd\": \"inject\"}", "inject needs an \"event\" string"),
+                           ("{\"cmd\": \"inject\", \"event\": \"Fly\"}", "unknown event: Fly"),
+                           ("{\"cmd\": \"inject\", \"event\": \"Close\"}", "not handled"),
+                           ("{\"cmd\": \"inject\", \"event\": \"Crash\"}", "the machine panicked: crashed")].iter() {
+        let reply = command(&server, &mut sm, &mut client, cmd);
+        assert_eq!(reply["type"], "error", "{}", cmd);
+        assert!(reply["message"].as_str().unwrap().contains(message), "{}: {}", cmd, reply);
+    }
+}
+
+#[test]
+fn a_panicking_inject_leaves_no_exits_behind() {
+    let server = DebugServer::bind_tcp("127.0.0.1:0").unwrap();
+    let (mut sm, mut client) = started(&server);
+
+    assert_eq!(command(&server, &mut sm, &mut client, "{\"cmd\": \"inject\", \"event\": \"Crash\"}")["type"], "error");
+    let reply = command(&server, &mut sm, &mut client, "{\"cmd\": \"inject\", \"event\": \"Open\"}");
+    assert_eq!(reply["current"], "Opened");
+    assert_eq!(*sm.shared_data(), ["Closed"]);
+}
+
+#[test]
+fn refuses_addresses_other_than_loopback() {
+    for addr in ["0.0.0.0:0", "[::]:0"].iter() {
+        let e = DebugServer::bind_tcp(*addr).unwrap_err();
+        assert_eq!(e.kind(), ::std::io::ErrorKind::InvalidInput, "{}", addr);
+    }
+}
+
+#[test]
+fn dropping_the_server_stops_accepting_and_disconnects_clients() {
+    let server = DebugServer::bind_tcp("127.0.0.1:0").unwrap();
+    let addr = server.tcp_addr().unwrap();
+    let (_sm, mut client) = started(&server);
+    drop(server);
+
+    let mut rest = Vec::new();
+    client.1.read_to_end(&mut rest).unwrap();
+    assert!(TcpStream::connect(addr).is_err());
+}
+
+#[cfg(unix)]
+#[test]
+fn dropping_a_unix_server_removes_its_socket() {
+    let path = ::std::env::temp_dir().join(format!("hsm-debug-{}.sock", ::std::process::id()));
+    let server = DebugServer::bind_unix(&path).unwrap();
+    assert!(path.exists());
+    drop(server);
+    assert!(!path.exists());
+}
@@ -0,0 +1,118 @@
+/*
+ * The MIT License (MIT)
+ *
+ * Copyright (c) 2015 Mattis Marjak (mattis.marjak@gmail.com)
+ *
+ * Permission is hereby granted, free of charge, to any person obtaining a copy
+ * of this software and associated documentation files (the "Software"), to deal
+ * in the Software without restriction, including without limitation the rights
+ * to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+ * copies of the Software, and to permit persons to whom the Software is
+ * furnished to do so, subject to the following conditions:
+ *
+ * The above copyright notice and this permission notice shall be included in all
+ * copies or substantial portions of the Software.
+ *
+ * THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+ * IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+ * FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+ * AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+ * LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+ * OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+ * SOFTWARE.
+ */
+
+#[macro_use]
+extern crate hsm;
+
+use hsm::{Action, Event, State, StateLookup, StateMachine};
+
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Events {
+    Ping,
+    Echo,
+    Pong,
+}
+
+hsm_define_objects!(StateStruct, States, Events, Vec<String>, (
+    Root {},
+    Echoing { pending: bool = false },
+    Leaf {}
+));
+hsm_state_parents!(States; Root -> None, Echoing -> Root, Leaf -> Echoing);
+
+hsm_impl_state!(Root, Events, States, Vec<String>, log, evt, probe,
+    hsm::Event::User(Events::Ping) => { log.push("Root ping".to_string()); hsm::Action::Ignore },
+    hsm::Event::User(Events::Pong) => { log.push("Root pong".to_string()); hsm::Action::Ignore },
+    _ => hsm::Action::Ignore
+);
+
+// Written by hand to override take_event and handles
+impl State<Events, States, Vec<String>> for Echoing {
+    fn handle_event(&mut self, log: &mut Vec<String>, evt: &Event<Events, States>, _: bool) -> Action<States> {
+        match *evt {
+            Event::User(Events::Echo) => { log.push("Echoing echo".to_string()); self.pending = true; Action::Ignore },
+            Event::User(_)            => { log.push("Echoing probed".to_string()); Action::Parent },
+            _                         => Action::Ignore
+        }
+    }
+
+    fn take_event(&mut self) -> Option<Events> {
+        match ::std::mem::replace(&mut self.pending, false) {
+            true  => Some(Events::Pong),
+            false => None
+        }
+    }
+
+    fn handles(&self, evt: &Events) -> Option<bool> {
+        Some(*evt == Events::Echo)
+    }
+}
+
+hsm_impl_state!(Leaf, Events, States, Vec<String>,
+    _ => hsm::Action::Parent
+);
+
+// Only implements the required methods, so every call goes through `lookup`
+#[derive(Debug)]
+struct ThroughLookup(StateStruct);
+impl StateLookup<States, Events, Vec<String>> for ThroughLookup {
+    fn lookup(&mut self, typ: &States) -> &mut dyn State<Events, States, Vec<String>> {
+        self.0.lookup(typ)
+    }
+    fn parent(&self, typ: &States) -> Option<States> {
+        self.0.parent(typ)
+    }
+    fn states(&self) -> Vec<States> {
+        self.0.states()
+    }
+}
+
+fn run<S: StateLookup<States, Events, Vec<String>> + ::std::fmt::Debug>(states: S) -> Vec<String> {
+    let mut sm = StateMachine::with_states(States::Leaf, states, Vec::new());
+    sm.start();
+    sm.input(Events::Ping).unwrap();
+    sm.input(Events::Echo).unwrap();
+    sm.shared_data().clone()
+}
+
+#[test]
+fn generated_dispatch_reaches_the_concrete_handlers() {
+    let mut states = <StateStruct as hsm::Initializer>::new();
+    let mut log = Vec::new();
+    let action = states.handle_event(&States::Echoing, &mut log, &Event::User(Events::Echo), true);
+    assert!(matches!(action, Action::Ignore));
+    assert_eq!(states.handles(&States::Echoing, &Events::Ping), Some(false));
+    assert_eq!(states.handles(&States::Root, &Events::Ping), None);
+    assert_eq!(states.take_event(&States::Echoing), Some(Events::Pong));
+    assert_eq!(states.take_event(&States::Echoing), None);
+    assert_eq!(log, ["Echoing echo"]);
+}
+
+#[test]
+fn generated_and_lookup_dispatch_agree() {
+    let generated = run(<StateStruct as hsm::Initializer>::new());
+    assert_eq!(generated, ["Root ping", "Echoing echo", "Root pong"]);
+    assert_eq!(run(ThroughLookup(<StateStruct as hsm::Initializer>::new())), generated);
+}
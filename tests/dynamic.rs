@@ -0,0 +1,117 @@
+/*
+ * The MIT License (MIT)
+ *
+ * Copyright (c) 2015 Mattis Marjak (mattis.marjak@gmail.com)
+ *
+ * Permission is hereby granted, free of charge, to any person obtaining a copy
+ * of this software and associated documentation files (the "Software"), to deal
+ * in the Software without restriction, including without limitation the rights
+ * to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+ * copies of the Software, and to permit persons to whom the Software is
+ * furnished to do so, subject to the following conditions:
+ *
+ * The above copyright notice and this permission notice shall be included in all
+ * copies or substantial portions of the Software.
+ *
+ * THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+ * IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+ * FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+ * AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+ * LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+ * OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+ * SOFTWARE.
+ */
+
+extern crate hsm;
+
+use hsm::{Action, Event, Name, State, ValidationError};
+use hsm::dynamic::{BuildError, DynamicBuilder, DynamicStateMachine, StateId};
+
+
+#[derive(Debug, Clone)]
+pub enum Events {
+    Next,
+}
+
+// Goes to `next` on Events::Next, or passes it on
+#[derive(Debug)]
+struct Step {
+    next: Option<&'static str>
+}
+impl Name for Step {
+    fn name(&self) -> &'static str {
+        "Step"
+    }
+}
+impl State<Events, StateId, Vec<String>> for Step {
+    fn handle_event(&mut self, _: &mut Vec<String>, evt: &Event<Events, StateId>, _: bool) -> Action<StateId> {
+        match *evt {
+            Event::User(Events::Next) => match self.next {
+                Some(next) => Action::Transition(next.into()),
+                None       => Action::Parent
+            },
+            _ => Action::Ignore
+        }
+    }
+}
+
+fn step(next: Option<&'static str>) -> Step {
+    Step { next }
+}
+
+fn build(builder: DynamicBuilder<Events, Vec<String>>) -> Result<DynamicStateMachine<Events, Vec<String>>, BuildError> {
+    builder.build(Vec::new())
+}
+
+#[test]
+fn runs_a_machine_registered_at_runtime() {
+    let mut sm = build(DynamicBuilder::new()
+        .state("Root", None, step(None))
+        .state("Group", Some("Root"), step(None))
+        .state("First", Some("Group"), step(Some("Second")))
+        .state("Second", Some("Group"), step(Some("Group")))
+        .initial_child("Group", "First")
+        .initial("Group")).unwrap();
+    sm.start();
+    assert_eq!(sm.current().name(), "First");
+    sm.input(Events::Next).unwrap();
+    assert_eq!(sm.current().name(), "Second");
+    // targeting Group descends to its initial child again
+    sm.input(Events::Next).unwrap();
+    assert_eq!(sm.current().name(), "First");
+    assert_eq!(sm.active_states(), vec![StateId::new("First"), StateId::new("Group"), StateId::new("Root")]);
+}
+
+#[test]
+fn reports_registration_errors() {
+    assert_eq!(build(DynamicBuilder::new().state("A", None, step(None)).state("A", None, step(None)).initial("A")).unwrap_err(),
+               BuildError::DuplicateState("A".into()));
+    assert_eq!(build(DynamicBuilder::new().state("A", Some("X"), step(None)).initial("A")).unwrap_err(),
+               BuildError::UnknownParent { state: "A".into(), parent: "X".into() });
+    assert_eq!(build(DynamicBuilder::new().state("A", None, step(None))).unwrap_err(),
+               BuildError::NoInitial);
+    assert_eq!(build(DynamicBuilder::new().state("A", None, step(None)).initial("B")).unwrap_err(),
+               BuildError::UnknownInitial("B".into()));
+    assert_eq!(build(DynamicBuilder::new().state("A", None, step(None)).initial_child("B", "A").initial("A")).unwrap_err(),
+               BuildError::UnknownState("B".into()));
+}
+
+#[test]
+fn validates_the_hierarchy() {
+    let err = build(DynamicBuilder::new()
+        .state("A", Some("B"), step(None))
+        .state("B", Some("A"), step(None))
+        .initial("A")).unwrap_err();
+    assert_eq!(err, BuildError::Invalid(ValidationError::ParentCycle { states: vec!["A".into(), "B".into()] }));
+    assert_eq!(err.to_string(), "states [StateId(\"A\"), StateId(\"B\")] form a parent cycle");
+}
+
+#[test]
+#[should_panic(expected = "State Missing is not registered")]
+fn panics_on_transitions_to_unknown_states() {
+    let mut sm = build(DynamicBuilder::new()
+        .state("A", None, step(Some("Missing")))
+        .initial("A")).unwrap();
+    sm.start();
+    let _ = sm.input(Events::Next);
+}
@@ -0,0 +1,133 @@
+/*
+ * The MIT License (MIT)
+ *
+ * Copyright (c) 2015 Mattis Marjak (mattis.marjak@gmail.com)
+ *
+ * Permission is hereby granted, free of charge, to any person obtaining a copy
+ * of this software and associated documentation files (the "Software"), to deal
+ * in the Software without restriction, including without limitation the rights
+ * to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+ * copies of the Software, and to permit persons to whom the Software is
+ * furnished to do so, subject to the following conditions:
+ *
+ * The above copyright notice and this permission notice shall be included in all
+ * copies or substantial portions of the Software.
+ *
+ * THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+ * IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+ * FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+ * AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+ * LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+ * OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+ * SOFTWARE.
+ */
+
+#[macro_use]
+extern crate hsm;
+
+use std::sync::Mutex;
+use hsm::explore::Explorer;
+
+
+#[derive(Debug, Clone)]
+pub enum Events {
+    Go,
+    Finish,
+    Crash,
+    Stray,
+}
+
+hsm_define_objects_noparents!(StateStruct, States, Events, u8, (
+    Idle {},
+    Busy {},
+    Done {},
+    Orphan {}
+));
+
+hsm_impl_state!(Idle, Events, States, u8,
+    hsm::Event::User(Events::Go) => hsm::Action::Transition(States::Busy),
+    hsm::Event::User(Events::Stray) => hsm::Action::Parent,
+    _ => hsm::Action::Ignore
+);
+
+hsm_impl_state!(Busy, Events, States, u8,
+    hsm::Event::User(Events::Finish) => hsm::Action::Transition(States::Done),
+    hsm::Event::User(Events::Crash) => panic!("crashed while busy"),
+    _ => hsm::Action::Ignore
+);
+
+hsm_impl_state!(Done, Events, States, u8,
+    hsm::Event::User(Events::Stray) => hsm::Action::Parent,
+    _ => hsm::Action::Ignore
+);
+
+hsm_impl_state!(Orphan, Events, States, u8,
+    _ => hsm::Action::Ignore
+);
+
+fn machine() -> hsm::StateMachine<StateStruct, States, Events, u8> {
+    hsm::StateMachine::new(States::Idle, 0)
+}
+
+#[test]
+fn reports_unreachable_states_and_deadlocks() {
+    let report = Explorer::new(vec![Events::Go, Events::Finish]).explore(&machine());
+    assert_eq!(report.unreachable, vec![States::Orphan]);
+    assert_eq!(report.deadlocks.len(), 1);
+    assert_eq!(report.deadlocks[0].state, States::Done);
+    assert_eq!(report.configurations, 3);
+    assert!(report.panics.is_empty());
+    assert!(!report.truncated);
+}
+
+#[test]
+fn reports_panics_with_the_shortest_path() {
+    let report = Explorer::new(vec![Events::Go, Events::Finish, Events::Crash]).explore(&machine());
+    assert_eq!(report.panics.len(), 1);
+    assert_eq!(report.panics[0].message, "crashed while busy");
+    assert_eq!(format!("{:?}", report.panics[0].path), "[Go, Crash]");
+}
+
+#[test]
+fn reports_input_errors_per_state_and_event() {
+    let report = Explorer::new(vec![Events::Go, Events::Finish, Events::Stray]).explore(&machine());
+    let errors: Vec<String> = report.errors.iter().map(|e| format!("{:?} after {:?}", e.error, e.path)).collect();
+    assert_eq!(errors, [
+        "Unhandled { state: Idle, event: Stray } after [Stray]",
+        "Unhandled { state: Done, event: Stray } after [Go, Finish, Stray]",
+    ]);
+    assert!(report.panics.is_empty());
+}
+
+#[test]
+fn stops_at_the_depth_limit() {
+    let report = Explorer::new(vec![Events::Go, Events::Finish]).max_depth(1).explore(&machine());
+    assert!(report.truncated);
+    assert!(report.deadlocks.is_empty());
+}
+
+// Fields that aren't Clone don't stop a machine from being defined, only
+// explored
+hsm_define_objects!(LockStruct, LockStates, Events, (), (
+    Locked {
+        data: Mutex<Vec<u8>> = Mutex::new(Vec::new())
+    }
+));
+
+hsm_impl_state!(Locked, Events, LockStates, (), self, shr, evt, probe,
+    hsm::Event::User(Events::Go) => {
+        self.data.lock().unwrap().push(1);
+        hsm::Action::Ignore
+    },
+    _ => hsm::Action::Ignore
+);
+
+hsm_state_parents!(LockStates ; Locked -> None);
+
+#[test]
+fn states_with_non_clone_fields_compile() {
+    let mut sm = hsm::StateMachine::<LockStruct, LockStates, Events, ()>::new(LockStates::Locked, ());
+    sm.start();
+    sm.input(Events::Go).unwrap();
+    assert!(format!("{:?}", sm).contains("data: Mutex { data: [1]"));
+}
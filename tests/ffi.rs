@@ -0,0 +1,147 @@
+/*
+ * The MIT License (MIT)
+ *
+ * Copyright (c) 2015 Mattis Marjak (mattis.marjak@gmail.com)
+ *
+ * Permission is hereby granted, free of charge, to any person obtaining a copy
+ * of this software and associated documentation files (the "Software"), to deal
+ * in the Software without restriction, including without limitation the rights
+ * to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+ * copies of the Software, and to permit persons to whom the Software is
+ * furnished to do so, subject to the following conditions:
+ *
+ * The above copyright notice and this permission notice shall be included in all
+ * copies or substantial portions of the Software.
+ *
+ * THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+ * IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+ * FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+ * AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+ * LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+ * OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+ * SOFTWARE.
+ */
+
+#![cfg(feature = "ffi")]
+
+#[macro_use]
+extern crate hsm;
+
+use std::ptr;
+use hsm::ffi::{INVALID, NO_STATE, OK, PANICKED, REJECTED};
+
+
+#[derive(Debug, Clone)]
+pub enum Events {
+    Coin(u8),
+    Push,
+    Kick,
+}
+
+#[derive(Debug)]
+pub struct Till {
+    coins  : u32,
+    jammed : bool,
+}
+
+hsm_define_objects_noparents!(StateStruct, States, Events, Till, (
+    Locked,
+    Unlocked
+));
+
+hsm_impl_state!(Locked, Events, States, Till, till, evt, probe,
+    hsm::Event::Enter(_) => {
+        assert!(!till.jammed, "jammed");
+        hsm::Action::Ignore
+    },
+    hsm::Event::User(Events::Coin(n)) => {
+        till.coins += n as u32;
+        hsm::Action::Transition(States::Unlocked)
+    },
+    hsm::Event::User(Events::Kick) => hsm::Action::Parent,
+    _ => hsm::Action::Ignore
+);
+
+hsm_impl_state!(Unlocked, Events, States, Till, till, evt, probe,
+    hsm::Event::User(Events::Push) => hsm::Action::Transition(States::Locked),
+    hsm::Event::User(Events::Kick) => panic!("kicked"),
+    _ => hsm::Action::Ignore
+);
+
+hsm_ffi!(turnstile ; StateStruct, States, Events, Till ;
+    new => hsm::StateMachine::new(States::Locked, Till { coins: 0, jammed: false }) ;
+    events {
+        COIN = 0 => |data: &[u8]| data.first().map(|n| Events::Coin(*n)),
+        PUSH = 1 => |_| Some(Events::Push),
+        KICK = 2 => |_| Some(Events::Kick)
+    }
+);
+
+hsm_ffi!(jammed ; StateStruct, States, Events, Till ;
+    new => hsm::StateMachine::new(States::Locked, Till { coins: 0, jammed: true }) ;
+    events {}
+);
+
+#[test]
+fn drives_a_machine_through_the_c_api() {
+    unsafe {
+        let m = turnstile::new();
+        assert_eq!(turnstile::start(m), OK);
+        assert_eq!(turnstile::current(m), States::Locked as u32);
+        assert_eq!(turnstile::input(m, 0, [2u8].as_ptr(), 1), OK);
+        assert_eq!(turnstile::current(m), States::Unlocked as u32);
+        assert_eq!((*m).shared_data().coins, 2);
+        assert_eq!(turnstile::input(m, 1, ptr::null(), 0), OK);
+        assert_eq!(turnstile::current(m), States::Locked as u32);
+        turnstile::free(m);
+    }
+}
+
+#[test]
+fn reports_bad_input_errors_and_panics() {
+    unsafe {
+        let m = turnstile::new();
+        assert_eq!(turnstile::start(m), OK);
+        // unknown event ID, and a coin without its payload
+        assert_eq!(turnstile::input(m, 7, ptr::null(), 0), INVALID);
+        assert_eq!(turnstile::input(m, 0, ptr::null(), 0), INVALID);
+        // nobody handles a kick while locked
+        assert_eq!(turnstile::input(m, 2, ptr::null(), 0), REJECTED);
+        assert_eq!(turnstile::input(m, 0, [1u8].as_ptr(), 1), OK);
+        assert_eq!(turnstile::input(m, 2, ptr::null(), 0), PANICKED);
+        turnstile::free(m);
+    }
+}
+
+#[test]
+fn null_machines_are_rejected() {
+    unsafe {
+        assert_eq!(turnstile::start(ptr::null_mut()), INVALID);
+        assert_eq!(turnstile::input(ptr::null_mut(), 1, ptr::null(), 0), INVALID);
+        assert_eq!(turnstile::current(ptr::null()), NO_STATE);
+        turnstile::free(ptr::null_mut());
+    }
+}
+
+#[test]
+fn start_reports_a_panic() {
+    unsafe {
+        let m = jammed::new();
+        assert_eq!(jammed::start(m), PANICKED);
+        jammed::free(m);
+    }
+}
+
+#[test]
+fn renders_a_matching_header() {
+    let header = turnstile::c_header();
+    for line in ["#define TURNSTILE_STATE_LOCKED 0",
+                 "#define TURNSTILE_STATE_UNLOCKED 1",
+                 "#define TURNSTILE_EVENT_KICK 2",
+                 "#define TURNSTILE_PANICKED -2",
+                 "#define TURNSTILE_NO_STATE UINT32_MAX",
+                 "int32_t turnstile_start(turnstile_machine *m);",
+                 "uint32_t turnstile_current(const turnstile_machine *m);"].iter() {
+        assert!(header.lines().any(|l| l == *line), "{} missing from\n{}", line, header);
+    }
+}
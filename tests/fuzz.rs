@@ -0,0 +1,108 @@
+/*
+ * The MIT License (MIT)
+ *
+ * Copyright (c) 2015 Mattis Marjak (mattis.marjak@gmail.com)
+ *
+ * Permission is hereby granted, free of charge, to any person obtaining a copy
+ * of this software and associated documentation files (the "Software"), to deal
+ * in the Software without restriction, including without limitation the rights
+ * to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+ * copies of the Software, and to permit persons to whom the Software is
+ * furnished to do so, subject to the following conditions:
+ *
+ * The above copyright notice and this permission notice shall be included in all
+ * copies or substantial portions of the Software.
+ *
+ * THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+ * IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+ * FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+ * AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+ * LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+ * OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+ * SOFTWARE.
+ */
+
+#[macro_use]
+extern crate hsm;
+
+use hsm::fuzz::{FailureKind, Fuzzer};
+
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Events {
+    Inc,
+    Noop,
+    Boom,
+}
+
+hsm_define_objects_noparents!(StateStruct, States, Events, u32, (
+    Counting {}
+));
+
+hsm_impl_state!(Counting, Events, States, u32, count, evt, probe,
+    hsm::Event::User(Events::Inc) => {
+        *count += 1;
+        hsm::Action::Ignore
+    },
+    hsm::Event::User(Events::Boom) => {
+        if *count >= 3 {
+            panic!("boom at {}", count);
+        }
+        hsm::Action::Ignore
+    },
+    _ => hsm::Action::Ignore
+);
+
+fn machine() -> hsm::StateMachine<StateStruct, States, Events, u32> {
+    hsm::StateMachine::new(States::Counting, 0)
+}
+
+#[test]
+fn passes_when_invariants_hold() {
+    let fuzzer = Fuzzer::new(vec![Events::Inc, Events::Noop])
+        .runs(5)
+        .invariant("never negative", |_, _| true);
+    assert!(fuzzer.run(&machine()).is_ok());
+}
+
+#[test]
+fn shrinks_an_invariant_failure() {
+    let failure = Fuzzer::new(vec![Events::Inc, Events::Noop])
+        .invariant("below five", |_, count| *count < 5)
+        .run(&machine())
+        .unwrap_err();
+    assert_eq!(failure.kind, FailureKind::Invariant("below five".to_string()));
+    assert_eq!(failure.events, vec![Events::Inc; 5]);
+    assert!(failure.original_len >= 5);
+}
+
+#[test]
+fn shrinks_a_panic() {
+    let failure = Fuzzer::weighted(vec![(Events::Inc, 3), (Events::Noop, 3), (Events::Boom, 1)])
+        .run(&machine())
+        .unwrap_err();
+    // the kind keeps the message of the unshrunk run
+    match failure.kind {
+        FailureKind::Panic(ref message) => assert!(message.starts_with("boom at "), "{}", message),
+        ref kind                        => panic!("unexpected failure {:?}", kind)
+    }
+    assert_eq!(failure.events, vec![Events::Inc, Events::Inc, Events::Inc, Events::Boom]);
+}
+
+#[test]
+fn the_seed_makes_runs_repeatable() {
+    let run = |seed| Fuzzer::new(vec![Events::Inc, Events::Noop, Events::Boom])
+        .seed(seed)
+        .run(&machine())
+        .unwrap_err();
+    let (a, b) = (run(7), run(7));
+    assert_eq!(a.seed, b.seed);
+    assert_eq!(a.original_len, b.original_len);
+    assert_eq!(a.events, b.events);
+}
+
+#[test]
+#[should_panic(expected = "at least one event with a non-zero weight")]
+fn rejects_an_alphabet_without_weight() {
+    Fuzzer::<States, Events, u32>::weighted(vec![(Events::Inc, 0)]);
+}
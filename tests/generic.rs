@@ -0,0 +1,153 @@
+/*
+ * The MIT License (MIT)
+ *
+ * Copyright (c) 2015 Mattis Marjak (mattis.marjak@gmail.com)
+ *
+ * Permission is hereby granted, free of charge, to any person obtaining a copy
+ * of this software and associated documentation files (the "Software"), to deal
+ * in the Software without restriction, including without limitation the rights
+ * to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+ * copies of the Software, and to permit persons to whom the Software is
+ * furnished to do so, subject to the following conditions:
+ *
+ * The above copyright notice and this permission notice shall be included in all
+ * copies or substantial portions of the Software.
+ *
+ * THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+ * IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+ * FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+ * AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+ * LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+ * OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+ * SOFTWARE.
+ */
+
+#[macro_use]
+extern crate hsm;
+
+use std::fmt;
+use hsm::explore::Explorer;
+
+
+#[derive(Debug, Clone)]
+pub enum Events<T> {
+    Push(T),
+    Flush,
+}
+
+#[derive(Debug, Clone)]
+pub struct Sink<T> {
+    flushed: Vec<T>
+}
+
+hsm_define_objects!(StateStruct<T>, States, Events<T>, Sink<T>, {
+    Buffer {
+        Empty,
+        Filling {items: Vec<T> = Vec::new()}
+    }
+} where T: fmt::Debug + Clone);
+
+hsm_impl_state!(<T> Buffer<T>, Events<T>, States, Sink<T>,
+    _ => hsm::Action::Ignore
+);
+
+hsm_impl_state!(<T> where [T: Clone] Empty<T>, Events<T>, States, Sink<T>, shr, evt, probe,
+    hsm::Event::User(Events::Push(_)) => hsm::Action::Transition(States::Filling),
+    _ => hsm::Action::Parent
+);
+
+hsm_impl_state!(<T> where [T: Clone] Filling<T>, Events<T>, States, Sink<T>, self, sink, evt, probe,
+    hsm::Event::Enter(ref t) => {
+        if let Some(Events::Push(ref item)) = t.trigger {
+            self.items.push(item.clone());
+        }
+        hsm::Action::Ignore
+    },
+    hsm::Event::User(Events::Push(ref item)) => {
+        self.items.push(item.clone());
+        hsm::Action::Ignore
+    },
+    hsm::Event::User(Events::Flush) => {
+        sink.flushed.append(&mut self.items);
+        hsm::Action::Transition(States::Empty)
+    },
+    _ => hsm::Action::Parent
+);
+
+fn machine<T: fmt::Debug + Clone>() -> hsm::StateMachine<StateStruct<T>, States, Events<T>, Sink<T>> {
+    hsm::StateMachine::new(States::Buffer, Sink { flushed: Vec::new() })
+}
+
+fn run<T: fmt::Debug + Clone>(items: Vec<T>) -> Vec<T> {
+    let mut sm = machine();
+    sm.start();
+    for item in items {
+        sm.input(Events::Push(item)).unwrap();
+    }
+    sm.input(Events::Flush).unwrap();
+    assert_eq!(*sm.current(), States::Empty);
+    sm.shared_data().flushed.clone()
+}
+
+#[derive(Debug, Clone, PartialEq)]
+struct Point {
+    x: i32,
+    y: i32,
+}
+
+#[test]
+fn one_definition_serves_several_payload_types() {
+    assert_eq!(run(vec![1, 2, 3]), [1, 2, 3]);
+    assert_eq!(run(vec!["a".to_string()]), ["a"]);
+    assert_eq!(run(vec![Point { x: 1, y: 2 }]), [Point { x: 1, y: 2 }]);
+    assert!(run(Vec::<u8>::new()).is_empty());
+}
+
+#[test]
+fn generic_machines_can_be_cloned_and_explored() {
+    let report = Explorer::new(vec![Events::Push(true), Events::Flush]).max_depth(3).explore(&machine());
+    assert!(report.unreachable.is_empty());
+    assert!(report.panics.is_empty() && report.errors.is_empty());
+}
+
+// Two parameters, flat definition without parents
+#[derive(Debug, Clone)]
+pub enum Pair<A, B> {
+    Left(A),
+    Right(B),
+}
+
+hsm_define_objects_noparents!(PairStruct<A, B>, PairStates, Pair<A, B>, Vec<String>, (
+    WaitLeft,
+    WaitRight {left: Option<A> = None}
+) where A: fmt::Debug + Clone, B: fmt::Debug);
+
+hsm_impl_state!(<A, B> WaitLeft<A, B>, Pair<A, B>, PairStates, Vec<String>,
+    hsm::Event::User(Pair::Left(_)) => hsm::Action::Transition(PairStates::WaitRight),
+    _ => hsm::Action::Ignore
+);
+
+hsm_impl_state!(<A, B> where [A: fmt::Debug + Clone, B: fmt::Debug] WaitRight<A, B>, Pair<A, B>, PairStates, Vec<String>, self, log, evt, probe,
+    hsm::Event::Enter(ref t) => {
+        if let Some(Pair::Left(ref a)) = t.trigger {
+            self.left = Some(a.clone());
+        }
+        hsm::Action::Ignore
+    },
+    hsm::Event::User(Pair::Right(ref b)) => {
+        log.push(format!("{:?} and {:?}", self.left, b));
+        hsm::Action::Transition(PairStates::WaitLeft)
+    },
+    _ => hsm::Action::Ignore
+);
+
+#[test]
+fn machines_take_several_type_parameters() {
+    let mut sm = hsm::StateMachine::<PairStruct<u8, &str>, PairStates, Pair<u8, &str>, Vec<String>>::new(PairStates::WaitLeft, Vec::new());
+    sm.start();
+    sm.input(Pair::Right("ignored")).unwrap();
+    sm.input(Pair::Left(4)).unwrap();
+    sm.input(Pair::Right("four")).unwrap();
+    assert_eq!(*sm.current(), PairStates::WaitLeft);
+    assert_eq!(*sm.shared_data(), ["Some(4) and \"four\""]);
+}
@@ -0,0 +1,116 @@
+/*
+ * The MIT License (MIT)
+ *
+ * Copyright (c) 2015 Mattis Marjak (mattis.marjak@gmail.com)
+ *
+ * Permission is hereby granted, free of charge, to any person obtaining a copy
+ * of this software and associated documentation files (the "Software"), to deal
+ * in the Software without restriction, including without limitation the rights
+ * to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+ * copies of the Software, and to permit persons to whom the Software is
+ * furnished to do so, subject to the following conditions:
+ *
+ * The above copyright notice and this permission notice shall be included in all
+ * copies or substantial portions of the Software.
+ *
+ * THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+ * IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+ * FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+ * AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+ * LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+ * OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+ * SOFTWARE.
+ */
+
+#[macro_use]
+extern crate hsm;
+
+
+#[derive(Debug, Clone)]
+pub enum Events {
+    Digit(u8),
+    Plus,
+    Reset,
+    Clear,
+}
+
+// Which states had their handler called, and for what
+pub type Calls = Vec<String>;
+
+hsm_define_objects!(StateStruct, States, Events, Calls, (
+    Root {},
+    Calc {},
+    Typing {}
+));
+hsm_state_parents!(States; Root -> None, Calc -> Root, Typing -> Calc);
+
+hsm_impl_state!(Root, Events, States, Calls, self, calls, evt, probe,
+    pre { calls.push(format!("Root {:?}", evt)); },
+    hsm::Event::User(Events::Reset) => hsm::Action::Transition(States::Typing),
+    _ => hsm::Action::Ignore
+);
+
+// Only ever asked about Plus, other user events skip it
+hsm_impl_state!(Calc, Events, States, Calls, handles Events::Plus, self, calls, evt, probe,
+    pre { calls.push(format!("Calc {:?}", evt)); },
+    hsm::Event::User(Events::Plus) => hsm::Action::Ignore,
+    _ => hsm::Action::Parent
+);
+
+// Takes small digits only, a declared event can still go to the parent
+hsm_impl_state!(Typing, Events, States, Calls, handles Events::Digit(_) | Events::Reset, self, calls, evt, probe,
+    pre { calls.push(format!("Typing {:?}", evt)); },
+    hsm::Event::User(Events::Digit(0..=4)) => hsm::Action::Ignore,
+    _ => hsm::Action::Parent
+);
+
+fn calls_for(evts: &[Events]) -> Calls {
+    let mut sm = hsm::StateMachine::<StateStruct, States, Events, Calls>::new(States::Typing, Vec::new());
+    sm.start();
+    sm.shared_data_mut().clear();
+    for evt in evts {
+        sm.input(evt.clone()).unwrap();
+    }
+    sm.shared_data().clone()
+}
+
+#[test]
+fn states_that_declare_an_event_are_probed() {
+    assert_eq!(calls_for(&[Events::Digit(3)]), ["Typing User(Digit(3))"]);
+    assert_eq!(calls_for(&[Events::Plus]), ["Calc User(Plus)"]);
+}
+
+#[test]
+fn undeclared_events_skip_to_the_parent() {
+    // Neither Typing nor Calc declare Clear
+    assert_eq!(calls_for(&[Events::Clear]), ["Root User(Clear)"]);
+}
+
+#[test]
+fn declared_events_may_still_go_to_the_parent() {
+    // Calc doesn't declare digits, so a large one goes from Typing to Root
+    assert_eq!(calls_for(&[Events::Digit(7)]), ["Typing User(Digit(7))", "Root User(Digit(7))"]);
+    let calls = calls_for(&[Events::Reset]);
+    assert_eq!(calls[0], "Typing User(Reset)");
+    // Calc is skipped, Root handles Reset and then everything is exited and entered again
+    assert_eq!(calls[1], "Root User(Reset)");
+    assert!(calls[2..].iter().all(|c| !c.contains("User")), "{:?}", calls);
+    assert!(calls.iter().any(|c| c.starts_with("Calc Exit")), "{:?}", calls);
+}
+
+#[test]
+fn skips_are_remembered_per_variant() {
+    let calls = calls_for(&[Events::Clear, Events::Digit(7), Events::Clear, Events::Digit(3), Events::Plus, Events::Digit(9)]);
+    assert_eq!(calls, [
+        "Root User(Clear)",
+        "Typing User(Digit(7))", "Root User(Digit(7))",
+        "Root User(Clear)",
+        "Typing User(Digit(3))",
+        "Calc User(Plus)",
+        "Typing User(Digit(9))", "Root User(Digit(9))",
+    ]);
+
+    // The cached skip still queues Calc's exit
+    let calls = calls_for(&[Events::Reset, Events::Reset]);
+    assert_eq!(calls.iter().filter(|c| c.starts_with("Calc Exit")).count(), 2, "{:?}", calls);
+}
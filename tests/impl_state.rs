@@ -0,0 +1,106 @@
+/*
+ * The MIT License (MIT)
+ *
+ * Copyright (c) 2015 Mattis Marjak (mattis.marjak@gmail.com)
+ *
+ * Permission is hereby granted, free of charge, to any person obtaining a copy
+ * of this software and associated documentation files (the "Software"), to deal
+ * in the Software without restriction, including without limitation the rights
+ * to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+ * copies of the Software, and to permit persons to whom the Software is
+ * furnished to do so, subject to the following conditions:
+ *
+ * The above copyright notice and this permission notice shall be included in all
+ * copies or substantial portions of the Software.
+ *
+ * THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+ * IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+ * FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+ * AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+ * LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+ * OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+ * SOFTWARE.
+ */
+
+#[macro_use]
+extern crate hsm;
+
+
+#[derive(Debug, Clone)]
+pub enum Events {
+    Digit(u8),
+    Minus,
+    Done,
+}
+
+hsm_define_objects_noparents!(StateStruct, States, Events, Vec<String>, (
+    WaitMinusOrInt { counter: u8 = 0 },
+    Summing { total: u32 = 0, seen: u32 = 0 },
+    Closing { events: u32 = 0 },
+    Closed {}
+));
+
+// Fields through `self`, no pre or post block
+hsm_impl_state!(WaitMinusOrInt, Events, States, Vec<String>, self, log, evt, probe,
+    hsm::Event::User(Events::Minus) => {
+        self.counter += 1;
+        log.push(format!("minus {}", self.counter));
+        match self.counter {
+            2 => hsm::Action::Transition(States::Summing),
+            _ => hsm::Action::Ignore
+        }
+    },
+    _ => hsm::Action::Ignore
+);
+
+hsm_impl_state!(Summing, Events, States, Vec<String>, self, log, evt, probe,
+    pre { self.seen += 1; },
+    post { log.push(format!("seen {} total {}", self.seen, self.total)); },
+    hsm::Event::User(Events::Digit(d)) => { self.total += d as u32; hsm::Action::Ignore },
+    hsm::Event::User(Events::Done) => hsm::Action::Transition(States::Closing),
+    _ => hsm::Action::Ignore
+);
+
+hsm_impl_state!(Closing, Events, States, Vec<String>, self, log, evt, probe,
+    post { self.events += 1; },
+    hsm::Event::User(Events::Done) => {
+        log.push(format!("closing after {}", self.events));
+        hsm::Action::Transition(States::Closed)
+    },
+    _ => hsm::Action::Ignore
+);
+
+hsm_impl_state!(Closed, Events, States, Vec<String>, self, log, evt, probe,
+    pre { log.push(format!("closed got {:?}", evt)); },
+    _ => hsm::Action::Ignore
+);
+
+fn run(events: Vec<Events>) -> Vec<String> {
+    let mut sm = hsm::StateMachine::<StateStruct, States, Events, Vec<String>>::new(States::WaitMinusOrInt, Vec::new());
+    sm.start();
+    for evt in events {
+        sm.input(evt).unwrap();
+    }
+    sm.shared_data().clone()
+}
+
+#[test]
+fn handlers_update_state_fields() {
+    let log = run(vec![Events::Minus, Events::Digit(1), Events::Minus]);
+    assert_eq!(log[..2], ["minus 1", "minus 2"]);
+}
+
+#[test]
+fn pre_and_post_run_around_every_call() {
+    let log = run(vec![Events::Minus, Events::Minus, Events::Digit(4), Events::Digit(5), Events::Done]);
+    // Enter, two digits, Done and the Exit it causes
+    assert_eq!(log[2..], ["seen 1 total 0", "seen 2 total 4", "seen 3 total 9", "seen 4 total 9", "seen 5 total 9"]);
+}
+
+#[test]
+fn post_sees_the_effects_of_the_arm() {
+    let log = run(vec![Events::Minus, Events::Minus, Events::Done, Events::Digit(1), Events::Done]);
+    // Enter and Digit ran the post block before the second Done was matched
+    assert!(log.contains(&"closing after 2".to_string()), "{:?}", log);
+    assert!(log.last().unwrap().starts_with("closed got Enter"), "{:?}", log);
+}
@@ -0,0 +1,179 @@
+/*
+ * The MIT License (MIT)
+ *
+ * Copyright (c) 2015 Mattis Marjak (mattis.marjak@gmail.com)
+ *
+ * Permission is hereby granted, free of charge, to any person obtaining a copy
+ * of this software and associated documentation files (the "Software"), to deal
+ * in the Software without restriction, including without limitation the rights
+ * to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+ * copies of the Software, and to permit persons to whom the Software is
+ * furnished to do so, subject to the following conditions:
+ *
+ * The above copyright notice and this permission notice shall be included in all
+ * copies or substantial portions of the Software.
+ *
+ * THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+ * IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+ * FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+ * AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+ * LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+ * OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+ * SOFTWARE.
+ */
+
+#[macro_use]
+extern crate hsm;
+
+use hsm::InputError;
+
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Events {
+    ToGroup,
+    ToMember,
+    // Delayed transitions into Group and Member, with the target named up front
+    Later,
+    LaterMember,
+    // Delayed transition into Group picking its target after the exits
+    LaterUnnamed,
+}
+
+#[derive(Debug)]
+pub struct Log {
+    seen: Vec<String>,
+}
+
+fn record(log: &mut Log, state: &str, evt: &hsm::Event<Events, States>) {
+    match *evt {
+        hsm::Event::Enter(_)     => log.seen.push(format!("enter {}", state)),
+        hsm::Event::Exit(ref t)  => log.seen.push(format!("exit {} to {:?}", state, t.target)),
+        _                        => {}
+    }
+}
+
+// Group has a child but no initial child
+hsm_define_objects!(StateStruct, States, Events, Log, (
+    Root {},
+    Idle {},
+    Group {},
+    Member {}
+));
+hsm_state_parents!(States; Root -> None, Idle -> Root, Group -> Root, Member -> Group);
+
+// Idle is exited before the actions of delayed transitions run here
+hsm_impl_state!(Root, Events, States, Log, log, evt, probe,
+    hsm::Event::User(Events::Later) => hsm_delayed_transition!(probe, States::Group, {
+        log.seen.push("action".to_string());
+    }),
+    hsm::Event::User(Events::LaterMember) => hsm_delayed_transition!(probe, States::Member, {
+        log.seen.push("action".to_string());
+    }),
+    hsm::Event::User(Events::LaterUnnamed) => hsm_delayed_transition!(probe, {
+        log.seen.push("action".to_string());
+        States::Group
+    }),
+    _ => hsm::Action::Ignore
+);
+
+hsm_impl_state!(Idle, Events, States, Log, log, evt, probe,
+    hsm::Event::User(Events::ToGroup) => hsm::Action::Transition(States::Group),
+    hsm::Event::User(Events::ToMember) => hsm::Action::Transition(States::Member),
+    _ => {
+        record(log, "Idle", evt);
+        hsm::Action::Parent
+    }
+);
+
+hsm_impl_state!(Group, Events, States, Log, log, evt, probe,
+    _ => {
+        record(log, "Group", evt);
+        hsm::Action::Parent
+    }
+);
+
+hsm_impl_state!(Member, Events, States, Log, log, evt, probe,
+    _ => {
+        record(log, "Member", evt);
+        hsm::Action::Parent
+    }
+);
+
+type Machine = hsm::StateMachine<StateStruct, States, Events, Log>;
+
+fn started(leaf_only: bool) -> Machine {
+    let mut sm = Machine::new(States::Idle, Log { seen: Vec::new() });
+    sm.set_leaf_only(leaf_only);
+    sm.start();
+    sm.shared_data_mut().seen.clear();
+    sm
+}
+
+fn seen(sm: &Machine) -> &[String] {
+    &sm.shared_data().seen
+}
+
+#[test]
+fn composite_targets_are_allowed_unless_leaf_only() {
+    let mut sm = started(false);
+    assert!(!sm.is_leaf_only());
+    assert_eq!(sm.input(Events::ToGroup), Ok(()));
+    assert_eq!(*sm.current(), States::Group);
+}
+
+#[test]
+fn a_composite_target_is_refused_before_any_exit() {
+    let mut sm = started(true);
+    assert_eq!(sm.input(Events::ToGroup), Err(InputError::CompositeTarget(States::Group)));
+    assert_eq!(*sm.current(), States::Idle);
+    assert!(seen(&sm).is_empty());
+
+    assert_eq!(sm.input(Events::ToMember), Ok(()));
+    assert_eq!(*sm.current(), States::Member);
+    assert_eq!(seen(&sm), ["exit Idle to Some(Member)", "enter Group", "enter Member"]);
+}
+
+#[test]
+fn a_named_delayed_target_is_checked_before_the_exits_and_the_action() {
+    let mut sm = started(true);
+    assert_eq!(sm.input(Events::Later), Err(InputError::CompositeTarget(States::Group)));
+    assert_eq!(*sm.current(), States::Idle);
+    assert!(seen(&sm).is_empty());
+
+    // The exits see the target named while probing
+    assert_eq!(sm.input(Events::LaterMember), Ok(()));
+    assert_eq!(*sm.current(), States::Member);
+    assert_eq!(seen(&sm), ["exit Idle to Some(Member)", "action", "enter Group", "enter Member"]);
+}
+
+#[test]
+fn unnamed_delayed_targets_are_allowed_unless_leaf_only() {
+    let mut sm = started(false);
+    assert_eq!(sm.input(Events::LaterUnnamed), Ok(()));
+    assert_eq!(*sm.current(), States::Group);
+    assert_eq!(seen(&sm), ["exit Idle to None", "action", "enter Group"]);
+}
+
+#[test]
+fn an_unnamed_delayed_composite_target_is_refused_after_the_action() {
+    let mut sm = started(true);
+    assert_eq!(sm.input(Events::LaterUnnamed), Err(InputError::CompositeTarget(States::Group)));
+    assert_eq!(*sm.current(), States::Idle);
+    assert_eq!(seen(&sm), ["exit Idle to None", "action", "enter Idle"]);
+}
+
+#[test]
+fn leaf_only_can_be_switched_off() {
+    let mut sm = started(true);
+    sm.set_leaf_only(false);
+    assert_eq!(sm.input(Events::ToGroup), Ok(()));
+    assert_eq!(*sm.current(), States::Group);
+}
+
+#[test]
+#[should_panic(expected = "Invalid state machine")]
+fn starting_in_a_composite_state_panics_when_leaf_only() {
+    let mut sm = Machine::new(States::Group, Log { seen: Vec::new() });
+    sm.set_leaf_only(true);
+    sm.start();
+}
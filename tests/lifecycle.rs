@@ -0,0 +1,104 @@
+/*
+ * The MIT License (MIT)
+ *
+ * Copyright (c) 2015 Mattis Marjak (mattis.marjak@gmail.com)
+ *
+ * Permission is hereby granted, free of charge, to any person obtaining a copy
+ * of this software and associated documentation files (the "Software"), to deal
+ * in the Software without restriction, including without limitation the rights
+ * to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+ * copies of the Software, and to permit persons to whom the Software is
+ * furnished to do so, subject to the following conditions:
+ *
+ * The above copyright notice and this permission notice shall be included in all
+ * copies or substantial portions of the Software.
+ *
+ * THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+ * IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+ * FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+ * AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+ * LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+ * OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+ * SOFTWARE.
+ */
+
+#[macro_use]
+extern crate hsm;
+
+
+#[derive(Debug, Clone)]
+pub enum Events {
+    Go,
+    Tick,
+}
+
+hsm_define_objects!(StateStruct, States, Events, Vec<String>, (
+    Root {},
+    Idle {},
+    Counting { ticks: u32 = 0 }
+));
+hsm_state_parents!(States; Root -> None, Idle -> Root, Counting -> Root);
+
+hsm_impl_state!(Root, Events, States, Vec<String>, log, evt, probe,
+    hsm::Event::Enter(_) => { log.push("enter Root".to_string()); hsm::Action::Ignore },
+    hsm::Event::Exit(_)  => { log.push("exit Root".to_string()); hsm::Action::Ignore },
+    _ => hsm::Action::Ignore
+);
+
+hsm_impl_state!(Idle, Events, States, Vec<String>, log, evt, probe,
+    hsm::Event::Enter(_) => { log.push("enter Idle".to_string()); hsm::Action::Ignore },
+    hsm::Event::User(Events::Go) => hsm::Action::Transition(States::Counting),
+    _ => hsm::Action::Parent
+);
+
+hsm_impl_state!(Counting, Events, States, Vec<String>, self, log, evt, probe,
+    hsm::Event::Enter(_) => { log.push(format!("enter Counting at {}", self.ticks)); hsm::Action::Ignore },
+    hsm::Event::Exit(_)  => { log.push(format!("exit Counting at {}", self.ticks)); hsm::Action::Ignore },
+    hsm::Event::User(Events::Tick) => { self.ticks += 1; hsm::Action::Ignore },
+    _ => hsm::Action::Parent
+);
+
+type Machine = hsm::StateMachine<StateStruct, States, Events, Vec<String>>;
+
+fn counted(ticks: u32) -> Machine {
+    let mut sm = Machine::new(States::Idle, Vec::new());
+    sm.start();
+    sm.input(Events::Go).unwrap();
+    for _ in 0..ticks {
+        sm.input(Events::Tick).unwrap();
+    }
+    sm.shared_data_mut().clear();
+    sm
+}
+
+#[test]
+fn stop_exits_the_active_states_leaf_to_root() {
+    let mut sm = counted(2);
+    sm.stop();
+    assert!(!sm.is_started());
+    assert_eq!(*sm.shared_data(), ["exit Counting at 2", "exit Root"]);
+    // a stopped machine has nothing left to exit
+    sm.stop();
+    assert_eq!(sm.shared_data().len(), 2);
+}
+
+#[test]
+fn reset_rebuilds_the_states_without_running_actions() {
+    let mut sm = counted(3);
+    sm.reset();
+    assert!(!sm.is_started());
+    assert_eq!(*sm.current(), States::Idle);
+    assert!(sm.shared_data().is_empty());
+    sm.start();
+    sm.input(Events::Go).unwrap();
+    assert_eq!(sm.shared_data().last().unwrap(), "enter Counting at 0");
+}
+
+#[test]
+fn restart_stops_resets_and_starts_again() {
+    let mut sm = counted(1);
+    sm.restart();
+    assert!(sm.is_started());
+    assert_eq!(*sm.current(), States::Idle);
+    assert_eq!(*sm.shared_data(), ["exit Counting at 1", "exit Root", "enter Root", "enter Idle"]);
+}
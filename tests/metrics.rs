@@ -0,0 +1,115 @@
+/*
+ * The MIT License (MIT)
+ *
+ * Copyright (c) 2015 Mattis Marjak (mattis.marjak@gmail.com)
+ *
+ * Permission is hereby granted, free of charge, to any person obtaining a copy
+ * of this software and associated documentation files (the "Software"), to deal
+ * in the Software without restriction, including without limitation the rights
+ * to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+ * copies of the Software, and to permit persons to whom the Software is
+ * furnished to do so, subject to the following conditions:
+ *
+ * The above copyright notice and this permission notice shall be included in all
+ * copies or substantial portions of the Software.
+ *
+ * THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+ * IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+ * FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+ * AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+ * LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+ * OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+ * SOFTWARE.
+ */
+
+#[macro_use]
+extern crate hsm;
+
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::Duration;
+use hsm::metrics::Metrics;
+
+
+#[derive(Debug, Clone)]
+pub enum Events {
+    Go,
+    Done,
+    Bogus,
+}
+
+hsm_define_objects_noparents!(StateStruct, States, Events, (), (
+    Idle,
+    Busy
+));
+
+hsm_impl_state!(Idle, Events, States, (),
+    hsm::Event::User(Events::Go) => hsm::Action::Transition(States::Busy),
+    hsm::Event::User(Events::Bogus) => hsm::Action::Parent,
+    _ => hsm::Action::Ignore
+);
+
+hsm_impl_state!(Busy, Events, States, (),
+    hsm::Event::User(Events::Done) => hsm::Action::Transition(States::Idle),
+    _ => hsm::Action::Ignore
+);
+
+fn instrumented() -> (hsm::StateMachine<StateStruct, States, Events, ()>, Arc<Mutex<Metrics>>) {
+    let metrics = Arc::new(Mutex::new(Metrics::new()));
+    let mut sm = hsm::StateMachine::new(States::Idle, ());
+    sm.add_listener(Box::new(metrics.clone()));
+    sm.set_unhandled_policy(hsm::UnhandledPolicy::Ignore);
+    sm.start();
+    (sm, metrics)
+}
+
+#[test]
+fn counts_events_transitions_and_unhandled_events() {
+    let (mut sm, metrics) = instrumented();
+    sm.input(Events::Go).unwrap();
+    sm.input(Events::Go).unwrap();
+    sm.input(Events::Done).unwrap();
+    sm.input(Events::Bogus).unwrap();
+
+    let metrics = metrics.lock().unwrap();
+    assert_eq!(metrics.events("Idle"), 2);
+    assert_eq!(metrics.events("Busy"), 2);
+    assert_eq!(metrics.transitions("Idle", "Busy"), 1);
+    assert_eq!(metrics.transitions("Busy", "Idle"), 1);
+    assert_eq!(metrics.transitions("Idle", "Idle"), 0);
+    assert_eq!(metrics.unhandled("Idle"), 1);
+}
+
+#[test]
+fn sums_dwell_time_including_the_current_visit() {
+    let (mut sm, metrics) = instrumented();
+    sm.input(Events::Go).unwrap();
+    thread::sleep(Duration::from_millis(20));
+    sm.input(Events::Done).unwrap();
+
+    let metrics = metrics.lock().unwrap();
+    let busy = metrics.dwell("Busy");
+    assert!(busy >= Duration::from_millis(20), "{:?}", busy);
+    assert_eq!(metrics.dwell("Busy"), busy);
+    // Idle is current again and keeps accumulating
+    let idle = metrics.dwell("Idle");
+    thread::sleep(Duration::from_millis(5));
+    assert!(metrics.dwell("Idle") > idle);
+}
+
+#[test]
+fn renders_prometheus_text() {
+    let (mut sm, metrics) = instrumented();
+    sm.input(Events::Bogus).unwrap();
+    sm.input(Events::Go).unwrap();
+
+    let text = metrics.lock().unwrap().render_prometheus("door \"1\"");
+    let lines: Vec<&str> = text.lines().collect();
+    for name in ["hsm_events_total", "hsm_transitions_total", "hsm_unhandled_events_total", "hsm_state_dwell_seconds_total"].iter() {
+        assert!(lines.contains(&format!("# TYPE {} counter", name).as_str()), "{}", text);
+    }
+    assert!(lines.contains(&"hsm_events_total{machine=\"door \\\"1\\\"\",state=\"Idle\"} 2"), "{}", text);
+    assert!(lines.contains(&"hsm_transitions_total{machine=\"door \\\"1\\\"\",source=\"Idle\",target=\"Busy\"} 1"), "{}", text);
+    assert!(lines.contains(&"hsm_unhandled_events_total{machine=\"door \\\"1\\\"\",state=\"Idle\"} 1"), "{}", text);
+    assert!(lines.iter().any(|l| l.starts_with("hsm_state_dwell_seconds_total{machine=\"door \\\"1\\\"\",state=\"Busy\"} ")), "{}", text);
+}
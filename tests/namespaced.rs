@@ -0,0 +1,126 @@
+/*
+ * The MIT License (MIT)
+ *
+ * Copyright (c) 2015 Mattis Marjak (mattis.marjak@gmail.com)
+ *
+ * Permission is hereby granted, free of charge, to any person obtaining a copy
+ * of this software and associated documentation files (the "Software"), to deal
+ * in the Software without restriction, including without limitation the rights
+ * to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+ * copies of the Software, and to permit persons to whom the Software is
+ * furnished to do so, subject to the following conditions:
+ *
+ * The above copyright notice and this permission notice shall be included in all
+ * copies or substantial portions of the Software.
+ *
+ * THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+ * IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+ * FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+ * AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+ * LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+ * OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+ * SOFTWARE.
+ */
+
+#[macro_use]
+extern crate hsm;
+
+
+#[derive(Debug, Clone)]
+pub enum Events {
+    Go,
+    Stop,
+}
+
+// Three machines with an Idle state next to each other, none of them needs
+// anything imported
+hsm_define_objects!(mod motor, StateStruct, States, Events, (), (
+    Powered {},
+    Idle {},
+    Running {}
+));
+hsm_state_parents!(mod motor, States ; Powered -> None, Idle -> Powered, Running -> Powered);
+
+hsm_define_objects_noparents!(mod lamp, StateStruct, States, Events, (), (
+    Idle,
+    On
+));
+
+hsm_define_objects!(mod door, StateStruct, States, Events, (), {
+    Idle,
+    Moving
+});
+
+hsm_impl_state!(motor::Powered, Events, motor::States, (),
+    _ => hsm::Action::Ignore
+);
+
+hsm_impl_state!(motor::Idle, Events, motor::States, (),
+    hsm::Event::User(Events::Go) => hsm::Action::Transition(motor::States::Running),
+    _ => hsm::Action::Parent
+);
+
+hsm_impl_state!(motor::Running, Events, motor::States, (),
+    hsm::Event::User(Events::Stop) => hsm::Action::Transition(motor::States::Idle),
+    _ => hsm::Action::Parent
+);
+
+hsm_impl_state!(lamp::Idle, Events, lamp::States, (),
+    hsm::Event::User(Events::Go) => hsm::Action::Transition(lamp::States::On),
+    _ => hsm::Action::Ignore
+);
+
+hsm_impl_state!(lamp::On, Events, lamp::States, (),
+    _ => hsm::Action::Ignore
+);
+
+hsm_impl_state!(door::Idle, Events, door::States, (),
+    hsm::Event::User(Events::Go) => hsm::Action::Transition(door::States::Moving),
+    _ => hsm::Action::Ignore
+);
+
+hsm_impl_state!(door::Moving, Events, door::States, (),
+    _ => hsm::Action::Ignore
+);
+
+// The flat form still works inside a module of the user's own
+mod garage {
+    use Events;
+
+    hsm_define_objects_noparents!(StateStruct, States, Events, (), (
+        Idle,
+        Open
+    ));
+
+    hsm_impl_state!(Idle, Events, States, (),
+        hsm::Event::User(Events::Go) => hsm::Action::Transition(States::Open),
+        _ => hsm::Action::Ignore
+    );
+
+    hsm_impl_state!(Open, Events, States, (),
+        _ => hsm::Action::Ignore
+    );
+}
+
+#[test]
+fn machines_with_the_same_state_names_coexist() {
+    let mut motor = hsm::StateMachine::<motor::StateStruct, motor::States, Events, ()>::new(motor::States::Idle, ());
+    let mut lamp = hsm::StateMachine::<lamp::StateStruct, lamp::States, Events, ()>::new(lamp::States::Idle, ());
+    let mut door = hsm::StateMachine::<door::StateStruct, door::States, Events, ()>::new(door::States::Idle, ());
+    let mut garage = hsm::StateMachine::<garage::StateStruct, garage::States, Events, ()>::new(garage::States::Idle, ());
+    motor.start();
+    lamp.start();
+    door.start();
+    garage.start();
+    for evt in [Events::Go, Events::Stop, Events::Go] {
+        motor.input(evt).unwrap();
+    }
+    lamp.input(Events::Go).unwrap();
+    door.input(Events::Go).unwrap();
+    garage.input(Events::Go).unwrap();
+    assert_eq!(motor.active_states(), [motor::States::Running, motor::States::Powered]);
+    assert_eq!(*lamp.current(), lamp::States::On);
+    assert_eq!(*door.current(), door::States::Moving);
+    assert_eq!(*garage.current(), garage::States::Open);
+    assert_eq!(door.all_states(), [door::States::Idle, door::States::Moving]);
+}
@@ -0,0 +1,127 @@
+/*
+ * The MIT License (MIT)
+ *
+ * Copyright (c) 2015 Mattis Marjak (mattis.marjak@gmail.com)
+ *
+ * Permission is hereby granted, free of charge, to any person obtaining a copy
+ * of this software and associated documentation files (the "Software"), to deal
+ * in the Software without restriction, including without limitation the rights
+ * to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+ * copies of the Software, and to permit persons to whom the Software is
+ * furnished to do so, subject to the following conditions:
+ *
+ * The above copyright notice and this permission notice shall be included in all
+ * copies or substantial portions of the Software.
+ *
+ * THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+ * IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+ * FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+ * AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+ * LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+ * OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+ * SOFTWARE.
+ */
+
+#[macro_use]
+extern crate hsm;
+
+use hsm::StateLookup;
+
+
+#[derive(Debug, Clone)]
+pub enum Events {
+    Connect,
+    Connected,
+    Drop,
+    Fail,
+}
+
+hsm_define_objects!(StateStruct, States, Events, Vec<String>, {
+    Root {
+        Idle,
+        Active {attempts: u8 = 0} {
+            Connecting,
+            Online {speed: u32 = 100}
+        }
+    },
+    Broken {}
+});
+
+hsm_impl_state!(Root, Events, States, Vec<String>,
+    hsm::Event::User(Events::Fail) => hsm::Action::Transition(States::Broken),
+    _ => hsm::Action::Ignore
+);
+
+hsm_impl_state!(Idle, Events, States, Vec<String>,
+    hsm::Event::User(Events::Connect) => hsm::Action::Transition(States::Active),
+    _ => hsm::Action::Parent
+);
+
+hsm_impl_state!(Active, Events, States, Vec<String>, self, log, evt, probe,
+    hsm::Event::Enter(_) => {
+        self.attempts += 1;
+        log.push(format!("attempt {}", self.attempts));
+        hsm::Action::Ignore
+    },
+    hsm::Event::User(Events::Drop) => hsm::Action::Transition(States::Idle),
+    _ => hsm::Action::Parent
+);
+
+hsm_impl_state!(Connecting, Events, States, Vec<String>,
+    hsm::Event::User(Events::Connected) => hsm::Action::Transition(States::Online),
+    _ => hsm::Action::Parent
+);
+
+hsm_impl_state!(Online, Events, States, Vec<String>, self, log, evt, probe,
+    hsm::Event::Enter(_) => { log.push(format!("online at {}", self.speed)); hsm::Action::Ignore },
+    _ => hsm::Action::Parent
+);
+
+hsm_impl_state!(Broken, Events, States, Vec<String>,
+    _ => hsm::Action::Ignore
+);
+
+fn started() -> hsm::StateMachine<StateStruct, States, Events, Vec<String>> {
+    let mut sm = hsm::StateMachine::new(States::Root, Vec::new());
+    sm.start();
+    sm
+}
+
+#[test]
+fn declares_parents_and_depths() {
+    let sm = started();
+    assert_eq!(sm.all_states(), [States::Root, States::Idle, States::Active, States::Connecting, States::Online, States::Broken]);
+    let depths: Vec<usize> = sm.all_states().iter().map(|s| s.depth()).collect();
+    assert_eq!(depths, [0, 1, 1, 2, 2, 0]);
+    let states = <StateStruct as hsm::Initializer>::new();
+    assert_eq!(states.parent(&States::Online), Some(States::Active));
+    assert_eq!(states.parent(&States::Active), Some(States::Root));
+    assert_eq!(states.parent(&States::Root), None);
+    assert_eq!(states.parent(&States::Broken), None);
+    assert_eq!(sm.validate(), Ok(()));
+}
+
+#[test]
+fn first_children_are_entered_when_their_parent_is_targeted() {
+    let states = <StateStruct as hsm::Initializer>::new();
+    assert_eq!(states.initial_child(&States::Root), Some(States::Idle));
+    assert_eq!(states.initial_child(&States::Active), Some(States::Connecting));
+    assert_eq!(states.initial_child(&States::Online), None);
+
+    let mut sm = started();
+    assert_eq!(*sm.current(), States::Idle);
+    sm.input(Events::Connect).unwrap();
+    assert_eq!(sm.active_states(), [States::Connecting, States::Active, States::Root]);
+}
+
+#[test]
+fn nested_states_keep_their_fields() {
+    let mut sm = started();
+    sm.input(Events::Connect).unwrap();
+    sm.input(Events::Connected).unwrap();
+    sm.input(Events::Drop).unwrap();
+    sm.input(Events::Connect).unwrap();
+    assert_eq!(*sm.shared_data(), ["attempt 1", "online at 100", "attempt 2"]);
+    sm.input(Events::Fail).unwrap();
+    assert_eq!(sm.active_states(), [States::Broken]);
+}
@@ -0,0 +1,359 @@
+/*
+ * The MIT License (MIT)
+ *
+ * Copyright (c) 2015 Mattis Marjak (mattis.marjak@gmail.com)
+ *
+ * Permission is hereby granted, free of charge, to any person obtaining a copy
+ * of this software and associated documentation files (the "Software"), to deal
+ * in the Software without restriction, including without limitation the rights
+ * to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+ * copies of the Software, and to permit persons to whom the Software is
+ * furnished to do so, subject to the following conditions:
+ *
+ * The above copyright notice and this permission notice shall be included in all
+ * copies or substantial portions of the Software.
+ *
+ * THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+ * IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+ * FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+ * AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+ * LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+ * OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+ * SOFTWARE.
+ */
+
+#[macro_use]
+extern crate hsm;
+
+use std::cell::Cell;
+use std::rc::Rc;
+
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Events {
+    Connect,
+    Challenge,
+    Response,
+    LoggedIn,
+    Disconnect,
+    Count,
+    Skip,
+}
+
+mod login {
+    use Events as OuterEvents;
+
+    #[derive(Debug, Clone, PartialEq)]
+    pub enum Events {
+        Challenge,
+        Response,
+    }
+
+    // Exits seen by the inner machine
+    pub type Exits = Vec<States>;
+
+    hsm_define_objects_noparents!(StateStruct, States, Events, Exits, (
+        WaitChallenge,
+        WaitResponse,
+        LoggedIn
+    ));
+
+    hsm_impl_state!(WaitChallenge, Events, States, Exits, exits, evt, probe,
+        ::hsm::Event::User(Events::Challenge) => ::hsm::Action::Transition(States::WaitResponse),
+        ::hsm::Event::Exit(_) => { exits.push(States::WaitChallenge); ::hsm::Action::Ignore },
+        _ => ::hsm::Action::Unhandled
+    );
+
+    hsm_impl_state!(WaitResponse, Events, States, Exits, exits, evt, probe,
+        ::hsm::Event::User(Events::Response) => ::hsm::Action::Transition(States::LoggedIn),
+        ::hsm::Event::Exit(_) => { exits.push(States::WaitResponse); ::hsm::Action::Ignore },
+        _ => ::hsm::Action::Ignore
+    );
+
+    hsm_impl_state!(LoggedIn, Events, States, Exits,
+        _ => ::hsm::Action::Ignore
+    );
+
+    pub type Machine = ::hsm::Submachine<StateStruct, States, Events, Exits, OuterEvents>;
+
+    pub fn new() -> Machine {
+        ::hsm::Submachine::new(::hsm::StateMachine::new(States::WaitChallenge, Vec::new()), forward, complete)
+    }
+
+    fn forward(evt: &OuterEvents) -> Option<Events> {
+        match *evt {
+            OuterEvents::Challenge => Some(Events::Challenge),
+            OuterEvents::Response  => Some(Events::Response),
+            _                      => None
+        }
+    }
+
+    fn complete(state: &States, _: &Exits) -> Option<OuterEvents> {
+        match *state {
+            States::LoggedIn => Some(OuterEvents::LoggedIn),
+            _                => None
+        }
+    }
+}
+
+// A leaf only machine whose only transition targets a state without an
+// initial child, so every forwarded Connect fails
+mod strict {
+    use Events as OuterEvents;
+
+    #[derive(Debug, Clone, PartialEq)]
+    pub enum Events {
+        Go,
+    }
+
+    hsm_define_objects!(StateStruct, States, Events, (), (
+        Start {},
+        Group {},
+        Member {}
+    ));
+    hsm_state_parents!(States; Start -> None, Group -> None, Member -> Group);
+
+    hsm_impl_state!(Start, Events, States, (),
+        ::hsm::Event::User(Events::Go) => ::hsm::Action::Transition(States::Group),
+        _ => ::hsm::Action::Ignore
+    );
+
+    hsm_impl_state!(Group, Events, States, (),
+        _ => ::hsm::Action::Ignore
+    );
+
+    hsm_impl_state!(Member, Events, States, (),
+        _ => ::hsm::Action::Ignore
+    );
+
+    pub fn new() -> ::hsm::Submachine<StateStruct, States, Events, (), OuterEvents> {
+        let mut machine = ::hsm::StateMachine::new(States::Start, ());
+        machine.set_leaf_only(true);
+        ::hsm::Submachine::new(machine, forward, complete)
+    }
+
+    fn forward(evt: &OuterEvents) -> Option<Events> {
+        match *evt {
+            OuterEvents::Connect => Some(Events::Go),
+            _                    => None
+        }
+    }
+
+    fn complete(_: &States, _: &()) -> Option<OuterEvents> {
+        None
+    }
+}
+
+// Counts Ticks in a state field and logs each count, Skips are left
+// unhandled after bumping SKIPS
+mod counter {
+    use std::cell::Cell;
+    use Events as OuterEvents;
+
+    thread_local!(pub static SKIPS: Cell<u32> = const { Cell::new(0) });
+
+    #[derive(Debug, Clone, PartialEq)]
+    pub enum Events {
+        Tick,
+        Skip,
+    }
+
+    hsm_define_objects_noparents!(StateStruct, States, Events, Vec<u32>, (
+        Counting { count: u32 = 0 }
+    ));
+
+    hsm_impl_state!(Counting, Events, States, Vec<u32>, self, log, evt, probe,
+        ::hsm::Event::User(Events::Tick) => {
+            self.count += 1;
+            log.push(self.count);
+            ::hsm::Action::Ignore
+        },
+        ::hsm::Event::User(Events::Skip) => {
+            SKIPS.with(|skips| skips.set(skips.get() + 1));
+            ::hsm::Action::Unhandled
+        },
+        _ => ::hsm::Action::Ignore
+    );
+
+    pub type Machine = ::hsm::Submachine<StateStruct, States, Events, Vec<u32>, OuterEvents>;
+
+    pub fn new() -> Machine {
+        ::hsm::Submachine::new(::hsm::StateMachine::new(States::Counting, Vec::new()), forward, complete)
+    }
+
+    fn forward(evt: &OuterEvents) -> Option<Events> {
+        match *evt {
+            OuterEvents::Count => Some(Events::Tick),
+            OuterEvents::Skip  => Some(Events::Skip),
+            _                  => None
+        }
+    }
+
+    fn complete(_: &States, _: &Vec<u32>) -> Option<OuterEvents> {
+        None
+    }
+}
+
+hsm_define_objects!(StateStruct, States, Events, (), (
+    Session {},
+    Disconnected {},
+    Login { login: login::Machine = login::new() },
+    Connected {},
+    Counter { counter: counter::Machine = counter::new() }
+));
+hsm_state_parents!(States; Session -> None, Disconnected -> Session, Login -> Session, Connected -> Session, Counter -> Session);
+
+hsm_impl_state!(Session, Events, States, (),
+    hsm::Event::User(Events::Disconnect) => hsm::Action::Transition(States::Disconnected),
+    _ => hsm::Action::Ignore
+);
+
+hsm_impl_state!(Disconnected, Events, States, (),
+    hsm::Event::User(Events::Connect) => hsm::Action::Transition(States::Login),
+    hsm::Event::User(Events::Count) => hsm::Action::Transition(States::Counter),
+    _ => hsm::Action::Parent
+);
+
+hsm_impl_submachine!(Login, login, Events, States, (),
+    hsm::Event::User(Events::LoggedIn) => hsm::Action::Transition(States::Connected),
+    _ => hsm::Action::Parent
+);
+
+hsm_impl_state!(Connected, Events, States, (),
+    _ => hsm::Action::Parent
+);
+
+hsm_impl_submachine!(Counter, counter, Events, States, (), shr, evt, probe,
+    hsm::Event::User(Events::Skip) => hsm_delayed_transition!(probe, States::Disconnected, {}),
+    _ => hsm::Action::Parent
+);
+
+fn started() -> hsm::StateMachine<StateStruct, States, Events, ()> {
+    let mut sm = hsm::StateMachine::new(States::Disconnected, ());
+    sm.start();
+    sm
+}
+
+#[test]
+fn completing_the_submachine_moves_the_outer_machine() {
+    let mut sm = started();
+    sm.input(Events::Connect).unwrap();
+    sm.input(Events::Challenge).unwrap();
+    assert_eq!(*sm.current(), States::Login);
+    sm.input(Events::Response).unwrap();
+    assert_eq!(*sm.current(), States::Connected);
+}
+
+#[test]
+fn reentering_restarts_the_submachine() {
+    let mut sm = started();
+    sm.input(Events::Connect).unwrap();
+    sm.input(Events::Challenge).unwrap();
+    sm.input(Events::Disconnect).unwrap();
+    sm.input(Events::Connect).unwrap();
+    sm.input(Events::Challenge).unwrap();
+    assert_eq!(*sm.current(), States::Login);
+    sm.input(Events::Response).unwrap();
+    assert_eq!(*sm.current(), States::Connected);
+}
+
+fn user(evt: Events) -> hsm::Event<Events, States> {
+    hsm::Event::User(evt)
+}
+
+fn ctx() -> Rc<hsm::Transition<States, Events>> {
+    Rc::new(hsm::Transition { source: None, target: None, trigger: None })
+}
+
+#[test]
+fn forwards_mapped_events_and_passes_the_rest_on() {
+    let mut sub = login::new();
+    sub.handle_event(&hsm::Event::Enter(ctx()), false);
+    assert_eq!(*sub.machine().current(), login::States::WaitChallenge);
+    match sub.handle_event(&user(Events::Challenge), false) {
+        hsm::Action::Ignore => {},
+        action              => panic!("unexpected {:?}", action)
+    }
+    assert_eq!(*sub.machine().current(), login::States::WaitResponse);
+    match sub.handle_event(&user(Events::Connect), false) {
+        hsm::Action::Parent => {},
+        action              => panic!("unexpected {:?}", action)
+    }
+    assert_eq!(sub.take_event(), None);
+}
+
+#[test]
+fn hands_out_the_completion_event_once() {
+    let mut sub = login::new();
+    sub.handle_event(&hsm::Event::Enter(ctx()), false);
+    sub.handle_event(&user(Events::Challenge), false);
+    sub.handle_event(&user(Events::Response), false);
+    assert_eq!(sub.take_event(), Some(Events::LoggedIn));
+    assert_eq!(sub.take_event(), None);
+}
+
+#[test]
+fn exiting_runs_the_inner_exits() {
+    let mut sub = login::new();
+    sub.handle_event(&hsm::Event::Enter(ctx()), false);
+    sub.handle_event(&user(Events::Challenge), false);
+    sub.handle_event(&hsm::Event::Exit(ctx()), false);
+    assert!(!sub.machine().is_started());
+    assert_eq!(*sub.machine().shared_data(), vec![login::States::WaitChallenge, login::States::WaitResponse]);
+}
+
+#[test]
+fn events_the_submachine_leaves_unhandled_go_to_the_parent() {
+    let mut sub = login::new();
+    sub.handle_event(&hsm::Event::Enter(ctx()), false);
+    match sub.handle_event(&user(Events::Response), false) {
+        hsm::Action::Parent => {},
+        action              => panic!("unexpected {:?}", action)
+    }
+    assert_eq!(*sub.machine().current(), login::States::WaitChallenge);
+    assert_eq!(sub.take_error(), None);
+
+    // Session ignores it for the outer machine
+    let mut sm = started();
+    sm.input(Events::Connect).unwrap();
+    assert_eq!(sm.input(Events::Response), Ok(()));
+    assert_eq!(*sm.current(), States::Login);
+}
+
+#[test]
+fn other_submachine_errors_are_kept_instead_of_panicking() {
+    let mut sub = strict::new();
+    sub.handle_event(&hsm::Event::Enter(ctx()), false);
+    match sub.handle_event(&user(Events::Connect), false) {
+        hsm::Action::Unhandled => {},
+        action                 => panic!("unexpected {:?}", action)
+    }
+    assert_eq!(*sub.machine().current(), strict::States::Start);
+    assert_eq!(sub.take_error(), Some(hsm::InputError::CompositeTarget(strict::States::Group)));
+    assert_eq!(sub.take_error(), None);
+}
+
+#[test]
+fn reentering_rebuilds_the_inner_states_and_data() {
+    let mut sub = counter::new();
+    sub.handle_event(&hsm::Event::Enter(ctx()), false);
+    sub.handle_event(&user(Events::Count), false);
+    sub.handle_event(&user(Events::Count), false);
+    assert_eq!(*sub.machine().shared_data(), vec![1, 2]);
+    sub.handle_event(&hsm::Event::Exit(ctx()), false);
+    sub.handle_event(&hsm::Event::Enter(ctx()), false);
+    assert_eq!(*sub.machine().shared_data(), Vec::<u32>::new());
+    sub.handle_event(&user(Events::Count), false);
+    assert_eq!(*sub.machine().shared_data(), vec![1]);
+}
+
+#[test]
+fn a_delayed_transition_feeds_the_submachine_once() {
+    counter::SKIPS.with(|skips| skips.set(0));
+    let mut sm = started();
+    sm.input(Events::Count).unwrap();
+    assert_eq!(*sm.current(), States::Counter);
+    sm.input(Events::Skip).unwrap();
+    assert_eq!(*sm.current(), States::Disconnected);
+    assert_eq!(counter::SKIPS.with(Cell::get), 1);
+}
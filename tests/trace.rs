@@ -0,0 +1,163 @@
+/*
+ * The MIT License (MIT)
+ *
+ * Copyright (c) 2015 Mattis Marjak (mattis.marjak@gmail.com)
+ *
+ * Permission is hereby granted, free of charge, to any person obtaining a copy
+ * of this software and associated documentation files (the "Software"), to deal
+ * in the Software without restriction, including without limitation the rights
+ * to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+ * copies of the Software, and to permit persons to whom the Software is
+ * furnished to do so, subject to the following conditions:
+ *
+ * The above copyright notice and this permission notice shall be included in all
+ * copies or substantial portions of the Software.
+ *
+ * THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+ * IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+ * FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+ * AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+ * LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+ * OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+ * SOFTWARE.
+ */
+
+#![cfg(feature = "json")]
+
+#[macro_use]
+extern crate hsm;
+extern crate serde_json;
+
+use std::fs;
+use std::io::Cursor;
+use std::process::Command;
+use std::sync::{Arc, Mutex};
+use hsm::trace::{self, Record, TraceWriter};
+
+
+#[derive(Debug, Clone)]
+pub enum Events {
+    Open,
+    Digit(u8),
+    Close,
+}
+
+hsm_define_objects!(StateStruct, States, Events, (), (
+    Door {},
+    Closed {},
+    Opened {}
+));
+hsm_state_parents!(States; Door -> None, Closed -> Door, Opened -> Door);
+
+hsm_impl_state!(Door, Events, States, (),
+    _ => hsm::Action::Parent
+);
+
+hsm_impl_state!(Closed, Events, States, (),
+    hsm::Event::User(Events::Open) => hsm::Action::Transition(States::Opened),
+    _ => hsm::Action::Parent
+);
+
+hsm_impl_state!(Opened, Events, States, (),
+    hsm::Event::User(Events::Digit(_)) => hsm::Action::Ignore,
+    hsm::Event::User(Events::Close) => hsm::Action::Transition(States::Closed),
+    _ => hsm::Action::Parent
+);
+
+// Runs a short session and returns its trace
+fn traced() -> Vec<Record> {
+    let writer = Arc::new(Mutex::new(TraceWriter::new(Vec::new())));
+    let mut sm = hsm::StateMachine::<StateStruct, States, Events, ()>::new(States::Closed, ());
+    sm.add_listener(Box::new(writer.clone()));
+    sm.set_unhandled_policy(hsm::UnhandledPolicy::Ignore);
+    sm.start();
+    sm.input(Events::Open).unwrap();
+    sm.input(Events::Digit(3)).unwrap();
+    sm.input(Events::Open).unwrap();
+    sm.input(Events::Close).unwrap();
+    sm.stop();
+    drop(sm);
+    let out = Arc::try_unwrap(writer).unwrap().into_inner().unwrap().into_inner();
+    trace::read(Cursor::new(out)).unwrap()
+}
+
+#[test]
+fn records_every_event_with_its_exits_and_enters() {
+    let records = traced();
+    let summary: Vec<String> = records.iter().map(|r| format!("{:?} {:?} {} {:?} {:?} {}",
+        r.event, r.state, r.unhandled, r.exits, r.enters, r.current)).collect();
+    assert_eq!(summary, [
+        "None None false [] [\"Door\", \"Closed\"] Closed",
+        "Some(\"Open\") Some(\"Closed\") false [\"Closed\"] [\"Opened\"] Opened",
+        "Some(\"Digit(3)\") Some(\"Opened\") false [] [] Opened",
+        "Some(\"Open\") Some(\"Door\") true [] [] Opened",
+        "Some(\"Close\") Some(\"Opened\") false [\"Opened\"] [\"Closed\"] Closed",
+        "None None false [\"Closed\", \"Door\"] [] Closed",
+    ]);
+    assert!(records[0].is_start() && !records[0].is_stop());
+    assert!(records[5].is_stop() && !records[5].is_start());
+    assert!(records.windows(2).all(|w| w[0].ts <= w[1].ts));
+}
+
+#[test]
+fn reading_names_the_bad_line_and_skips_blank_ones() {
+    let doc = "{\"ts\":1.0,\"event\":null,\"state\":null,\"current\":\"A\",\"future\":1}\n\n{\"ts\":2.0}\n";
+    let err = trace::read(Cursor::new(doc)).unwrap_err();
+    assert!(err.to_string().starts_with("line 3: "), "{}", err);
+    assert_eq!(trace::read(Cursor::new(&doc[..doc.find("\n\n").unwrap()])).unwrap().len(), 1);
+}
+
+fn hsm_trace(args: &[&str]) -> (bool, String, String) {
+    let out = Command::new(env!("CARGO_BIN_EXE_hsm-trace")).args(args).output().unwrap();
+    (out.status.success(), String::from_utf8(out.stdout).unwrap(), String::from_utf8(out.stderr).unwrap())
+}
+
+fn trace_file(name: &str) -> String {
+    let path = ::std::env::temp_dir().join(format!("hsm-trace-{}-{}.jsonl", name, ::std::process::id()));
+    let lines: Vec<String> = traced().iter().map(|r| serde_json::to_string(r).unwrap()).collect();
+    fs::write(&path, lines.join("\n")).unwrap();
+    path.to_str().unwrap().to_string()
+}
+
+#[test]
+fn the_cli_prints_and_filters_traces() {
+    let file = trace_file("print");
+    let (ok, out, _) = hsm_trace(&[&file]);
+    assert!(ok);
+    let lines: Vec<&str> = out.lines().map(|l| l.trim_start()).collect();
+    assert_eq!(lines.len(), 6);
+    assert!(lines[0].ends_with("start  enter [Door, Closed]  -> Closed"), "{}", out);
+    assert!(lines[3].ends_with("Open UNHANDLED at Door  -> Opened"), "{}", out);
+    assert!(lines[5].ends_with("stop  exit [Closed, Door]  -> Closed"), "{}", out);
+
+    let (_, out, _) = hsm_trace(&["--event", "Digit", &file]);
+    assert_eq!(out.lines().count(), 1);
+    assert!(out.contains("Digit(3) by Opened"), "{}", out);
+    let (_, out, _) = hsm_trace(&["--state", "Opened", &file]);
+    assert_eq!(out.lines().count(), 4, "{}", out);
+    fs::remove_file(&file).unwrap();
+}
+
+#[test]
+fn the_cli_computes_statistics() {
+    let file = trace_file("stats");
+    let (ok, out, _) = hsm_trace(&["--stats", &file]);
+    assert!(ok);
+    let row = |name: &str| out.lines().find(|l| l.split_whitespace().next() == Some(name)).map(|l| l.split_whitespace().collect::<Vec<_>>());
+    assert_eq!(row("Door").unwrap()[1..4], ["0", "1", "1"]);
+    assert_eq!(row("Opened").unwrap()[1..4], ["2", "0", "1"]);
+    assert_eq!(row("Closed").unwrap()[1..4], ["1", "0", "2"]);
+    assert_eq!(row("Digit").unwrap()[1], "1");
+    assert_eq!(row("Open").unwrap()[1], "2");
+    fs::remove_file(&file).unwrap();
+}
+
+#[test]
+fn the_cli_reports_bad_arguments_and_files() {
+    let (ok, _, err) = hsm_trace(&["--bogus"]);
+    assert!(!ok);
+    assert!(err.starts_with("unknown option --bogus"), "{}", err);
+    let (ok, _, err) = hsm_trace(&["/nonexistent/trace.jsonl"]);
+    assert!(!ok);
+    assert!(err.starts_with("hsm-trace: "), "{}", err);
+}
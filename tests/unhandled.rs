@@ -0,0 +1,143 @@
+/*
+ * The MIT License (MIT)
+ *
+ * Copyright (c) 2015 Mattis Marjak (mattis.marjak@gmail.com)
+ *
+ * Permission is hereby granted, free of charge, to any person obtaining a copy
+ * of this software and associated documentation files (the "Software"), to deal
+ * in the Software without restriction, including without limitation the rights
+ * to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+ * copies of the Software, and to permit persons to whom the Software is
+ * furnished to do so, subject to the following conditions:
+ *
+ * The above copyright notice and this permission notice shall be included in all
+ * copies or substantial portions of the Software.
+ *
+ * THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+ * IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+ * FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+ * AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+ * LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+ * OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+ * SOFTWARE.
+ */
+
+#[macro_use]
+extern crate hsm;
+
+use std::cell::RefCell;
+use std::rc::Rc;
+use std::sync::{Arc, Mutex};
+use hsm::{InputError, Listener, UnhandledPolicy};
+
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Events {
+    // Bubbles up past the root
+    Stray,
+    // Given up on by Child, Root never sees it
+    Foreign,
+}
+
+#[derive(Debug)]
+pub struct Log {
+    seen: Vec<String>,
+}
+
+hsm_define_objects!(StateStruct, States, Events, Log, (
+    Root {},
+    Child {}
+));
+hsm_state_parents!(States; Root -> None, Child -> Root);
+
+hsm_impl_state!(Root, Events, States, Log, log, evt, probe,
+    hsm::Event::User(Events::Foreign) => {
+        log.seen.push("Root took Foreign".to_string());
+        hsm::Action::Ignore
+    },
+    hsm::Event::Exit(_) => {
+        log.seen.push("exit Root".to_string());
+        hsm::Action::Ignore
+    },
+    _ => hsm::Action::Parent
+);
+
+hsm_impl_state!(Child, Events, States, Log, log, evt, probe,
+    hsm::Event::User(Events::Foreign) => hsm::Action::Unhandled,
+    hsm::Event::Exit(_) => {
+        log.seen.push("exit Child".to_string());
+        hsm::Action::Ignore
+    },
+    _ => hsm::Action::Parent
+);
+
+type Machine = hsm::StateMachine<StateStruct, States, Events, Log>;
+
+#[derive(Debug, Default)]
+struct Unhandled {
+    seen: Vec<(States, Events)>,
+}
+impl Listener<States, Events> for Unhandled {
+    fn unhandled(&mut self, state: &States, evt: &Events) {
+        self.seen.push((state.clone(), evt.clone()));
+    }
+}
+
+fn started(policy: Option<UnhandledPolicy<States, Events>>) -> (Machine, Arc<Mutex<Unhandled>>) {
+    let mut sm = Machine::new(States::Child, Log { seen: Vec::new() });
+    if let Some(policy) = policy {
+        sm.set_unhandled_policy(policy);
+    }
+    let listener = Arc::new(Mutex::new(Unhandled::default()));
+    sm.add_listener(Box::new(listener.clone()));
+    sm.start();
+    (sm, listener)
+}
+
+fn unhandled(listener: &Arc<Mutex<Unhandled>>) -> Vec<(States, Events)> {
+    listener.lock().unwrap().seen.clone()
+}
+
+#[test]
+fn unhandled_events_are_errors_by_default() {
+    let (mut sm, listener) = started(None);
+    assert_eq!(sm.input(Events::Stray), Err(InputError::Unhandled { state: States::Root, event: Events::Stray }));
+    assert_eq!(sm.input(Events::Foreign), Err(InputError::Unhandled { state: States::Child, event: Events::Foreign }));
+    assert_eq!(unhandled(&listener), [(States::Root, Events::Stray), (States::Child, Events::Foreign)]);
+    assert_eq!(format!("{}", InputError::Unhandled { state: States::Root, event: Events::Stray }),
+               "event Stray was not handled, state Root gave up on it");
+}
+
+#[test]
+fn unhandled_events_leave_the_machine_alone() {
+    let (mut sm, _) = started(None);
+    sm.input(Events::Stray).ok();
+    sm.input(Events::Foreign).ok();
+    // Root isn't asked about Foreign and nothing is exited
+    assert_eq!(*sm.current(), States::Child);
+    assert!(sm.shared_data().seen.is_empty());
+}
+
+#[test]
+fn ignore_and_log_drop_unhandled_events() {
+    for policy in [UnhandledPolicy::Ignore, UnhandledPolicy::Log] {
+        let (mut sm, listener) = started(Some(policy));
+        assert_eq!(sm.input(Events::Stray), Ok(()));
+        assert_eq!(sm.input(Events::Foreign), Ok(()));
+        assert_eq!(unhandled(&listener), [(States::Root, Events::Stray), (States::Child, Events::Foreign)]);
+    }
+}
+
+#[test]
+fn the_callback_gets_the_state_that_gave_up() {
+    let called = Rc::new(RefCell::new(Vec::new()));
+    let seen = called.clone();
+    let policy = UnhandledPolicy::Callback(Rc::new(move |state: &States, evt: &Events| {
+        seen.borrow_mut().push((state.clone(), evt.clone()));
+    }));
+    let (mut sm, listener) = started(Some(policy));
+    assert_eq!(sm.input(Events::Foreign), Ok(()));
+    assert_eq!(sm.input(Events::Stray), Ok(()));
+    assert_eq!(*called.borrow(), [(States::Child, Events::Foreign), (States::Root, Events::Stray)]);
+    assert_eq!(unhandled(&listener).len(), 2);
+}
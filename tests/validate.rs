@@ -0,0 +1,73 @@
+/*
+ * The MIT License (MIT)
+ *
+ * Copyright (c) 2015 Mattis Marjak (mattis.marjak@gmail.com)
+ *
+ * Permission is hereby granted, free of charge, to any person obtaining a copy
+ * of this software and associated documentation files (the "Software"), to deal
+ * in the Software without restriction, including without limitation the rights
+ * to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+ * copies of the Software, and to permit persons to whom the Software is
+ * furnished to do so, subject to the following conditions:
+ *
+ * The above copyright notice and this permission notice shall be included in all
+ * copies or substantial portions of the Software.
+ *
+ * THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+ * IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+ * FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+ * AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+ * LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+ * OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+ * SOFTWARE.
+ */
+
+#[macro_use]
+extern crate hsm;
+
+use hsm::ValidationError;
+
+
+#[derive(Debug, Clone)]
+pub enum Events {
+    Go,
+}
+
+hsm_define_objects!(StateStruct, States, Events, (), (
+    Outer,
+    Inner
+));
+
+hsm_impl_state!(Outer, Events, States, (),
+    _ => hsm::Action::Ignore
+);
+
+hsm_impl_state!(Inner, Events, States, (),
+    _ => hsm::Action::Parent
+);
+
+hsm_state_parents!(States ; Outer -> None, Inner -> Outer);
+
+#[test]
+fn validate_reports_a_composite_initial_state() {
+    let sm = hsm::StateMachine::<StateStruct, States, Events, ()>::new(States::Outer, ());
+    assert_eq!(sm.validate(), Err(ValidationError::InitialNotLeaf { initial: States::Outer, child: States::Inner }));
+    let sm = hsm::StateMachine::<StateStruct, States, Events, ()>::new(States::Inner, ());
+    assert_eq!(sm.validate(), Ok(()));
+}
+
+#[test]
+#[cfg(debug_assertions)]
+#[should_panic(expected = "Invalid state machine: initial state Outer is not a leaf, Inner is its child")]
+fn start_rejects_an_invalid_hierarchy() {
+    let mut sm = hsm::StateMachine::<StateStruct, States, Events, ()>::new(States::Outer, ());
+    sm.start();
+}
+
+#[test]
+fn start_accepts_a_valid_hierarchy() {
+    let mut sm = hsm::StateMachine::<StateStruct, States, Events, ()>::new(States::Inner, ());
+    sm.start();
+    sm.input(Events::Go).unwrap();
+    assert_eq!(*sm.current(), States::Inner);
+}